@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions(id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, tag)
+);
+
+CREATE TABLE segments (
+    segment_id uuid PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    tag TEXT NULL,
+    subscribed_from DATE NULL,
+    subscribed_until DATE NULL,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TABLE custom_fields (
+    field_id uuid PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    field_type TEXT NOT NULL,
+    options TEXT[] NOT NULL,
+    required BOOLEAN NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE subscriber_custom_fields (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions(id),
+    field_id uuid NOT NULL
+        REFERENCES custom_fields(field_id) ON DELETE CASCADE,
+    value TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, field_id)
+);
+
+ALTER TABLE segments ADD COLUMN field_name TEXT NULL;
+ALTER TABLE segments ADD COLUMN field_value TEXT NULL;
@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE newsletter_issues (
+    newsletter_issue_id uuid PRIMARY KEY,
+    title TEXT NOT NULL,
+    content_format TEXT NOT NULL,
+    content_markdown TEXT NOT NULL,
+    content_html TEXT NOT NULL,
+    content_text TEXT NOT NULL,
+    segment_id uuid NULL,
+    status TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    published_at timestamptz NULL
+);
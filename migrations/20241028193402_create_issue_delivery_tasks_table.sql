@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE issue_delivery_tasks (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    subscriber_email TEXT NOT NULL,
+    status TEXT NOT NULL,
+    n_retries INT NOT NULL,
+    execute_after timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
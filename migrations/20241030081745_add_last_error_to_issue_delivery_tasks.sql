@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE issue_delivery_tasks ADD COLUMN last_error TEXT NULL;
@@ -0,0 +1,12 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE issue_delivery_tasks ADD COLUMN tracking_token TEXT NULL UNIQUE;
+CREATE TABLE issue_opens (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    opened_at timestamptz NOT NULL,
+    user_agent TEXT NULL
+);
+CREATE INDEX issue_opens_newsletter_issue_id_idx ON issue_opens (newsletter_issue_id);
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE issue_clicks (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    url TEXT NOT NULL,
+    clicked_at timestamptz NOT NULL,
+    user_agent TEXT NULL
+);
+CREATE INDEX issue_clicks_newsletter_issue_id_idx ON issue_clicks (newsletter_issue_id);
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues ADD COLUMN utm_source TEXT NULL;
+ALTER TABLE newsletter_issues ADD COLUMN utm_medium TEXT NULL;
+ALTER TABLE newsletter_issues ADD COLUMN utm_campaign TEXT NULL;
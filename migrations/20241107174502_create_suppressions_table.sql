@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE suppressions (
+    email TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    source TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+INSERT INTO suppressions (email, reason, source, created_at)
+SELECT lower(email), status, 'postmark', now()
+FROM subscriptions
+WHERE status IN ('bounced', 'complained')
+ON CONFLICT DO NOTHING;
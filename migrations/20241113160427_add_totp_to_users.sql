@@ -0,0 +1,14 @@
+-- Add migration script here
+-- The secret is encrypted, prefixed with its nonce. It is stored before being confirmed with a first code.
+ALTER TABLE users ADD COLUMN totp_secret BYTEA NULL;
+ALTER TABLE users ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT false;
+-- The time step of the last accepted code, so that a code cannot be replayed.
+ALTER TABLE users ADD COLUMN totp_last_time_step BIGINT NULL;
+
+CREATE TABLE recovery_codes (
+    recovery_code_id uuid PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL
+);
+CREATE INDEX recovery_codes_user_id_idx ON recovery_codes (user_id);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Sessions remember the epoch they were created in; bumping it logs the user out everywhere.
+ALTER TABLE users ADD COLUMN session_epoch INTEGER NOT NULL DEFAULT 0;
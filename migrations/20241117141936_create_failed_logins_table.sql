@@ -0,0 +1,20 @@
+-- Add migration script here
+-- Consecutive failed logins, counted per submitted username and per client IP.
+CREATE TABLE failed_logins (
+    scope TEXT NOT NULL CHECK (scope IN ('username', 'ip')),
+    key TEXT NOT NULL,
+    failures INTEGER NOT NULL,
+    last_failure_at timestamptz NOT NULL,
+    locked_until timestamptz NULL,
+    PRIMARY KEY (scope, key)
+);
+
+-- The audit trail of lockouts.
+CREATE TABLE login_lockouts (
+    lockout_id uuid PRIMARY KEY,
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    failures INTEGER NOT NULL,
+    locked_until timestamptz NOT NULL,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- One row per admin session, so that sessions can be listed and revoked.
+-- The session state itself stays in Redis.
+CREATE TABLE user_sessions (
+    session_id uuid PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    user_agent TEXT NULL,
+    ip TEXT NULL,
+    created_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    revoked_at timestamptz NULL
+);
+
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
@@ -0,0 +1,15 @@
+-- Add migration script here
+ALTER TABLE subscriber_tags
+    DROP CONSTRAINT subscriber_tags_subscriber_id_fkey,
+    ADD CONSTRAINT subscriber_tags_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions(id) ON DELETE CASCADE;
+
+CREATE TABLE subscriber_lists (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions(id) ON DELETE CASCADE,
+    list_name TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, list_name)
+);
+
+ALTER TABLE segments ADD COLUMN list_name TEXT NULL;
+ALTER TABLE segments ADD COLUMN engaged_within_days INT NULL;
@@ -0,0 +1,5 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD COLUMN unsubscribe_token TEXT NULL;
+UPDATE subscriptions SET unsubscribe_token = replace(gen_random_uuid()::text, '-', '');
+ALTER TABLE subscriptions ALTER COLUMN unsubscribe_token SET NOT NULL;
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_unsubscribe_token_key UNIQUE (unsubscribe_token);
@@ -0,0 +1,17 @@
+-- Add migration script here
+ALTER TABLE issue_opens
+    DROP CONSTRAINT issue_opens_newsletter_issue_id_fkey,
+    ADD CONSTRAINT issue_opens_newsletter_issue_id_fkey
+        FOREIGN KEY (newsletter_issue_id) REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    DROP CONSTRAINT issue_opens_subscriber_id_fkey,
+    ADD CONSTRAINT issue_opens_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE CASCADE,
+    ALTER COLUMN user_agent TYPE VARCHAR(512) USING left(user_agent, 512);
+ALTER TABLE issue_clicks
+    DROP CONSTRAINT issue_clicks_newsletter_issue_id_fkey,
+    ADD CONSTRAINT issue_clicks_newsletter_issue_id_fkey
+        FOREIGN KEY (newsletter_issue_id) REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    DROP CONSTRAINT issue_clicks_subscriber_id_fkey,
+    ADD CONSTRAINT issue_clicks_subscriber_id_fkey
+        FOREIGN KEY (subscriber_id) REFERENCES subscriptions (id) ON DELETE CASCADE,
+    ALTER COLUMN user_agent TYPE VARCHAR(512) USING left(user_agent, 512);
@@ -0,0 +1,8 @@
+-- Add migration script here
+-- Recent password reset requests, to rate limit them per username and per client IP.
+CREATE TABLE password_reset_requests (
+    username TEXT NOT NULL,
+    ip TEXT NULL,
+    requested_at timestamptz NOT NULL
+);
+CREATE INDEX password_reset_requests_requested_at_idx ON password_reset_requests (requested_at);
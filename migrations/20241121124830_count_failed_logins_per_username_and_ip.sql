@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Failures are now counted per username and client IP pair, so nobody can lock another user out.
+DELETE FROM failed_logins WHERE scope = 'username';
+ALTER TABLE failed_logins
+    DROP CONSTRAINT failed_logins_scope_check,
+    ADD CONSTRAINT failed_logins_scope_check CHECK (scope IN ('username_ip', 'ip'));
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Sessions are invalidated through the user_sessions index only.
+ALTER TABLE users DROP COLUMN session_epoch;
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Failures are counted per username again, alongside the username and client IP pair,
+-- so that guesses spread over many addresses are slowed down too.
+ALTER TABLE failed_logins
+    DROP CONSTRAINT failed_logins_scope_check,
+    ADD CONSTRAINT failed_logins_scope_check CHECK (scope IN ('username', 'username_ip', 'ip'));
@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+set -eo pipefail
+
+# Rebuild the bundled list of breached passwords from a top-N password list,
+# one password per line, most common first (e.g. a list derived from Pwned Passwords).
+# Usage: ./scripts/build_breached_passwords.sh passwords.txt [N]
+
+if [ -z "$1" ]; then
+    echo >&2 "Usage: $0 passwords.txt [N]"
+    exit 1
+fi
+
+PASSWORDS="$1"
+TOP_N="${2:-100000}"
+OUTPUT="$(dirname "$0")/../src/authentication/breached_passwords.txt"
+
+{
+    echo "# Uppercase SHA-1 hashes of passwords known from breaches and common-password lists, sorted."
+    echo "# Passwords shorter than the minimum length are left out, they are refused anyway."
+    # Keep the N most common passwords the password policy would otherwise accept.
+    head -n "${TOP_N}" "${PASSWORDS}" \
+        | perl -CS -MDigest::SHA=sha1_hex -nle 'print uc sha1_hex(do { utf8::encode(my $p = $_); $p }) if length($_) >= 12' \
+        | LC_ALL=C sort -u
+} > "${OUTPUT}"
+
+echo >&2 "Wrote $(grep -vc '^#' "${OUTPUT}") hashes to ${OUTPUT}"
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use sha1::{Digest, Sha1};
+
+/// Stored as SHA-1 hashes, the format breach corpora such as Pwned Passwords are published in,
+/// so the list can be extended from them without handling the passwords themselves.
+/// `scripts/build_breached_passwords.sh` rebuilds it from a top-N password list.
+const BREACHED_PASSWORD_HASHES: &str = include_str!("breached_passwords.txt");
+
+/// Hashes are bucketed by their first characters, like the Pwned Passwords range API,
+/// each bucket holding the sorted remainders of its hashes.
+const PREFIX_LENGTH: usize = 5;
+
+static BREACHED_PASSWORDS: Lazy<HashMap<&'static str, Vec<&'static str>>> = Lazy::new(|| {
+    let mut buckets: HashMap<&str, Vec<&str>> = HashMap::new();
+    for hash in BREACHED_PASSWORD_HASHES.lines().filter(|line| !line.starts_with('#') && !line.is_empty()) {
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+        buckets.entry(prefix).or_default().push(suffix);
+    }
+    for suffixes in buckets.values_mut() {
+        suffixes.sort_unstable();
+    }
+    buckets
+});
+
+/// Whether `password`, or its lowercase form, is in the bundled list of breached and common passwords.
+pub fn is_breached(password: &str) -> bool {
+    [sha1_hex(password), sha1_hex(&password.to_lowercase())]
+        .iter()
+        .any(|hash| {
+            let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+            BREACHED_PASSWORDS
+                .get(prefix)
+                .is_some_and(|suffixes| suffixes.binary_search(&suffix).is_ok())
+        })
+}
+
+fn sha1_hex(password: &str) -> String {
+    hex::encode_upper(Sha1::digest(password.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_breached, BREACHED_PASSWORD_HASHES};
+
+    #[test]
+    fn common_passwords_are_breached() {
+        assert!(is_breached("password1234"));
+        assert!(is_breached("correcthorsebatterystaple"));
+    }
+
+    #[test]
+    fn the_check_ignores_case() {
+        assert!(is_breached("PASSWORD1234"));
+    }
+
+    #[test]
+    fn other_passwords_are_not_breached() {
+        assert!(!is_breached("a-long-enough-password"));
+    }
+
+    #[test]
+    fn the_list_only_holds_uppercase_sha1_hashes() {
+        for hash in BREACHED_PASSWORD_HASHES.lines().filter(|line| !line.starts_with('#')) {
+            assert_eq!(hash.len(), 40, "{}", hash);
+            assert!(hash.chars().all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c)), "{}", hash);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+
+use crate::startup::HmacSecret;
+
+/// The form field the token is submitted in.
+pub const CSRF_TOKEN_FIELD: &str = "csrf_token";
+
+/// The token every state-changing admin form must carry, set by `reject_forged_requests`.
+#[derive(Clone, Debug)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    /// The hidden input to put in every form posting to `/admin`.
+    pub fn form_field(&self) -> String {
+        format!(
+            "<input hidden type=\"text\" name=\"{}\" value=\"{}\">",
+            CSRF_TOKEN_FIELD, self.0
+        )
+    }
+}
+
+impl AsRef<str> for CsrfToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The token of the indexed session `session_id`. It is derived rather than stored,
+/// and another site cannot compute it without the secret.
+pub fn csrf_token(session_id: Uuid, secret: &HmacSecret) -> CsrfToken {
+    CsrfToken(hex::encode(csrf_mac(session_id, secret).finalize().into_bytes()))
+}
+
+/// Whether `candidate` is the token of `session_id`, compared in constant time.
+pub fn verify_csrf_token(session_id: Uuid, candidate: &str, secret: &HmacSecret) -> bool {
+    let Ok(tag) = hex::decode(candidate) else {
+        return false;
+    };
+    csrf_mac(session_id, secret).verify_slice(&tag).is_ok()
+}
+
+fn csrf_mac(session_id: Uuid, secret: &HmacSecret) -> Hmac<sha2::Sha256> {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.0.expose_secret().as_bytes()).unwrap();
+    mac.update(b"csrf.");
+    mac.update(session_id.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{csrf_token, verify_csrf_token};
+    use crate::startup::HmacSecret;
+
+    fn secret() -> HmacSecret {
+        HmacSecret(Secret::new("a-long-enough-secret-for-the-tests".into()))
+    }
+
+    #[test]
+    fn the_token_of_a_session_is_accepted() {
+        let session_id = Uuid::new_v4();
+        let token = csrf_token(session_id, &secret());
+        assert!(verify_csrf_token(session_id, token.as_ref(), &secret()));
+    }
+
+    #[test]
+    fn the_token_of_another_session_is_rejected() {
+        let token = csrf_token(Uuid::new_v4(), &secret());
+        assert!(!verify_csrf_token(Uuid::new_v4(), token.as_ref(), &secret()));
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        assert!(!verify_csrf_token(Uuid::new_v4(), "", &secret()));
+        assert!(!verify_csrf_token(Uuid::new_v4(), "not-hex", &secret()));
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Failures older than this are forgotten.
+const FAILURE_WINDOW_MINUTES: i32 = 60;
+
+/// How many failures are tolerated before attempts are slowed down, then refused.
+struct LockoutPolicy {
+    scope: &'static str,
+    free_attempts: i32,
+    lockout_threshold: i32,
+    lockout_minutes: i64,
+}
+
+impl LockoutPolicy {
+    /// How long to refuse attempts after `failures` consecutive failures: nothing at first,
+    /// then a delay doubling with each failure, then a lockout.
+    fn lock_duration(&self, failures: i32) -> Option<Duration> {
+        if failures <= self.free_attempts {
+            None
+        } else if failures >= self.lockout_threshold {
+            Some(self.lockout())
+        } else {
+            Some(Duration::seconds(1 << (failures - self.free_attempts - 1).min(6)))
+        }
+    }
+
+    fn lockout(&self) -> Duration {
+        Duration::minutes(self.lockout_minutes)
+    }
+}
+
+const USERNAME_IP_POLICY: LockoutPolicy = LockoutPolicy {
+    scope: "username_ip",
+    free_attempts: 3,
+    lockout_threshold: 10,
+    lockout_minutes: 15,
+};
+
+/// Looser, since failures from any address count, so anyone can cause them.
+const USERNAME_POLICY: LockoutPolicy = LockoutPolicy {
+    scope: "username",
+    free_attempts: 10,
+    lockout_threshold: 50,
+    lockout_minutes: 15,
+};
+
+/// Looser, since several admins may share an IP address.
+const IP_POLICY: LockoutPolicy = LockoutPolicy {
+    scope: "ip",
+    free_attempts: 10,
+    lockout_threshold: 50,
+    lockout_minutes: 15,
+};
+
+/// What a login attempt is counted against. Usernames are counted whether they exist or not,
+/// so lockouts do not reveal which accounts exist.
+/// `ip` is the address of the client, see [`crate::utils::client_ip`].
+pub struct LoginAttempt<'a> {
+    pub username: &'a str,
+    pub ip: Option<&'a str>,
+}
+
+impl LoginAttempt<'_> {
+    /// Failures from one client IP lock the username out there long before they do everywhere.
+    fn username_ip_key(&self) -> String {
+        format!("{}/{}", self.ip.unwrap_or("unknown"), self.username)
+    }
+
+    fn keys(&self) -> Vec<(&'static LockoutPolicy, String)> {
+        let mut keys = vec![
+            (&USERNAME_IP_POLICY, self.username_ip_key()),
+            (&USERNAME_POLICY, self.username.to_owned()),
+        ];
+        if let Some(ip) = self.ip {
+            keys.push((&IP_POLICY, ip.to_owned()));
+        }
+        keys
+    }
+}
+
+/// Whether an attempt may go ahead.
+pub enum LoginThrottle {
+    /// Attempts are refused until then. This one was not counted.
+    LockedOut(DateTime<Utc>),
+    /// The attempt was counted as a failure, to be cleared if it succeeds.
+    Counted(CountedAttempt),
+}
+
+/// The lockout an attempt leads to if it fails.
+pub struct CountedAttempt {
+    locked_until: Option<DateTime<Utc>>,
+    lockouts: Vec<Lockout>,
+}
+
+struct Lockout {
+    scope: &'static str,
+    key: String,
+    failures: i32,
+    locked_until: DateTime<Utc>,
+}
+
+/// Count an attempt as a failure before its credentials are checked, unless attempts are refused.
+/// The check and the count are one statement per counter, so concurrent attempts cannot get past
+/// a lockout; the lockout they lead to is set in the same transaction, while the rows are locked.
+#[tracing::instrument(
+    name="Count a login attempt.",
+    skip(attempt, pool),
+    fields(username=%attempt.username, ip=?attempt.ip)
+)]
+pub async fn count_login_attempt(attempt: &LoginAttempt<'_>, pool: &PgPool) -> anyhow::Result<LoginThrottle> {
+    let mut transaction = pool.begin().await.context("Failed to begin a transaction.")?;
+    let mut refused_until = None;
+    let mut counted = CountedAttempt {
+        locked_until: None,
+        lockouts: Vec::new(),
+    };
+    for (policy, key) in attempt.keys() {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO failed_logins (scope, key, failures, last_failure_at)
+            VALUES ($1, $2, 1, now())
+            ON CONFLICT (scope, key) DO UPDATE SET
+                failures = CASE
+                    WHEN failed_logins.last_failure_at < now() - make_interval(mins => $3) THEN 1
+                    ELSE failed_logins.failures + 1
+                END,
+                last_failure_at = now()
+            WHERE failed_logins.locked_until IS NULL OR failed_logins.locked_until <= now()
+            RETURNING failures
+            "#,
+            policy.scope,
+            &key,
+            FAILURE_WINDOW_MINUTES,
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to count a login attempt.")?;
+        let Some(row) = row else {
+            // Locked out: the conflicting row is locked by the upsert, so it can be read back as is.
+            let locked_until = sqlx::query_scalar!(
+                r#"SELECT locked_until AS "locked_until!" FROM failed_logins WHERE scope = $1 AND key = $2"#,
+                policy.scope,
+                &key,
+            )
+            .fetch_one(&mut *transaction)
+            .await
+            .context("Failed to read a login lockout.")?;
+            refused_until = refused_until.max(Some(locked_until));
+            continue;
+        };
+        let Some(duration) = policy.lock_duration(row.failures) else {
+            continue;
+        };
+        let until = Utc::now() + duration;
+        sqlx::query!(
+            r#"UPDATE failed_logins SET locked_until = $3 WHERE scope = $1 AND key = $2"#,
+            policy.scope,
+            &key,
+            until,
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to lock logins out.")?;
+        if duration == policy.lockout() {
+            counted.lockouts.push(Lockout {
+                scope: policy.scope,
+                key,
+                failures: row.failures,
+                locked_until: until,
+            });
+        }
+        counted.locked_until = counted.locked_until.max(Some(until));
+    }
+    if let Some(locked_until) = refused_until {
+        // Nothing is counted against the other keys either.
+        transaction.rollback().await.context("Failed to roll back a transaction.")?;
+        return Ok(LoginThrottle::LockedOut(locked_until));
+    }
+    transaction.commit().await.context("Failed to commit a transaction.")?;
+    Ok(LoginThrottle::Counted(counted))
+}
+
+/// Record the lockouts a failed attempt led to.
+/// Returns until when attempts are now refused, if they are.
+pub async fn record_failed_login(attempt: CountedAttempt, pool: &PgPool) -> anyhow::Result<Option<DateTime<Utc>>> {
+    for lockout in &attempt.lockouts {
+        record_lockout(lockout, pool).await?;
+    }
+    Ok(attempt.locked_until)
+}
+
+async fn record_lockout(lockout: &Lockout, pool: &PgPool) -> anyhow::Result<()> {
+    tracing::warn!(
+        scope = lockout.scope,
+        key = lockout.key,
+        failures = lockout.failures,
+        locked_until = %lockout.locked_until,
+        "Locked logins out after repeated failures."
+    );
+    sqlx::query!(
+        r#"
+        INSERT INTO login_lockouts (lockout_id, scope, key, failures, locked_until, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        Uuid::new_v4(),
+        lockout.scope,
+        lockout.key,
+        lockout.failures,
+        lockout.locked_until,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a login lockout.")?;
+    Ok(())
+}
+
+/// Forget the failures of a username after a successful login, and take back the attempt
+/// counted against the IP address.
+/// The earlier failures of the IP address are kept, so that one valid account cannot be used to reset them.
+#[tracing::instrument(
+    name="Clear failed logins.",
+    skip(attempt, pool),
+    fields(username=%attempt.username, ip=?attempt.ip)
+)]
+pub async fn clear_failed_logins(attempt: &LoginAttempt<'_>, pool: &PgPool) -> anyhow::Result<()> {
+    let mut transaction = pool.begin().await.context("Failed to begin a transaction.")?;
+    sqlx::query!(
+        r#"
+        DELETE FROM failed_logins
+        WHERE (scope = 'username_ip' AND key = $1) OR (scope = 'username' AND key = $2)
+        "#,
+        attempt.username_ip_key(),
+        attempt.username,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to clear failed logins.")?;
+    if let Some(ip) = attempt.ip {
+        // The attempt was let through, so any lock it set on the IP address was its own.
+        sqlx::query!(
+            r#"
+            UPDATE failed_logins SET failures = failures - 1, locked_until = NULL
+            WHERE scope = 'ip' AND key = $1 AND failures > 0
+            "#,
+            ip,
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to take back a login attempt.")?;
+    }
+    transaction.commit().await.context("Failed to commit a transaction.")?;
+    Ok(())
+}
+
+/// A user-facing description of the time left before `locked_until`, e.g. `5 minutes`.
+pub fn time_left(locked_until: DateTime<Utc>) -> String {
+    let seconds = (locked_until - Utc::now()).num_seconds().max(1);
+    match seconds {
+        1 => "1 second".into(),
+        2..=59 => format!("{} seconds", seconds),
+        _ => {
+            let minutes = (seconds + 59) / 60;
+            if minutes == 1 { "1 minute".into() } else { format!("{} minutes", minutes) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::{time_left, IP_POLICY, USERNAME_IP_POLICY, USERNAME_POLICY};
+
+    #[test]
+    fn the_first_failures_are_free() {
+        for failures in 1..=3 {
+            assert_eq!(USERNAME_IP_POLICY.lock_duration(failures), None);
+        }
+        assert_eq!(USERNAME_POLICY.lock_duration(10), None);
+        assert_eq!(IP_POLICY.lock_duration(10), None);
+    }
+
+    #[test]
+    fn delays_double_with_each_failure_until_the_lockout() {
+        assert_eq!(USERNAME_IP_POLICY.lock_duration(4), Some(Duration::seconds(1)));
+        assert_eq!(USERNAME_IP_POLICY.lock_duration(5), Some(Duration::seconds(2)));
+        assert_eq!(USERNAME_IP_POLICY.lock_duration(9), Some(Duration::seconds(32)));
+        assert_eq!(USERNAME_IP_POLICY.lock_duration(10), Some(Duration::minutes(15)));
+        assert_eq!(USERNAME_IP_POLICY.lock_duration(42), Some(Duration::minutes(15)));
+        assert_eq!(USERNAME_POLICY.lock_duration(50), Some(Duration::minutes(15)));
+        assert_eq!(IP_POLICY.lock_duration(49), Some(Duration::seconds(64)));
+    }
+
+    #[test]
+    fn the_time_left_is_rounded_up_to_the_minute() {
+        assert_eq!(time_left(Utc::now() - Duration::seconds(5)), "1 second");
+        assert!(time_left(Utc::now() + Duration::seconds(30)).ends_with("seconds"));
+        assert_eq!(time_left(Utc::now() + Duration::seconds(14 * 60 + 30)), "15 minutes");
+    }
+}
@@ -1,16 +1,20 @@
 use std::ops::Deref;
 
-use actix_web::{body::MessageBody, dev::{ServiceRequest, ServiceResponse}, error::InternalError, HttpMessage};
+use actix_web::{body::{EitherBody, MessageBody}, dev::{self, ServiceRequest, ServiceResponse}, error::InternalError, HttpMessage};
+use actix_web::http::Method;
 use actix_web::middleware::Next;
-use actix_web::FromRequest;
+use actix_web::{web, FromRequest};
+use actix_web_flash_messages::FlashMessage;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{session_state::TypedSession, utils::{e500, see_other}};
+use crate::{authentication::{csrf::{csrf_token, verify_csrf_token, CSRF_TOKEN_FIELD}, revoke_session, sessions::is_activity_due, touch_session, Role}, configuration::{SessionSettings, TotpSettings}, routes::get_active_user, session_state::TypedSession, startup::HmacSecret, utils::{e403, e500, see_other}};
 
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
-) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
@@ -18,8 +22,61 @@ pub async fn reject_anonymous_users(
     
     match session.get_user_id().map_err(e500)? {
         Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
+            // Deactivated or deleted users lose access even if they are still logged in.
+            // The response is returned rather than raised so that the session and flash cookies are set.
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| e500("The database pool is not available"))?
+                .clone();
+            let session_settings = req
+                .app_data::<web::Data<SessionSettings>>()
+                .ok_or_else(|| e500("The session settings are not available"))?
+                .clone();
+            let now = Utc::now();
+            let last_seen_at = session.get_last_seen_at().map_err(e500)?;
+            let expiry = expiry_message(
+                session.get_logged_in_at().map_err(e500)?,
+                last_seen_at,
+                &session_settings,
+                now,
+            );
+            if let Some(message) = expiry {
+                if let Some(session_id) = session.get_session_id().map_err(e500)? {
+                    revoke_session(user_id, session_id, &pool).await.map_err(e500)?;
+                }
+                session.log_out();
+                FlashMessage::error(message).send();
+                return Ok(req.into_response(see_other("/login")).map_into_right_body());
+            }
+            // Writing the timestamp saves the session and sends a new cookie, so it is done at most
+            // once per interval, like the activity recorded in the database.
+            if last_seen_at.is_none_or(|last_seen_at| is_activity_due(last_seen_at, now)) {
+                session.insert_last_seen_at(now).map_err(e500)?;
+            }
+            let Some(user) = get_active_user(user_id, &pool).await.map_err(e500)? else {
+                session.log_out();
+                FlashMessage::error("Your account has been deactivated.").send();
+                return Ok(req.into_response(see_other("/login")).map_into_right_body());
+            };
+            let session_id = session.get_session_id().map_err(e500)?;
+            let is_live = match session_id {
+                Some(session_id) => touch_session(session_id, user_id, &pool).await.map_err(e500)?,
+                None => false,
+            };
+            if !is_live {
+                session.log_out();
+                FlashMessage::error("Your session has been revoked, please log in again.").send();
+                return Ok(req.into_response(see_other("/login")).map_into_right_body());
+            }
+            let totp_required = req
+                .app_data::<web::Data<TotpSettings>>()
+                .is_some_and(|settings| settings.required_for_owners);
+            if totp_required && user.role == Role::Owner && !user.totp_enabled && !is_totp_setup_path(req.path()) {
+                FlashMessage::error("Owners must set up two-factor authentication.").send();
+                return Ok(req.into_response(see_other("/admin/totp")).map_into_right_body());
+            }
+            req.extensions_mut().insert(UserId { user_id, role: user.role });
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
         },
         None => {
             let response = see_other("/login");
@@ -29,12 +86,131 @@ pub async fn reject_anonymous_users(
     }
 }
 
+/// Make the CSRF token of the session available to the handlers rendering forms, and refuse
+/// state-changing requests whose form does not carry it. Must run after `reject_anonymous_users`.
+pub async fn reject_forged_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let session_id = session
+        .get_session_id()
+        .map_err(e500)?
+        .ok_or_else(|| e403("The session is not indexed."))?;
+    let secret = req
+        .app_data::<web::Data<HmacSecret>>()
+        .ok_or_else(|| e500("The HMAC secret is not available"))?
+        .clone();
+    if !req.method().is_safe() {
+        // The form is read here and put back for the handler.
+        let body = req.extract::<web::Bytes>().await?;
+        let candidate = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body)
+            .ok()
+            .and_then(|fields| fields.into_iter().find(|(name, _)| name == CSRF_TOKEN_FIELD))
+            .map(|(_, value)| value);
+        if !candidate.is_some_and(|candidate| verify_csrf_token(session_id, &candidate, &secret)) {
+            return Err(e403("The CSRF token is missing or invalid."));
+        }
+        let (_, mut payload) = actix_http::h1::Payload::create(true);
+        payload.unread_data(body);
+        req.set_payload(dev::Payload::from(payload));
+    }
+    req.extensions_mut().insert(csrf_token(session_id, &secret));
+    next.call(req).await
+}
+
+/// Why the session has expired at `now`, if it has.
+fn expiry_message(
+    logged_in_at: Option<DateTime<Utc>>,
+    last_seen_at: Option<DateTime<Utc>>,
+    settings: &SessionSettings,
+    now: DateTime<Utc>,
+) -> Option<&'static str> {
+    // Sessions opened before the timestamps were recorded cannot be trusted to be recent.
+    let (Some(logged_in_at), Some(last_seen_at)) = (logged_in_at, last_seen_at) else {
+        return Some("Your session has expired, please log in again.");
+    };
+    if now - logged_in_at >= settings.absolute_timeout() {
+        Some("Your session has reached its maximum duration, please log in again.")
+    } else if now - last_seen_at >= settings.idle_timeout() {
+        Some("Your session has expired after a period of inactivity, please log in again.")
+    } else {
+        None
+    }
+}
+
+/// The pages an owner can reach before setting up two-factor authentication.
+fn is_totp_setup_path(path: &str) -> bool {
+    path == "/admin/logout" || path == "/admin/totp" || path.starts_with("/admin/totp/")
+}
+
+/// Let a user through only if their role allows the route they are requesting.
+/// Must run after `reject_anonymous_users`.
+pub async fn authorize_by_role(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let minimum = minimum_role(req.method(), req.path());
+    require_role(minimum, req, next).await
+}
+
+/// The least privileged role allowed on an admin route.
+/// Reading is open to every role; anything else needs an editor unless it is listed here.
+fn minimum_role(method: &Method, path: &str) -> Role {
+    if path == "/admin/users" || path.starts_with("/admin/users/") {
+        return Role::Owner;
+    }
+    if method == Method::GET {
+        // Forms that only editors can submit.
+        let is_editor_form = path == "/admin/newsletters"
+            || (path.starts_with("/admin/newsletters/issues/") && path.ends_with("/edit"));
+        return if is_editor_form { Role::Editor } else { Role::Viewer };
+    }
+    // Every user manages their own account.
+    let is_own_account = method == Method::POST
+        && matches!(
+            path,
+            "/admin/password"
+                | "/admin/logout"
+                | "/admin/sessions/revoke"
+                | "/admin/sessions/revoke-all"
+                | "/admin/totp/enroll"
+                | "/admin/totp/confirm"
+                | "/admin/totp/disable"
+        );
+    if is_own_account { Role::Viewer } else { Role::Editor }
+}
+
+async fn require_role<B: MessageBody>(
+    minimum: Role,
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<B>, actix_web::Error> {
+    let role = req.extensions().get::<UserId>().map(UserId::role);
+    match role {
+        Some(role) if role >= minimum => next.call(req).await,
+        _ => Err(e403(format!("This action requires the {} role.", minimum))),
+    }
+}
+
 #[derive(Clone, Debug, Copy)]
-pub struct UserId(Uuid);
+pub struct UserId {
+    user_id: Uuid,
+    role: Role,
+}
+
+impl UserId {
+    pub fn role(&self) -> Role {
+        self.role
+    }
+}
 
 impl std::fmt::Display for UserId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.user_id.fmt(f)
     }
 }
 
@@ -42,6 +218,70 @@ impl Deref for UserId {
     type Target = Uuid;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.user_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use actix_web::http::Method;
+
+    use super::{expiry_message, minimum_role};
+    use crate::authentication::Role;
+    use crate::configuration::SessionSettings;
+
+    fn settings() -> SessionSettings {
+        SessionSettings { idle_timeout_minutes: 30, absolute_timeout_minutes: 720 }
+    }
+
+    #[test]
+    fn an_active_session_has_not_expired() {
+        let now = Utc::now();
+        let logged_in_at = now - Duration::hours(11);
+        let last_seen_at = now - Duration::minutes(29);
+        assert_eq!(expiry_message(Some(logged_in_at), Some(last_seen_at), &settings(), now), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn an_idle_session_expires() {
+        let now = Utc::now();
+        let message = expiry_message(Some(now - Duration::hours(1)), Some(now - Duration::minutes(30)), &settings(), now);
+        assert!(message.unwrap().contains("inactivity"));
+    }
+
+    #[test]
+    fn a_session_expires_after_its_maximum_duration_even_if_active() {
+        let now = Utc::now();
+        let message = expiry_message(Some(now - Duration::hours(12)), Some(now), &settings(), now);
+        assert!(message.unwrap().contains("maximum duration"));
+    }
+
+    #[test]
+    fn a_session_without_timestamps_has_expired() {
+        assert!(expiry_message(None, None, &settings(), Utc::now()).is_some());
+    }
+
+    #[test]
+    fn pages_are_readable_by_every_role() {
+        assert_eq!(minimum_role(&Method::GET, "/admin/subscribers"), Role::Viewer);
+        assert_eq!(minimum_role(&Method::GET, "/admin/newsletters/issues/1/preview"), Role::Viewer);
+    }
+
+    #[test]
+    fn changes_need_an_editor_unless_they_are_to_the_own_account() {
+        assert_eq!(minimum_role(&Method::POST, "/admin/segments"), Role::Editor);
+        assert_eq!(minimum_role(&Method::POST, "/admin/some-new-route"), Role::Editor);
+        assert_eq!(minimum_role(&Method::DELETE, "/admin/password"), Role::Editor);
+        assert_eq!(minimum_role(&Method::GET, "/admin/newsletters/issues/1/edit"), Role::Editor);
+        assert_eq!(minimum_role(&Method::POST, "/admin/password"), Role::Viewer);
+        assert_eq!(minimum_role(&Method::POST, "/admin/totp/disable"), Role::Viewer);
+    }
+
+    #[test]
+    fn user_management_is_reserved_to_owners() {
+        assert_eq!(minimum_role(&Method::GET, "/admin/users"), Role::Owner);
+        assert_eq!(minimum_role(&Method::POST, "/admin/users/role"), Role::Owner);
+    }
+}
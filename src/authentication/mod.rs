@@ -1,5 +1,18 @@
+pub mod breached_passwords;
+pub mod csrf;
+pub mod lockout;
 pub mod middleware;
 pub mod password;
+pub mod password_reset;
+pub mod role;
+pub mod sessions;
+pub mod totp;
 
-pub use password::{AuthError, Credentials, change_password, validate_credentials};
-pub use middleware::{reject_anonymous_users, UserId};
\ No newline at end of file
+pub use lockout::{clear_failed_logins, count_login_attempt, record_failed_login, time_left, LoginAttempt, LoginThrottle};
+pub use password::{AuthError, Credentials, DummyPasswordHash, change_password, create_user, validate_credentials, validate_new_password};
+pub use csrf::{csrf_token, CsrfToken};
+pub use middleware::{authorize_by_role, reject_anonymous_users, reject_forged_requests, UserId};
+pub use password_reset::{allow_reset_request, generate_reset_token, get_reset_recipient, reset_password, verify_reset_token};
+pub use role::Role;
+pub use sessions::{complete_login, delete_stale_sessions, get_active_sessions, revoke_all_sessions, revoke_session, touch_session, UserSession};
+pub use totp::{confirm_totp_enrollment, disable_totp, get_totp_state, start_totp_enrollment, verify_second_factor, TotpState};
@@ -1,9 +1,13 @@
 use anyhow::Context;
 use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
+use crate::authentication::breached_passwords::is_breached;
+use crate::authentication::Role;
+use crate::configuration::PasswordHashingSettings;
 use crate::telemetry::spawn_blocking_with_tracing;
 
 #[derive(thiserror::Error, Debug)]
@@ -20,37 +24,111 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
+/// A hash to verify passwords against when the username is unknown, so that it takes as long as
+/// for existing users. It is computed at startup with the current hashing parameters.
+pub struct DummyPasswordHash(Secret<String>);
+
+impl DummyPasswordHash {
+    pub fn compute(hashing: &PasswordHashingSettings) -> anyhow::Result<Self> {
+        let password = Secret::new(Uuid::new_v4().to_string());
+        compute_password_hash(password, hashing).map(Self)
+    }
+}
+
 
 #[tracing::instrument(
     name="Validate credentials",
-    skip(credentials, pool)
+    skip(credentials, hashing, dummy_hash, pool)
 )]
 pub async fn validate_credentials(
     credentials: Credentials,
+    hashing: &PasswordHashingSettings,
+    dummy_hash: &DummyPasswordHash,
     pool: &PgPool,
 ) -> Result<Uuid, AuthError> {
     let mut user_id: Option<Uuid> = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-        gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno".to_string()
-    );
+    let mut expected_password_hash = dummy_hash.0.clone();
     if let Some((stored_user_id, stored_password_hash)) = 
-        get_stored_credentials(&credentials.username, &pool)
+        get_stored_credentials(&credentials.username, pool)
         .await?
         {
             user_id = Some(stored_user_id);
             expected_password_hash = stored_password_hash;
         }
 
-    let _ = spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+    let stored_password_hash = expected_password_hash.expose_secret().clone();
+    let hashing_settings = hashing.clone();
+    let upgraded_password_hash = spawn_blocking_with_tracing(move || -> Result<Option<Secret<String>>, AuthError> {
+        verify_password_hash(expected_password_hash.clone(), credentials.password.clone())?;
+        // The password is only known now, so this is the time to rehash it with the current parameters.
+        if !is_outdated(&expected_password_hash, &hashing_settings)? {
+            return Ok(None);
+        }
+        compute_password_hash(credentials.password, &hashing_settings)
+            .map(Some)
+            .map_err(AuthError::UnexpectedError)
     })
     .await
     .context("Failed to spawn blocking task.")??;
 
-    user_id.ok_or_else(|| 
-        AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))
+    let user_id = user_id.ok_or_else(|| 
+        AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))?;
+    if let Some(password_hash) = upgraded_password_hash {
+        // Logging in does not depend on the upgrade, it will be attempted again next time.
+        if let Err(e) = upgrade_password_hash(user_id, &stored_password_hash, password_hash, pool).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to upgrade a password hash."
+            );
+        }
+    }
+    Ok(user_id)
+}
+
+/// Whether a stored hash was computed with another algorithm, version or parameters than the current ones.
+fn is_outdated(password_hash: &Secret<String>, hashing: &PasswordHashingSettings) -> Result<bool, AuthError> {
+    let password_hash = PasswordHash::new(password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format.")
+        .map_err(AuthError::UnexpectedError)?;
+    let current = hashing
+        .params()
+        .context("Invalid password hashing parameters.")
+        .map_err(AuthError::UnexpectedError)?;
+    let Ok(stored) = Params::try_from(&password_hash) else {
+        return Ok(true);
+    };
+    Ok(password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+        || stored.m_cost() != current.m_cost()
+        || stored.t_cost() != current.t_cost()
+        || stored.p_cost() != current.p_cost())
+}
+
+/// Replace the password hash of a user, unless it changed since it was verified.
+#[tracing::instrument(
+    name="Upgrade password hash.",
+    skip(stored_password_hash, password_hash, pool)
+)]
+async fn upgrade_password_hash(
+    user_id: Uuid,
+    stored_password_hash: &str,
+    password_hash: Secret<String>,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3"#,
+        password_hash.expose_secret(),
+        user_id,
+        stored_password_hash,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to perform query to upgrade a password hash.")?;
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -62,7 +140,7 @@ async fn get_stored_credentials(username: &str, pool: &PgPool) -> Result<Option<
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1"#,
+        WHERE username = $1 AND is_active"#,
         username
     )
     .fetch_optional(pool)
@@ -76,7 +154,7 @@ async fn get_stored_credentials(username: &str, pool: &PgPool) -> Result<Option<
     name="Verify password hash",
     skip(expected_password_hash, password_candidate)
 )]
-fn verify_password_hash(
+pub fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>
 ) -> Result<(), AuthError> {
@@ -98,11 +176,17 @@ fn verify_password_hash(
 
 #[tracing::instrument(
     name="Change password.",
-    skip(password, pool)
+    skip(password, hashing, pool)
 )]
-pub async fn change_password(user_id: Uuid, password: Secret<String>, pool: &PgPool) -> anyhow::Result<()> {
+pub async fn change_password(
+    user_id: Uuid,
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    let hashing = hashing.clone();
     let password_hash = spawn_blocking_with_tracing(move || {
-        compute_password_hash(password)
+        compute_password_hash(password, &hashing)
     })
         .await?
         .context("Failed to spawn blocking task.")?;
@@ -120,15 +204,135 @@ pub async fn change_password(user_id: Uuid, password: Secret<String>, pool: &PgP
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> anyhow::Result<Secret<String>> {
+/// Check that a password chosen by `username` is acceptable.
+pub fn validate_new_password(password: &Secret<String>, username: &str) -> Result<(), String> {
+    let password = password.expose_secret();
+    let password_length = password.graphemes(true).count();
+    if !(12..=129).contains(&password_length) {
+        return Err("The new password should be between 12 and 129 characters.".into());
+    }
+    if contains_username(password, username) {
+        return Err("The new password should not contain your username.".into());
+    }
+    if is_breached(password) {
+        return Err("This password has appeared in a data breach or is too common, please choose another one.".into());
+    }
+    Ok(())
+}
+
+/// Usernames shorter than this are too likely to appear by chance to be refused.
+const MIN_USERNAME_LENGTH_TO_CHECK: usize = 3;
+
+fn contains_username(password: &str, username: &str) -> bool {
+    let username = username.trim().to_lowercase();
+    username.graphemes(true).count() >= MIN_USERNAME_LENGTH_TO_CHECK
+        && password.to_lowercase().contains(&username)
+}
+
+#[tracing::instrument(
+    name="Create user.",
+    skip(password, hashing, transaction)
+)]
+pub async fn create_user(
+    username: &str,
+    email: &str,
+    role: Role,
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<Uuid> {
+    let hashing = hashing.clone();
+    let password_hash = spawn_blocking_with_tracing(move || {
+        compute_password_hash(password, &hashing)
+    })
+        .await?
+        .context("Failed to spawn blocking task.")?;
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, email, password_hash, is_active, role)
+        VALUES ($1, $2, $3, $4, true, $5)"#,
+        user_id,
+        username,
+        email,
+        password_hash.expose_secret(),
+        role.as_str(),
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to insert a new user in the database.")?;
+    Ok(user_id)
+}
+
+pub fn compute_password_hash(password: Secret<String>, hashing: &PasswordHashingSettings) -> anyhow::Result<Secret<String>> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::new(
         Algorithm::Argon2id,
         Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
+        hashing.params().context("Invalid password hashing parameters.")?,
     )
         .hash_password(password.expose_secret().as_bytes(), &salt)
-        .unwrap()
+        .context("Failed to hash password.")?
         .to_string();
     Ok(Secret::new(password_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::{compute_password_hash, is_outdated, validate_new_password, DummyPasswordHash};
+    use crate::configuration::PasswordHashingSettings;
+
+    fn hashing(memory_size: u32, iterations: u32) -> PasswordHashingSettings {
+        PasswordHashingSettings { memory_size, iterations, parallelism: 1 }
+    }
+
+    #[test]
+    fn hashes_with_the_current_parameters_are_up_to_date() {
+        let password_hash = compute_password_hash(Secret::new("password".into()), &hashing(4096, 2)).unwrap();
+        assert!(!is_outdated(&password_hash, &hashing(4096, 2)).unwrap());
+    }
+
+    #[test]
+    fn hashes_with_other_parameters_are_outdated() {
+        let password_hash = compute_password_hash(Secret::new("password".into()), &hashing(4096, 2)).unwrap();
+        assert!(is_outdated(&password_hash, &hashing(8192, 2)).unwrap());
+        assert!(is_outdated(&password_hash, &hashing(4096, 3)).unwrap());
+    }
+
+    #[test]
+    fn the_dummy_hash_uses_the_current_parameters() {
+        let dummy_hash = DummyPasswordHash::compute(&hashing(4096, 3)).unwrap();
+        assert!(!is_outdated(&dummy_hash.0, &hashing(4096, 3)).unwrap());
+    }
+
+    #[test]
+    fn passwords_containing_the_username_are_rejected() {
+        let password = Secret::new("my-name-is-Alice-Smith".to_string());
+        assert!(validate_new_password(&password, "alice").is_err());
+        assert!(validate_new_password(&password, "bob").is_ok());
+    }
+
+    #[test]
+    fn very_short_usernames_are_not_checked() {
+        let password = Secret::new("a-long-enough-password".to_string());
+        assert!(validate_new_password(&password, "al").is_ok());
+    }
+
+    #[test]
+    fn breached_passwords_are_rejected() {
+        let password = Secret::new("Password1234".to_string());
+        assert!(validate_new_password(&password, "alice").is_err());
+    }
+
+    #[test]
+    fn hashes_with_another_algorithm_are_outdated() {
+        let password_hash = Secret::new(
+            "$argon2i$v=19$m=4096,t=2,p=1$\
+            c29tZXNhbHQ$\
+            iWh06vD8Fy27wf9npn6FXWiCX4K6pW6Ue1Bnzz07Z8A".to_string()
+        );
+        assert!(is_outdated(&password_hash, &hashing(4096, 2)).unwrap());
+    }
 }
\ No newline at end of file
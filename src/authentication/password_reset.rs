@@ -0,0 +1,246 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::PasswordHashingSettings;
+use crate::startup::HmacSecret;
+use crate::telemetry::spawn_blocking_with_tracing;
+
+use super::password::compute_password_hash;
+
+/// How long a password reset link stays valid.
+const RESET_TOKEN_TTL_MINUTES: i64 = 30;
+
+/// Reset requests are counted over this window.
+const RESET_REQUEST_WINDOW_MINUTES: i32 = 60;
+/// How many links can be asked for a username within the window, whether it exists or not.
+const MAX_RESET_REQUESTS_PER_USERNAME: i64 = 3;
+/// Looser, since several admins may share an IP address.
+const MAX_RESET_REQUESTS_PER_IP: i64 = 20;
+
+/// A user who asked to reset their password and can be emailed a link.
+pub struct ResetRecipient {
+    pub user_id: Uuid,
+    pub email: String,
+    password_hash: Secret<String>,
+}
+
+/// A user whose reset token has been verified.
+pub struct ResetTarget {
+    pub user_id: Uuid,
+    pub username: String,
+    password_hash: Secret<String>,
+}
+
+/// The active user called `username`, if they have an email address to send the link to.
+#[tracing::instrument(
+    name="Find the recipient of a password reset link.",
+    skip(pool)
+)]
+pub async fn get_reset_recipient(username: &str, pool: &PgPool) -> anyhow::Result<Option<ResetRecipient>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, email, password_hash
+        FROM users
+        WHERE username = $1 AND is_active AND email IS NOT NULL
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the recipient of a password reset link.")?;
+    Ok(row.and_then(|r| Some(ResetRecipient {
+        user_id: r.user_id,
+        email: r.email?,
+        password_hash: Secret::new(r.password_hash),
+    })))
+}
+
+/// Count a reset request, returning `false` when there have been too many recently
+/// for the username or from the IP address.
+#[tracing::instrument(
+    name="Rate limit a password reset request.",
+    skip(pool)
+)]
+pub async fn allow_reset_request(username: &str, ip: Option<&str>, pool: &PgPool) -> anyhow::Result<bool> {
+    sqlx::query!(
+        r#"DELETE FROM password_reset_requests WHERE requested_at < now() - make_interval(mins => $1)"#,
+        RESET_REQUEST_WINDOW_MINUTES,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to forget old password reset requests.")?;
+    let counts = sqlx::query!(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE username = $1) AS "by_username!",
+               COUNT(*) FILTER (WHERE ip = $2) AS "by_ip!"
+        FROM password_reset_requests
+        "#,
+        username,
+        ip,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count password reset requests.")?;
+    if counts.by_username >= MAX_RESET_REQUESTS_PER_USERNAME || counts.by_ip >= MAX_RESET_REQUESTS_PER_IP {
+        return Ok(false);
+    }
+    sqlx::query!(
+        r#"INSERT INTO password_reset_requests (username, ip, requested_at) VALUES ($1, $2, now())"#,
+        username,
+        ip,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a password reset request.")?;
+    Ok(true)
+}
+
+/// A signed token letting `recipient` set a new password until it expires.
+/// The signature covers the current password hash, so the token stops working once it has been used.
+pub fn generate_reset_token(recipient: &ResetRecipient, secret: &HmacSecret) -> String {
+    let expires_at = Utc::now() + Duration::minutes(RESET_TOKEN_TTL_MINUTES);
+    let payload = format!("{}.{}", recipient.user_id, expires_at.timestamp());
+    let tag = reset_mac(&payload, &recipient.password_hash, secret).finalize().into_bytes();
+    format!("{}.{}", payload, hex::encode(tag))
+}
+
+/// The user a reset token was issued to, provided it is valid, unexpired and unused.
+#[tracing::instrument(
+    name="Verify a password reset token.",
+    skip(token, secret, pool)
+)]
+pub async fn verify_reset_token(token: &str, secret: &HmacSecret, pool: &PgPool) -> anyhow::Result<Option<ResetTarget>> {
+    let Some((user_id, expires_at, payload, tag)) = parse_reset_token(token) else {
+        return Ok(None);
+    };
+    if expires_at <= Utc::now() {
+        return Ok(None);
+    }
+    let row = sqlx::query!(
+        r#"SELECT username, password_hash FROM users WHERE user_id = $1 AND is_active"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the password hash of a user.")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let password_hash = Secret::new(row.password_hash);
+    if reset_mac(payload, &password_hash, secret).verify_slice(&tag).is_err() {
+        return Ok(None);
+    }
+    Ok(Some(ResetTarget { user_id, username: row.username, password_hash }))
+}
+
+/// Set the new password and log the user out of all their sessions.
+/// Returns `false` if the password changed since the token was verified, i.e. the token was used concurrently.
+#[tracing::instrument(
+    name="Reset a password.",
+    skip(target, password, hashing, pool),
+    fields(user_id=%target.user_id)
+)]
+pub async fn reset_password(
+    target: ResetTarget,
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+    pool: &PgPool,
+) -> anyhow::Result<bool> {
+    let hashing = hashing.clone();
+    let password_hash = spawn_blocking_with_tracing(move || {
+        compute_password_hash(password, &hashing)
+    })
+        .await?
+        .context("Failed to spawn blocking task.")?;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        target.user_id,
+        target.password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to reset a password.")?
+    .rows_affected();
+    Ok(updated == 1)
+}
+
+fn parse_reset_token(token: &str) -> Option<(Uuid, DateTime<Utc>, &str, Vec<u8>)> {
+    let (payload, tag) = token.rsplit_once('.')?;
+    let (user_id, expires_at) = payload.split_once('.')?;
+    let user_id = Uuid::parse_str(user_id).ok()?;
+    let expires_at = DateTime::from_timestamp(expires_at.parse().ok()?, 0)?;
+    Some((user_id, expires_at, payload, hex::decode(tag).ok()?))
+}
+
+fn reset_mac(payload: &str, password_hash: &Secret<String>, secret: &HmacSecret) -> Hmac<sha2::Sha256> {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.0.expose_secret().as_bytes()).unwrap();
+    mac.update(b"password-reset.");
+    mac.update(payload.as_bytes());
+    mac.update(b".");
+    mac.update(password_hash.expose_secret().as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::Mac;
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{generate_reset_token, parse_reset_token, reset_mac, ResetRecipient};
+    use crate::startup::HmacSecret;
+
+    fn recipient(password_hash: &str) -> ResetRecipient {
+        ResetRecipient {
+            user_id: Uuid::new_v4(),
+            email: "admin@example.com".into(),
+            password_hash: Secret::new(password_hash.into()),
+        }
+    }
+
+    fn secret() -> HmacSecret {
+        HmacSecret(Secret::new("a-secret".into()))
+    }
+
+    fn is_valid(token: &str, password_hash: &str) -> bool {
+        let (_, _, payload, tag) = parse_reset_token(token).unwrap();
+        reset_mac(payload, &Secret::new(password_hash.into()), &secret())
+            .verify_slice(&tag)
+            .is_ok()
+    }
+
+    #[test]
+    fn a_token_is_only_valid_for_the_password_it_was_issued_for() {
+        let recipient = recipient("old-hash");
+        let token = generate_reset_token(&recipient, &secret());
+        assert!(is_valid(&token, "old-hash"));
+        assert!(!is_valid(&token, "new-hash"));
+    }
+
+    #[test]
+    fn a_token_names_its_user_and_cannot_be_transferred() {
+        let recipient = recipient("hash");
+        let token = generate_reset_token(&recipient, &secret());
+        let (user_id, _, _, _) = parse_reset_token(&token).unwrap();
+        assert_eq!(user_id, recipient.user_id);
+
+        let forged = token.replacen(&recipient.user_id.to_string(), &Uuid::new_v4().to_string(), 1);
+        assert!(!is_valid(&forged, "hash"));
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        assert!(parse_reset_token("").is_none());
+        assert!(parse_reset_token("not-a-uuid.123.abcd").is_none());
+        assert!(parse_reset_token(&format!("{}.soon.abcd", Uuid::new_v4())).is_none());
+    }
+}
@@ -0,0 +1,217 @@
+use actix_web::{http::header::USER_AGENT, HttpRequest};
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::SessionSettings;
+use crate::session_state::TypedSession;
+use crate::utils::client_ip;
+
+/// Where a session was opened from, as reported by the client.
+pub struct SessionDevice {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl SessionDevice {
+    pub fn from_request(request: &HttpRequest) -> Self {
+        Self {
+            user_agent: request
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_owned),
+            ip: client_ip(request),
+        }
+    }
+}
+
+pub struct UserSession {
+    pub session_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// Index a new session of `user_id` and return its id, to be stored in the session state.
+#[tracing::instrument(
+    name="Start a user session.",
+    skip(device, pool)
+)]
+pub async fn start_session(user_id: Uuid, device: &SessionDevice, pool: &PgPool) -> anyhow::Result<Uuid> {
+    let session_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (session_id, user_id, user_agent, ip, created_at, last_seen_at)
+        VALUES ($1, $2, $3, $4, now(), now())
+        "#,
+        session_id,
+        user_id,
+        device.user_agent,
+        device.ip,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a new user session.")?;
+    Ok(session_id)
+}
+
+/// Log `user_id` in once all their factors are checked: the session gets a fresh id,
+/// is indexed so that it can be listed and revoked, and its timeouts start.
+#[tracing::instrument(
+    name="Complete a login.",
+    skip(session, request, pool)
+)]
+pub async fn complete_login(
+    user_id: Uuid,
+    session: &TypedSession,
+    request: &HttpRequest,
+    pool: &PgPool,
+) -> anyhow::Result<()> {
+    session.renew();
+    session.remove_pending_user_id();
+    session.insert_user_id(user_id)?;
+    let session_id = start_session(user_id, &SessionDevice::from_request(request), pool).await?;
+    session.insert_session_id(session_id)?;
+    let now = Utc::now();
+    session.insert_logged_in_at(now)?;
+    session.insert_last_seen_at(now)?;
+    Ok(())
+}
+
+/// Activity is recorded at most this often, to spare a write on every request.
+const TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+/// Whether activity last recorded at `last_seen_at` should be recorded again at `now`.
+pub fn is_activity_due(last_seen_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    now - last_seen_at >= Duration::seconds(TOUCH_INTERVAL_SECONDS)
+}
+
+/// Record activity on a session. Returns `false` if it was revoked or does not belong to `user_id`.
+#[tracing::instrument(
+    name="Touch a user session.",
+    skip(pool)
+)]
+pub async fn touch_session(session_id: Uuid, user_id: Uuid, pool: &PgPool) -> anyhow::Result<bool> {
+    let Some(session) = sqlx::query!(
+        r#"
+        SELECT last_seen_at FROM user_sessions
+        WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve a user session.")?
+    else {
+        return Ok(false);
+    };
+    if !is_activity_due(session.last_seen_at, Utc::now()) {
+        return Ok(true);
+    }
+    let updated = sqlx::query!(
+        r#"
+        UPDATE user_sessions SET last_seen_at = now()
+        WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the last activity of a user session.")?
+    .rows_affected();
+    Ok(updated == 1)
+}
+
+/// The sessions of `user_id` that have not been revoked, most recently used first.
+#[tracing::instrument(
+    name="Get active user sessions.",
+    skip(pool)
+)]
+pub async fn get_active_sessions(user_id: Uuid, pool: &PgPool) -> anyhow::Result<Vec<UserSession>> {
+    let sessions = sqlx::query_as!(
+        UserSession,
+        r#"
+        SELECT session_id, user_agent, ip, created_at, last_seen_at
+        FROM user_sessions
+        WHERE user_id = $1 AND revoked_at IS NULL
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve user sessions.")?;
+    Ok(sessions)
+}
+
+/// Returns `false` if the session does not belong to `user_id` or was already revoked.
+#[tracing::instrument(
+    name="Revoke a user session.",
+    skip(pool)
+)]
+pub async fn revoke_session(user_id: Uuid, session_id: Uuid, pool: &PgPool) -> anyhow::Result<bool> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE user_sessions SET revoked_at = now()
+        WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke a user session.")?
+    .rows_affected();
+    Ok(updated == 1)
+}
+
+/// Revoke every session of `user_id`, but `except` if given. Returns how many were revoked.
+#[tracing::instrument(
+    name="Revoke all user sessions.",
+    skip(pool)
+)]
+pub async fn revoke_all_sessions(user_id: Uuid, except: Option<Uuid>, pool: &PgPool) -> anyhow::Result<u64> {
+    let revoked = sqlx::query!(
+        r#"
+        UPDATE user_sessions SET revoked_at = now()
+        WHERE user_id = $1 AND revoked_at IS NULL AND session_id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        except,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke user sessions.")?
+    .rows_affected();
+    Ok(revoked)
+}
+
+/// Delete the sessions that were revoked or can no longer be used. Returns how many were deleted.
+/// Activity is only recorded every minute, so idle sessions are kept that much longer.
+#[tracing::instrument(
+    name="Delete stale user sessions.",
+    skip(settings, pool)
+)]
+pub async fn delete_stale_sessions(settings: &SessionSettings, pool: &PgPool) -> anyhow::Result<u64> {
+    let idle_timeout = settings.idle_timeout() + Duration::seconds(TOUCH_INTERVAL_SECONDS);
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM user_sessions
+        WHERE revoked_at IS NOT NULL
+           OR last_seen_at < $1
+           OR created_at < $2
+        "#,
+        Utc::now() - idle_timeout,
+        Utc::now() - settings.absolute_timeout(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete stale user sessions.")?
+    .rows_affected();
+    Ok(deleted)
+}
@@ -0,0 +1,421 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::Context;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng, RngCore};
+use secrecy::{ExposeSecret, Secret};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::{PasswordHashingSettings, TotpSettings};
+use crate::telemetry::spawn_blocking_with_tracing;
+
+use super::password::compute_password_hash;
+
+const DIGITS: u32 = 6;
+const TIME_STEP_SECONDS: u64 = 30;
+/// Codes of the previous and next time steps are accepted to allow for clock drift.
+const ALLOWED_DRIFT: u64 = 1;
+const NONCE_LENGTH: usize = 12;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// A new random secret, of the 160 bits recommended by RFC 4226.
+pub fn generate_totp_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 20];
+    thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+/// RFC 4648 base32 without padding, the encoding authenticator apps expect.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    encoded
+}
+
+/// The URI authenticator apps enroll from, usually shown as a QR code.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        urlencoding::encode(issuer),
+        urlencoding::encode(account),
+        base32_encode(secret),
+        urlencoding::encode(issuer),
+        DIGITS,
+        TIME_STEP_SECONDS,
+    )
+}
+
+pub fn current_time_step() -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("The system clock is set before 1970.");
+    now.as_secs() / TIME_STEP_SECONDS
+}
+
+/// The RFC 6238 code for `time_step`, an HOTP whose counter is the number of steps since the epoch.
+pub fn totp_code(secret: &[u8], time_step: u64) -> String {
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC can take a key of any size");
+    mac.update(&time_step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+}
+
+/// The time step `code` was generated for, if it is valid around `time_step`.
+fn matching_time_step(secret: &[u8], code: &str, time_step: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    (time_step.saturating_sub(ALLOWED_DRIFT)..=time_step + ALLOWED_DRIFT)
+        .find(|step| constant_time_eq(totp_code(secret, *step).as_bytes(), code.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn cipher(key: &Secret<String>) -> Aes256Gcm {
+    let key = Sha256::digest(key.expose_secret().as_bytes());
+    Aes256Gcm::new_from_slice(&key).expect("A SHA-256 digest is a valid AES-256 key")
+}
+
+/// Encrypt a TOTP secret with AES-256-GCM, prefixing the ciphertext with its random nonce.
+pub fn encrypt_totp_secret(secret: &[u8], key: &Secret<String>) -> anyhow::Result<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = cipher(key)
+        .encrypt(Nonce::from_slice(&nonce), secret)
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt a TOTP secret."))?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+pub fn decrypt_totp_secret(encrypted: &[u8], key: &Secret<String>) -> anyhow::Result<Vec<u8>> {
+    if encrypted.len() < NONCE_LENGTH {
+        anyhow::bail!("The encrypted TOTP secret is too short.");
+    }
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+    cipher(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt a TOTP secret."))
+}
+
+/// Single-use codes to log in with when the authenticator is lost, e.g. `k3x9a-7bq2m`.
+fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .take(10)
+                .collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+/// Where a user stands with two-factor authentication.
+pub enum TotpState {
+    Disabled,
+    /// A secret has been generated but not confirmed with a code yet.
+    Pending(Vec<u8>),
+    Enabled,
+}
+
+#[tracing::instrument(
+    name="Get the two-factor authentication state of a user.",
+    skip(settings, pool)
+)]
+pub async fn get_totp_state(user_id: Uuid, settings: &TotpSettings, pool: &PgPool) -> anyhow::Result<TotpState> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret, totp_enabled FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the two-factor authentication state of a user.")?;
+    let state = match (row.totp_enabled, row.totp_secret) {
+        (true, _) => TotpState::Enabled,
+        (false, Some(encrypted)) => TotpState::Pending(decrypt_totp_secret(&encrypted, &settings.encryption_key)?),
+        (false, None) => TotpState::Disabled,
+    };
+    Ok(state)
+}
+
+/// Store a new secret for the user to add to their authenticator. It is only used once confirmed.
+#[tracing::instrument(
+    name="Start a two-factor authentication enrollment.",
+    skip(settings, pool)
+)]
+pub async fn start_totp_enrollment(user_id: Uuid, settings: &TotpSettings, pool: &PgPool) -> anyhow::Result<()> {
+    let encrypted = encrypt_totp_secret(&generate_totp_secret(), &settings.encryption_key)?;
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_secret = $2, totp_last_time_step = NULL
+        WHERE user_id = $1 AND NOT totp_enabled
+        "#,
+        user_id,
+        encrypted,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a TOTP secret.")?;
+    Ok(())
+}
+
+/// Enable two-factor authentication if `code` matches the pending secret.
+/// Returns the recovery codes, which are only stored hashed, or `None` if the code is invalid.
+#[tracing::instrument(
+    name="Confirm a two-factor authentication enrollment.",
+    skip(code, settings, hashing, pool)
+)]
+pub async fn confirm_totp_enrollment(
+    user_id: Uuid,
+    code: &str,
+    settings: &TotpSettings,
+    hashing: &PasswordHashingSettings,
+    pool: &PgPool,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let TotpState::Pending(secret) = get_totp_state(user_id, settings, pool).await? else {
+        return Ok(None);
+    };
+    let Some(time_step) = matching_time_step(&secret, code, current_time_step()) else {
+        return Ok(None);
+    };
+    let recovery_codes = generate_recovery_codes();
+    let hashes = {
+        let codes = recovery_codes.clone();
+        let hashing = hashing.clone();
+        spawn_blocking_with_tracing(move || {
+            codes
+                .into_iter()
+                .map(|code| compute_password_hash(Secret::new(code), &hashing))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .await
+        .context("Failed to spawn blocking task.")??
+    };
+
+    let mut transaction = pool.begin().await.context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_enabled = true, totp_last_time_step = $2
+        WHERE user_id = $1
+        "#,
+        user_id,
+        time_step as i64,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to enable two-factor authentication.")?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete previous recovery codes.")?;
+    for hash in hashes {
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (recovery_code_id, user_id, code_hash)
+            VALUES ($1, $2, $3)
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            hash.expose_secret(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to store a recovery code.")?;
+    }
+    transaction.commit().await.context("Failed to commit the two-factor authentication enrollment.")?;
+    Ok(Some(recovery_codes))
+}
+
+#[tracing::instrument(
+    name="Disable two-factor authentication.",
+    skip(pool)
+)]
+pub async fn disable_totp(user_id: Uuid, pool: &PgPool) -> anyhow::Result<()> {
+    let mut transaction = pool.begin().await.context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_secret = NULL, totp_enabled = false, totp_last_time_step = NULL
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to disable two-factor authentication.")?;
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete recovery codes.")?;
+    transaction.commit().await.context("Failed to commit disabling two-factor authentication.")?;
+    Ok(())
+}
+
+/// Check a code from the user's authenticator or one of their unused recovery codes.
+/// Each code is only accepted once.
+#[tracing::instrument(
+    name="Verify a second factor.",
+    skip(code, settings, pool)
+)]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: &str,
+    settings: &TotpSettings,
+    pool: &PgPool,
+) -> anyhow::Result<bool> {
+    let row = sqlx::query!(
+        r#"
+        SELECT totp_secret, totp_last_time_step
+        FROM users
+        WHERE user_id = $1 AND totp_enabled
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the TOTP secret of a user.")?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    let encrypted = row.totp_secret.context("Two-factor authentication is enabled without a secret.")?;
+    let secret = decrypt_totp_secret(&encrypted, &settings.encryption_key)?;
+    if let Some(time_step) = matching_time_step(&secret, code, current_time_step()) {
+        // The condition on the last time step rejects replayed codes, even concurrently.
+        let accepted = sqlx::query!(
+            r#"
+            UPDATE users SET totp_last_time_step = $2
+            WHERE user_id = $1 AND (totp_last_time_step IS NULL OR totp_last_time_step < $2)
+            "#,
+            user_id,
+            time_step as i64,
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record the use of a TOTP code.")?
+        .rows_affected();
+        return Ok(accepted == 1);
+    }
+    use_recovery_code(user_id, code, pool).await
+}
+
+async fn use_recovery_code(user_id: Uuid, code: &str, pool: &PgPool) -> anyhow::Result<bool> {
+    let unused_codes = sqlx::query!(
+        r#"SELECT recovery_code_id, code_hash FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve recovery codes.")?
+    .into_iter()
+    .map(|r| (r.recovery_code_id, r.code_hash))
+    .collect::<Vec<_>>();
+    let candidate = normalize_recovery_code(code);
+    let matching_code = spawn_blocking_with_tracing(move || {
+        unused_codes.into_iter().find_map(|(recovery_code_id, code_hash)| {
+            let code_hash = PasswordHash::new(&code_hash).ok()?;
+            Argon2::default()
+                .verify_password(candidate.as_bytes(), &code_hash)
+                .ok()
+                .map(|_| recovery_code_id)
+        })
+    })
+    .await
+    .context("Failed to spawn blocking task.")?;
+    let Some(recovery_code_id) = matching_code else {
+        return Ok(false);
+    };
+    let used = sqlx::query!(
+        r#"UPDATE recovery_codes SET used_at = now() WHERE recovery_code_id = $1 AND used_at IS NULL"#,
+        recovery_code_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark a recovery code as used.")?
+    .rows_affected();
+    Ok(used == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::{
+        base32_encode, decrypt_totp_secret, encrypt_totp_secret, generate_recovery_codes, matching_time_step,
+        totp_code,
+    };
+
+    // The SHA-1 secret of the RFC 6238 test vectors.
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn codes_match_the_rfc_6238_test_vectors() {
+        // The RFC lists 8-digit codes; ours are their last 6 digits.
+        assert_eq!(totp_code(SECRET, 59 / 30), "287082");
+        assert_eq!(totp_code(SECRET, 1111111109 / 30), "081804");
+        assert_eq!(totp_code(SECRET, 2000000000 / 30), "279037");
+    }
+
+    #[test]
+    fn codes_of_adjacent_time_steps_are_accepted() {
+        let time_step = 1111111109 / 30;
+        assert_eq!(matching_time_step(SECRET, "081804", time_step + 1), Some(time_step));
+        assert_eq!(matching_time_step(SECRET, " 081804 ", time_step - 1), Some(time_step));
+        assert_eq!(matching_time_step(SECRET, "081804", time_step + 2), None);
+        assert_eq!(matching_time_step(SECRET, "81804", time_step), None);
+    }
+
+    #[test]
+    fn secrets_are_base32_encoded_without_padding() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_encode(SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn secrets_round_trip_through_encryption() {
+        let key = Secret::new("an-encryption-key".to_string());
+        let encrypted = encrypt_totp_secret(SECRET, &key).unwrap();
+        assert_ne!(&encrypted[12..], SECRET);
+        assert_eq!(decrypt_totp_secret(&encrypted, &key).unwrap(), SECRET);
+        assert!(decrypt_totp_secret(&encrypted, &Secret::new("another-key".to_string())).is_err());
+    }
+
+    #[test]
+    fn recovery_codes_are_distinct() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), 10);
+        assert!(codes.iter().all(|c| c.len() == 11 && c.as_bytes()[5] == b'-'));
+        let mut deduplicated = codes.clone();
+        deduplicated.sort();
+        deduplicated.dedup();
+        assert_eq!(deduplicated.len(), codes.len());
+    }
+}
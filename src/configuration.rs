@@ -1,14 +1,22 @@
+use std::net::IpAddr;
+
+use argon2::Params;
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
-use sqlx::ConnectOptions;
+use sqlx::{ConnectOptions, PgPool};
 
 use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::suppression_list::SuppressionList;
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub totp: TotpSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub session: SessionSettings,
     pub redis_uri: Secret<String>
 }
 
@@ -52,7 +60,12 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub host: String,
     pub base_url: String,
-    pub hmac_secret: Secret<String>
+    pub hmac_secret: Secret<String>,
+    pub webhook_secret: Secret<String>,
+    /// The addresses of the reverse proxies whose forwarding headers are believed.
+    /// They must overwrite those headers rather than append to what the client sent.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
@@ -101,6 +114,54 @@ impl TryFrom<String> for Environment {
     }
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct TotpSettings {
+    /// Encrypts the TOTP secrets stored in the database.
+    pub encryption_key: Secret<String>,
+    /// Whether owners must set up two-factor authentication before using the admin area.
+    pub required_for_owners: bool,
+}
+
+/// How long an admin session lasts.
+#[derive(serde::Deserialize, Clone)]
+pub struct SessionSettings {
+    /// The session expires when no request was made for this long.
+    #[serde(deserialize_with="deserialize_number_from_string")]
+    pub idle_timeout_minutes: i64,
+    /// The session expires this long after logging in, however active it is.
+    #[serde(deserialize_with="deserialize_number_from_string")]
+    pub absolute_timeout_minutes: i64,
+}
+
+impl SessionSettings {
+    pub fn idle_timeout(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.idle_timeout_minutes)
+    }
+
+    pub fn absolute_timeout(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.absolute_timeout_minutes)
+    }
+}
+
+/// The Argon2id parameters of new password hashes.
+/// Stored hashes using other parameters are upgraded on the next successful login.
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordHashingSettings {
+    /// In KiB.
+    #[serde(deserialize_with="deserialize_number_from_string")]
+    pub memory_size: u32,
+    #[serde(deserialize_with="deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with="deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+impl PasswordHashingSettings {
+    pub fn params(&self) -> Result<Params, argon2::Error> {
+        Params::new(self.memory_size, self.iterations, self.parallelism, None)
+    }
+}
+
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
     pub base_url: String,
@@ -117,4 +178,11 @@ impl EmailClientSettings {
     pub fn timeout(&self) -> std::time::Duration {
         std::time::Duration::from_millis(self.timeout_milliseconds)
     }
+
+    /// The client every email goes through, checking recipients against the suppression list in `pool`.
+    pub fn client(self, pool: PgPool) -> EmailClient {
+        let sender_email = self.sender().expect("invalid sender email address.");
+        let timeout = self.timeout();
+        EmailClient::new(self.base_url, sender_email, self.authorization_token, timeout, SuppressionList::Postgres(pool))
+    }
 }
\ No newline at end of file
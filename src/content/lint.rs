@@ -0,0 +1,128 @@
+/// Gmail clips messages whose HTML body is larger than 102KB.
+const MAX_HTML_BODY_BYTES: usize = 102 * 1024;
+
+const UNSUBSCRIBE_PLACEHOLDER: &str = "unsubscribe_url";
+
+/// Spot common newsletter mistakes the admin should review before sending.
+/// `html` is expected to have gone through `sanitize_html`, which normalizes
+/// tags and double-quotes attribute values.
+pub fn lint_newsletter(html: &str, text: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if html.len() > MAX_HTML_BODY_BYTES {
+        warnings.push(format!(
+            "The HTML body is {}KB, Gmail clips messages larger than 102KB.",
+            html.len() / 1024
+        ));
+    }
+    let images_without_alt = tags(html, "img")
+        .filter(|tag| attribute(tag, "alt").is_none())
+        .count();
+    if images_without_alt > 0 {
+        warnings.push(format!("{} image(s) have no alt text.", images_without_alt));
+    }
+    for href in tags(html, "a").filter_map(|tag| attribute(tag, "href")) {
+        if !is_reachable(href) {
+            // Warnings are shown as HTML on the admin page, the link is author-supplied.
+            warnings.push(format!(
+                "The link '{}' is relative and will not work from an inbox.",
+                htmlescape::encode_minimal(href)
+            ));
+        }
+    }
+    if !has_unsubscribe_placeholder(html) {
+        warnings.push("The HTML body has no {{ unsubscribe_url }} link.".into());
+    }
+    if !has_unsubscribe_placeholder(text) {
+        warnings.push("The text body has no {{ unsubscribe_url }} link.".into());
+    }
+    warnings
+}
+
+/// The content of every opening `<name ...>` tag.
+fn tags<'a>(html: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> {
+    html.match_indices('<').filter_map(move |(start, _)| {
+        let tag = &html[start + 1..];
+        let tag = &tag[..tag.find('>')?];
+        let is_match = tag.starts_with(name)
+            && tag[name.len()..].chars().next().is_none_or(|c| c.is_whitespace() || c == '/');
+        is_match.then_some(tag)
+    })
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let prefix = format!(" {}=\"", name);
+    let start = tag.find(&prefix)? + prefix.len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+fn is_reachable(href: &str) -> bool {
+    ["http://", "https://", "mailto:", "#", "{{"]
+        .iter()
+        .any(|prefix| href.trim_start().to_lowercase().starts_with(prefix))
+}
+
+fn has_unsubscribe_placeholder(body: &str) -> bool {
+    body.match_indices("{{").any(|(start, _)| {
+        body[start + 2..]
+            .split("}}")
+            .next()
+            .is_some_and(|tag| tag.split('|').next().unwrap_or_default().trim() == UNSUBSCRIBE_PLACEHOLDER)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint_newsletter;
+
+    const UNSUBSCRIBE: &str = r#"<a href="{{ unsubscribe_url }}">Unsubscribe</a>"#;
+
+    #[test]
+    fn a_clean_newsletter_has_no_warnings() {
+        let html = format!(r#"<p><img src="https://example.com/a.png" alt="A"> <a href="https://example.com">Hi</a></p>{}"#, UNSUBSCRIBE);
+        assert!(lint_newsletter(&html, "Bye {{unsubscribe_url}}").is_empty());
+    }
+
+    #[test]
+    fn missing_alt_text_is_reported() {
+        let html = format!(r#"<img src="https://example.com/a.png">{}"#, UNSUBSCRIBE);
+        assert_eq!(lint_newsletter(&html, "{{ unsubscribe_url }}"), vec!["1 image(s) have no alt text."]);
+    }
+
+    #[test]
+    fn relative_links_are_reported() {
+        let html = format!(r#"<a href="/blog/post">Post</a>{}"#, UNSUBSCRIBE);
+        assert_eq!(
+            lint_newsletter(&html, "{{ unsubscribe_url }}"),
+            vec!["The link '/blog/post' is relative and will not work from an inbox."]
+        );
+    }
+
+    #[test]
+    fn reported_links_are_escaped() {
+        let html = format!(r#"<a href="/x?a='&<script">Post</a>{}"#, UNSUBSCRIBE);
+        assert_eq!(
+            lint_newsletter(&html, "{{ unsubscribe_url }}"),
+            vec!["The link '/x?a=&#x27;&amp;&lt;script' is relative and will not work from an inbox."]
+        );
+    }
+
+    #[test]
+    fn missing_unsubscribe_placeholders_are_reported() {
+        assert_eq!(
+            lint_newsletter("<p>Hi</p>", "Hi"),
+            vec![
+                "The HTML body has no {{ unsubscribe_url }} link.",
+                "The text body has no {{ unsubscribe_url }} link.",
+            ]
+        );
+    }
+
+    #[test]
+    fn oversized_bodies_are_reported() {
+        let html = format!("<p>{}</p>{}", "a".repeat(103 * 1024), UNSUBSCRIBE);
+        let warnings = lint_newsletter(&html, "{{ unsubscribe_url }}");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Gmail clips messages larger than 102KB."));
+    }
+}
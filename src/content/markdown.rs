@@ -0,0 +1,101 @@
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+
+use super::placeholders::{protect_placeholders, restore_placeholders};
+
+/// Plain-text emails are wrapped at this many columns.
+const TEXT_WIDTH: usize = 78;
+
+pub struct NewsletterContent {
+    pub html: String,
+    pub text: String,
+}
+
+/// Render Markdown into an HTML body and its plain-text alternative.
+/// Raw HTML in the source is escaped rather than passed through, and links
+/// with a scheme other than http(s) or mailto are neutralized.
+pub fn markdown_to_content(markdown: &str) -> NewsletterContent {
+    let (markdown, placeholders) = protect_placeholders(markdown);
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let events = Parser::new_ext(&markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link { link_type, dest_url, title, id }) if !is_allowed_url(&dest_url) => {
+            Event::Start(Tag::Link { link_type, dest_url: CowStr::Borrowed("#"), title, id })
+        }
+        Event::Start(Tag::Image { link_type, dest_url, title, id }) if !is_allowed_url(&dest_url) => {
+            Event::Start(Tag::Image { link_type, dest_url: CowStr::Borrowed(""), title, id })
+        }
+        event => event,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    let text = html2text::from_read(html.as_bytes(), TEXT_WIDTH);
+    NewsletterContent {
+        html: restore_placeholders(&html, &placeholders),
+        text: restore_placeholders(&text, &placeholders),
+    }
+}
+
+/// Derive a plain-text alternative from an HTML body, listing links as footnotes.
+pub fn html_to_text(html: &str) -> String {
+    let (html, placeholders) = protect_placeholders(html);
+    let text = html2text::from_read(html.as_bytes(), TEXT_WIDTH);
+    restore_placeholders(&text, &placeholders)
+}
+
+fn is_allowed_url(url: &str) -> bool {
+    let url = url.trim().to_lowercase();
+    match url.split_once(':') {
+        // no scheme: relative links, anchors and protected template placeholders
+        None => true,
+        Some((scheme, _)) if scheme.contains('/') || scheme.contains('?') || scheme.contains('#') => true,
+        Some((scheme, _)) => ["http", "https", "mailto"].contains(&scheme),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{html_to_text, markdown_to_content};
+
+    #[test]
+    fn markdown_is_rendered_to_html() {
+        let content = markdown_to_content("# Title\n\nSome **bold** text.");
+        assert_eq!(content.html, "<h1>Title</h1>\n<p>Some <strong>bold</strong> text.</p>\n");
+    }
+
+    #[test]
+    fn links_become_footnotes_in_the_text_version() {
+        let content = markdown_to_content("Read [the post](https://example.com/post).");
+        assert!(content.text.contains("Read [the post][1]."));
+        assert!(content.text.contains("[1]: https://example.com/post"));
+    }
+
+    #[test]
+    fn raw_html_is_escaped() {
+        let content = markdown_to_content("Hi <script>alert(1)</script>");
+        assert!(!content.html.contains("<script>"));
+        assert!(content.html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn javascript_links_are_neutralized() {
+        let content = markdown_to_content("[click](javascript:alert(1))");
+        assert!(content.html.contains(r##"<a href="#">click</a>"##));
+    }
+
+    #[test]
+    fn template_placeholders_survive_rendering() {
+        let content = markdown_to_content(
+            r#"Hi {{ name | default: "there" }}, [unsubscribe]({{ unsubscribe_url }})"#,
+        );
+        assert!(content.html.contains(r#"Hi {{ name | default: "there" }}"#));
+        assert!(content.html.contains(r#"<a href="{{ unsubscribe_url }}">"#));
+        assert!(content.text.contains("[1]: {{ unsubscribe_url }}"));
+    }
+
+    #[test]
+    fn text_is_derived_from_html() {
+        let text = html_to_text(r#"<p>Hello <a href="https://example.com">world</a></p>"#);
+        assert!(text.starts_with("Hello [world][1]"));
+        assert!(text.contains("[1]: https://example.com"));
+    }
+}
@@ -0,0 +1,12 @@
+mod lint;
+mod markdown;
+mod placeholders;
+mod sanitize;
+mod tracking;
+mod utm;
+
+pub use lint::lint_newsletter;
+pub use markdown::{html_to_text, markdown_to_content, NewsletterContent};
+pub use sanitize::{sanitize_html, sanitize_rendered_html};
+pub use tracking::{add_open_pixel, rewrite_links};
+pub use utm::UtmParameters;
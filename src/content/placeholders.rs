@@ -0,0 +1,28 @@
+/// Swap `{{ ... }}` template placeholders for opaque alphanumeric tokens so that
+/// Markdown rendering, sanitization and text wrapping leave them untouched.
+pub fn protect_placeholders(source: &str) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(source.len());
+    let mut placeholders = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else { break };
+        output.push_str(&rest[..start]);
+        output.push_str(&placeholder_token(placeholders.len()));
+        placeholders.push(rest[start..start + end + 2].to_owned());
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    (output, placeholders)
+}
+
+pub fn restore_placeholders(s: &str, placeholders: &[String]) -> String {
+    let mut output = s.to_owned();
+    for (i, placeholder) in placeholders.iter().enumerate() {
+        output = output.replace(&placeholder_token(i), placeholder);
+    }
+    output
+}
+
+fn placeholder_token(i: usize) -> String {
+    format!("ZZPLACEHOLDER{}ZZ", i)
+}
@@ -0,0 +1,75 @@
+use std::collections::HashSet;
+
+use super::placeholders::{protect_placeholders, restore_placeholders};
+
+/// Run an HTML template through an allowlist: scripts, event handlers, forms and
+/// embedded content are removed, while the layout attributes commonly used in
+/// email templates are kept, along with `{{ ... }}` placeholders.
+pub fn sanitize_html(html: &str) -> String {
+    let (html, placeholders) = protect_placeholders(html);
+    restore_placeholders(&sanitize_rendered_html(&html), &placeholders)
+}
+
+/// Same allowlist, for HTML whose placeholders have been substituted.
+/// Substituted values can turn an attribute into a `javascript:` URL,
+/// so rendered bodies must go through this again before being sent.
+pub fn sanitize_rendered_html(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_generic_attributes(["style", "align", "valign", "width", "height", "bgcolor"])
+        .add_tag_attributes("table", ["border", "cellpadding", "cellspacing"])
+        .url_schemes(HashSet::from(["http", "https", "mailto"]))
+        .link_rel(None)
+        .clean(html)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sanitize_html, sanitize_rendered_html};
+
+    #[test]
+    fn scripts_are_removed() {
+        assert_eq!(sanitize_html("<p>Hi</p><script>alert(1)</script>"), "<p>Hi</p>");
+    }
+
+    #[test]
+    fn event_handlers_are_removed() {
+        assert_eq!(
+            sanitize_html(r#"<img src="https://example.com/a.png" alt="a" onerror="alert(1)">"#),
+            r#"<img src="https://example.com/a.png" alt="a">"#
+        );
+    }
+
+    #[test]
+    fn forms_are_removed() {
+        let html = sanitize_html(r#"<form action="https://evil.com"><input name="password"></form>"#);
+        assert!(!html.contains("<form"));
+        assert!(!html.contains("<input"));
+    }
+
+    #[test]
+    fn javascript_urls_are_removed() {
+        assert_eq!(sanitize_html(r#"<a href="javascript:alert(1)">x</a>"#), "<a>x</a>");
+    }
+
+    #[test]
+    fn inline_styles_and_template_placeholders_are_kept() {
+        let html = r#"<p style="color: red">Hi {{ name | default: "there" }}</p><a href="{{ unsubscribe_url }}">Unsubscribe</a>"#;
+        assert_eq!(sanitize_html(html), html);
+    }
+
+    #[test]
+    fn placeholders_cannot_hide_unsafe_urls_from_the_rendered_html() {
+        let template = sanitize_html(r#"<a href="{{ website | default: "javascript:alert(1)" }}">Site</a>"#);
+        let rendered = template.replace(r#"{{ website | default: "javascript:alert(1)" }}"#, "javascript:alert(1)");
+        assert_eq!(sanitize_rendered_html(&rendered), "<a>Site</a>");
+    }
+
+    #[test]
+    fn placeholders_are_not_protected_in_rendered_html() {
+        assert_eq!(
+            sanitize_rendered_html(r#"<a href="javascript:{{ x }}">x</a><img src="data:image/png;base64,AAAA" alt="x">"#),
+            r#"<a>x</a><img alt="x">"#
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::ops::Range;
+
+/// Append an invisible image loading `pixel_url` to an HTML body, inside
+/// `<body>` when the document has one.
+pub fn add_open_pixel(html: &str, pixel_url: &str) -> String {
+    let pixel = format!(
+        r#"<img src="{}" width="1" height="1" alt="" style="display:block;border:0">"#,
+        htmlescape::encode_minimal(pixel_url)
+    );
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{}{}", &html[..end], pixel, &html[end..]),
+        None => format!("{}{}", html, pixel),
+    }
+}
+
+/// Point every `<a href="...">` for which `rewrite` returns a URL at that URL instead.
+/// `html` is expected to double-quote attribute values, as `sanitize_html` does.
+pub fn rewrite_links(html: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let mut rewritten = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<a ") {
+        let end = rest[start..].find('>').map_or(rest.len(), |end| start + end);
+        let tag = &rest[start..end];
+        let new_tag = href_range(tag).and_then(|range| {
+            let href = htmlescape::decode_html(&tag[range.clone()]).ok()?;
+            let url = rewrite(&href)?;
+            Some(format!(
+                "{}{}{}",
+                &tag[..range.start],
+                htmlescape::encode_minimal(&url),
+                &tag[range.end..]
+            ))
+        });
+        rewritten.push_str(&rest[..start]);
+        rewritten.push_str(new_tag.as_deref().unwrap_or(tag));
+        rest = &rest[end..];
+    }
+    rewritten.push_str(rest);
+    rewritten
+}
+
+fn href_range(tag: &str) -> Option<Range<usize>> {
+    let prefix = " href=\"";
+    let start = tag.find(prefix)? + prefix.len();
+    let end = start + tag[start..].find('"')?;
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_open_pixel, rewrite_links};
+
+    #[test]
+    fn the_pixel_is_appended_to_fragments() {
+        assert_eq!(
+            add_open_pixel("<p>Hi</p>", "https://example.com/o/abc"),
+            r#"<p>Hi</p><img src="https://example.com/o/abc" width="1" height="1" alt="" style="display:block;border:0">"#
+        );
+    }
+
+    #[test]
+    fn the_pixel_goes_inside_the_body() {
+        let html = add_open_pixel("<html><body><p>Hi</p></body></html>", "https://example.com/o/abc");
+        assert!(html.starts_with("<html><body><p>Hi</p><img "));
+        assert!(html.ends_with("></body></html>"));
+    }
+
+    #[test]
+    fn links_are_rewritten_with_their_decoded_url() {
+        let html = rewrite_links(
+            r#"<p><a href="https://example.com/?a=1&amp;b=2" style="color: red">Read</a></p>"#,
+            |url| Some(format!("https://t.example.com/r?u={}", urlencoding::encode(url))),
+        );
+        assert_eq!(
+            html,
+            r#"<p><a href="https://t.example.com/r?u=https%3A%2F%2Fexample.com%2F%3Fa%3D1%26b%3D2" style="color: red">Read</a></p>"#
+        );
+    }
+
+    #[test]
+    fn links_can_be_left_untouched() {
+        let html = r#"<a href="mailto:a@example.com">Mail</a> <abbr title="x">X</abbr> <a name="top">Top</a>"#;
+        assert_eq!(rewrite_links(html, |_| None), html);
+    }
+}
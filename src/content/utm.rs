@@ -0,0 +1,131 @@
+use super::tracking::rewrite_links;
+
+/// The campaign attribution appended to the external links of an issue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtmParameters {
+    pub source: String,
+    pub medium: String,
+    pub campaign: String,
+}
+
+impl UtmParameters {
+    /// `url` with the parameters added to its query string, ahead of any fragment.
+    /// Parameters that are empty, or already present in `url`, are left out.
+    pub fn tag_url(&self, url: &str) -> String {
+        let (url, fragment) = match url.split_once('#') {
+            Some((url, fragment)) => (url, Some(fragment)),
+            None => (url, None),
+        };
+        let query = url.split_once('?').map(|(_, query)| query).unwrap_or_default();
+        let mut tagged = url.to_string();
+        for (name, value) in [
+            ("utm_source", &self.source),
+            ("utm_medium", &self.medium),
+            ("utm_campaign", &self.campaign),
+        ] {
+            let is_present = query.split('&').any(|pair| pair.split('=').next() == Some(name));
+            if value.is_empty() || is_present {
+                continue;
+            }
+            if !tagged.contains('?') {
+                tagged.push('?');
+            } else if !tagged.ends_with(['?', '&']) {
+                tagged.push('&');
+            }
+            tagged.push_str(&format!("{}={}", name, urlencoding::encode(value)));
+        }
+        match fragment {
+            Some(fragment) => format!("{}#{}", tagged, fragment),
+            None => tagged,
+        }
+    }
+
+    /// Tag the `http(s)` links of an HTML body, except those for which `skip` returns `true`.
+    pub fn tag_html(&self, html: &str, skip: impl Fn(&str) -> bool) -> String {
+        rewrite_links(html, |url| (is_web_url(url) && !skip(url)).then(|| self.tag_url(url)))
+    }
+
+    /// Tag the `http(s)` URLs written out in a text body, except those for which `skip` returns `true`.
+    pub fn tag_text(&self, text: &str, skip: impl Fn(&str) -> bool) -> String {
+        let mut tagged = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = ["http://", "https://"].iter().filter_map(|s| rest.find(s)).min() {
+            let end = rest[start..]
+                .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+                .map_or(rest.len(), |end| start + end);
+            // Punctuation ending a sentence is not part of the URL.
+            let url = rest[start..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+            tagged.push_str(&rest[..start]);
+            if skip(url) {
+                tagged.push_str(url);
+            } else {
+                tagged.push_str(&self.tag_url(url));
+            }
+            rest = &rest[start + url.len()..];
+        }
+        tagged.push_str(rest);
+        tagged
+    }
+}
+
+fn is_web_url(url: &str) -> bool {
+    let url = url.trim_start().to_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UtmParameters;
+
+    fn utm() -> UtmParameters {
+        UtmParameters {
+            source: "newsletter".into(),
+            medium: "email".into(),
+            campaign: "spring sale".into(),
+        }
+    }
+
+    #[test]
+    fn parameters_are_appended_to_urls_without_a_query() {
+        assert_eq!(
+            utm().tag_url("https://example.com/post"),
+            "https://example.com/post?utm_source=newsletter&utm_medium=email&utm_campaign=spring%20sale"
+        );
+    }
+
+    #[test]
+    fn existing_query_strings_and_fragments_are_preserved() {
+        assert_eq!(
+            utm().tag_url("https://example.com/post?id=1#comments"),
+            "https://example.com/post?id=1&utm_source=newsletter&utm_medium=email&utm_campaign=spring%20sale#comments"
+        );
+    }
+
+    #[test]
+    fn empty_or_existing_parameters_are_not_added() {
+        let utm = UtmParameters { campaign: String::new(), ..utm() };
+        assert_eq!(
+            utm.tag_url("https://example.com/?utm_source=blog"),
+            "https://example.com/?utm_source=blog&utm_medium=email"
+        );
+    }
+
+    #[test]
+    fn only_external_html_links_are_tagged() {
+        let html = r#"<a href="https://example.com/?a=1&amp;b=2">A</a><a href="mailto:a@example.com">M</a><a href="https://app.com/unsubscribe">U</a>"#;
+        assert_eq!(
+            utm().tag_html(html, |url| url == "https://app.com/unsubscribe"),
+            r#"<a href="https://example.com/?a=1&amp;b=2&amp;utm_source=newsletter&amp;utm_medium=email&amp;utm_campaign=spring%20sale">A</a><a href="mailto:a@example.com">M</a><a href="https://app.com/unsubscribe">U</a>"#
+        );
+    }
+
+    #[test]
+    fn urls_in_text_are_tagged_without_trailing_punctuation() {
+        let utm = UtmParameters { medium: String::new(), campaign: String::new(), ..utm() };
+        let text = "Read https://example.com/post. Bye (https://app.com/unsubscribe)";
+        assert_eq!(
+            utm.tag_text(text, |url| url == "https://app.com/unsubscribe"),
+            "Read https://example.com/post?utm_source=newsletter. Bye (https://app.com/unsubscribe)"
+        );
+    }
+}
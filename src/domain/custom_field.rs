@@ -0,0 +1,182 @@
+use chrono::NaiveDate;
+use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomFieldType {
+    Text,
+    Number,
+    Date,
+    Boolean,
+    Enum,
+}
+
+impl CustomFieldType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldType::Text => "text",
+            CustomFieldType::Number => "number",
+            CustomFieldType::Date => "date",
+            CustomFieldType::Boolean => "boolean",
+            CustomFieldType::Enum => "enum",
+        }
+    }
+}
+
+impl TryFrom<String> for CustomFieldType {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(CustomFieldType::Text),
+            "number" => Ok(CustomFieldType::Number),
+            "date" => Ok(CustomFieldType::Date),
+            "boolean" => Ok(CustomFieldType::Boolean),
+            "enum" => Ok(CustomFieldType::Enum),
+            other => Err(format!(
+                "{} is not a supported field type. Use one of `text`, `number`, `date`, `boolean` or `enum`.",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomFieldName(String);
+
+impl CustomFieldName {
+    pub fn parse(s: String) -> Result<CustomFieldName, String> {
+        let s = s.trim().to_lowercase();
+        let is_empty = s.is_empty();
+        let is_too_long = s.chars().count() > 64;
+        let has_forbidden_characters = s.chars().any(|c| !(c.is_ascii_alphanumeric() || c == '_'));
+        let is_reserved = ["email", "name", "unsubscribe_url"].contains(&s.as_str());
+        if is_empty || is_too_long || has_forbidden_characters {
+            Err(format!(
+                "'{}' is not a valid field name. Names are 1 to 64 letters, digits or '_'.",
+                s
+            ))
+        } else if is_reserved {
+            Err(format!("'{}' is a reserved field name.", s))
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl AsRef<str> for CustomFieldName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomField {
+    pub field_id: Uuid,
+    pub name: String,
+    pub field_type: CustomFieldType,
+    pub options: Vec<String>,
+    pub required: bool,
+}
+
+impl CustomField {
+    /// Validate a raw value against the field type, returning its normalized
+    /// representation as stored in the database.
+    pub fn parse_value(&self, raw: &str) -> Result<String, String> {
+        let raw = raw.trim();
+        match self.field_type {
+            CustomFieldType::Text => {
+                if raw.graphemes(true).count() > 1024 {
+                    Err(format!("'{}' cannot be longer than 1024 characters.", self.name))
+                } else {
+                    Ok(raw.to_owned())
+                }
+            }
+            CustomFieldType::Number => raw
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(|n| n.to_string())
+                .ok_or_else(|| format!("'{}' must be a number.", self.name)),
+            CustomFieldType::Date => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .map(|d| d.to_string())
+                .map_err(|_| format!("'{}' must be a date in the YYYY-MM-DD format.", self.name)),
+            CustomFieldType::Boolean => match raw.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Ok("true".into()),
+                "false" | "no" | "off" | "0" => Ok("false".into()),
+                _ => Err(format!("'{}' must be either true or false.", self.name)),
+            },
+            CustomFieldType::Enum => self
+                .options
+                .iter()
+                .find(|o| o.as_str() == raw)
+                .cloned()
+                .ok_or_else(|| format!("'{}' must be one of: {}.", self.name, self.options.join(", "))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomFieldValue {
+    pub field_id: Uuid,
+    pub name: String,
+    pub value: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomField, CustomFieldName, CustomFieldType};
+    use claims::{assert_err, assert_ok_eq};
+    use uuid::Uuid;
+
+    fn field(field_type: CustomFieldType) -> CustomField {
+        CustomField {
+            field_id: Uuid::new_v4(),
+            name: "field".into(),
+            field_type,
+            options: vec!["small".into(), "large".into()],
+            required: false,
+        }
+    }
+
+    #[test]
+    fn numbers_are_normalized() {
+        assert_ok_eq!(field(CustomFieldType::Number).parse_value(" 42.0 "), "42".to_string());
+    }
+
+    #[test]
+    fn non_numbers_are_rejected() {
+        assert_err!(field(CustomFieldType::Number).parse_value("forty-two"));
+        assert_err!(field(CustomFieldType::Number).parse_value("NaN"));
+    }
+
+    #[test]
+    fn dates_must_be_iso_formatted() {
+        assert_ok_eq!(field(CustomFieldType::Date).parse_value("2024-02-29"), "2024-02-29".to_string());
+        assert_err!(field(CustomFieldType::Date).parse_value("29/02/2024"));
+    }
+
+    #[test]
+    fn booleans_accept_form_checkbox_values() {
+        assert_ok_eq!(field(CustomFieldType::Boolean).parse_value("on"), "true".to_string());
+        assert_ok_eq!(field(CustomFieldType::Boolean).parse_value("False"), "false".to_string());
+        assert_err!(field(CustomFieldType::Boolean).parse_value("maybe"));
+    }
+
+    #[test]
+    fn enums_only_accept_their_options() {
+        assert_ok_eq!(field(CustomFieldType::Enum).parse_value("large"), "large".to_string());
+        assert_err!(field(CustomFieldType::Enum).parse_value("medium"));
+    }
+
+    #[test]
+    fn text_longer_than_1024_graphemes_is_rejected() {
+        assert_err!(field(CustomFieldType::Text).parse_value(&"a".repeat(1025)));
+    }
+
+    #[test]
+    fn reserved_field_names_are_rejected() {
+        assert_err!(CustomFieldName::parse("email".into()));
+        assert_err!(CustomFieldName::parse("first name".into()));
+    }
+}
@@ -1,7 +1,15 @@
+mod custom_field;
 mod new_subscriber;
+mod send_time;
 mod subscriber_email;
+mod subscriber_list;
 mod subscriber_name;
+mod subscriber_tag;
 
+pub use custom_field::{CustomField, CustomFieldName, CustomFieldType, CustomFieldValue};
 pub use new_subscriber::NewSubscriber;
+pub use send_time::SendTime;
 pub use subscriber_name::SubscriberName;
 pub use subscriber_email::SubscriberEmail;
+pub use subscriber_list::SubscriberList;
+pub use subscriber_tag::SubscriberTag;
@@ -1,8 +1,10 @@
 use crate::domain::subscriber_name::SubscriberName;
 use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::custom_field::CustomFieldValue;
 
 #[derive(Debug)]
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    pub custom_fields: Vec<CustomFieldValue>,
 }
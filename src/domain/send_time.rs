@@ -0,0 +1,84 @@
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// The moment a scheduled newsletter goes out, always in the future.
+#[derive(Debug, Clone, Copy)]
+pub struct SendTime(DateTime<Utc>);
+
+impl SendTime {
+    /// Parse a `YYYY-MM-DDTHH:MM` wall-clock time (as sent by a `datetime-local`
+    /// input) in the IANA `timezone`, e.g. `Europe/Paris`, defaulting to UTC.
+    pub fn parse(local: &str, timezone: &str, now: DateTime<Utc>) -> Result<SendTime, String> {
+        let timezone = match timezone.trim() {
+            "" => "UTC",
+            timezone => timezone,
+        };
+        let tz: Tz = timezone
+            .parse()
+            .map_err(|_| format!("'{}' is not a known timezone.", timezone))?;
+        let local = local.trim();
+        let naive = NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M:%S"))
+            .map_err(|_| format!("'{}' is not a date and time in the YYYY-MM-DDTHH:MM format.", local))?;
+        // Ambiguous times, when clocks go back, resolve to the first occurrence.
+        let send_time = tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .ok_or_else(|| format!("{} does not exist in {}.", local, timezone))?
+            .with_timezone(&Utc);
+        if send_time <= now {
+            return Err("The scheduled time must be in the future.".into());
+        }
+        Ok(Self(send_time))
+    }
+}
+
+impl AsRef<DateTime<Utc>> for SendTime {
+    fn as_ref(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SendTime;
+    use chrono::{TimeZone, Utc};
+    use claims::assert_err;
+
+    fn now() -> chrono::DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn local_times_are_converted_to_utc() {
+        let send_time = SendTime::parse("2024-07-02T07:00", "Europe/Paris", now()).unwrap();
+        assert_eq!(*send_time.as_ref(), Utc.with_ymd_and_hms(2024, 7, 2, 5, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn an_empty_timezone_means_utc() {
+        let send_time = SendTime::parse("2024-07-02T07:00", " ", now()).unwrap();
+        assert_eq!(*send_time.as_ref(), Utc.with_ymd_and_hms(2024, 7, 2, 7, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn unknown_timezones_are_rejected() {
+        assert_err!(SendTime::parse("2024-07-02T07:00", "Mars/Olympus_Mons", now()));
+    }
+
+    #[test]
+    fn times_skipped_by_daylight_saving_are_rejected() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_err!(SendTime::parse("2024-03-31T02:30", "Europe/Paris", now));
+    }
+
+    #[test]
+    fn past_times_are_rejected() {
+        assert_err!(SendTime::parse("2024-07-01T07:00", "UTC", now()));
+    }
+
+    #[test]
+    fn malformed_times_are_rejected() {
+        assert_err!(SendTime::parse("tomorrow at 7", "UTC", now()));
+    }
+}
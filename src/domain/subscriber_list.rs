@@ -0,0 +1,47 @@
+use crate::domain::SubscriberTag;
+
+/// The name of a list a subscriber belongs to. Lists follow the same rules as tags.
+#[derive(Debug, Clone)]
+pub struct SubscriberList(String);
+
+impl SubscriberList {
+    pub fn parse(s: String) -> Result<SubscriberList, String> {
+        let error = format!(
+            "'{}' is not a valid list name. List names are 1 to 64 letters, digits, '-' or '_'.",
+            s.trim()
+        );
+        SubscriberTag::parse(s)
+            .map(|tag| Self(tag.as_ref().to_owned()))
+            .map_err(|_| error)
+    }
+}
+
+impl AsRef<str> for SubscriberList {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for SubscriberList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberList;
+    use claims::assert_err;
+
+    #[test]
+    fn list_names_are_trimmed_and_lowercased() {
+        let list = SubscriberList::parse(" Weekly-Digest ".into()).unwrap();
+        assert_eq!(list.as_ref(), "weekly-digest");
+    }
+
+    #[test]
+    fn list_names_with_spaces_are_rejected() {
+        let error = assert_err!(SubscriberList::parse("weekly digest".into()));
+        assert!(error.contains("is not a valid list name."));
+    }
+}
@@ -0,0 +1,65 @@
+#[derive(Debug, Clone)]
+pub struct SubscriberTag(String);
+
+impl SubscriberTag {
+    pub fn parse(s: String) -> Result<SubscriberTag, String> {
+        let s = s.trim().to_lowercase();
+        let is_empty = s.is_empty();
+        let is_too_long = s.chars().count() > 64;
+        let has_forbidden_characters = s
+            .chars()
+            .any(|c| !(c.is_alphanumeric() || c == '-' || c == '_'));
+        if is_empty || is_too_long || has_forbidden_characters {
+            Err(format!(
+                "'{}' is not a valid tag. Tags are 1 to 64 letters, digits, '-' or '_'.",
+                s
+            ))
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for SubscriberTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberTag;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        let tag = SubscriberTag::parse("  Early-Adopters ".into()).unwrap();
+        assert_eq!(tag.as_ref(), "early-adopters");
+    }
+
+    #[test]
+    fn a_64_character_tag_is_valid() {
+        assert_ok!(SubscriberTag::parse("a".repeat(64)));
+    }
+
+    #[test]
+    fn a_tag_longer_than_64_characters_is_rejected() {
+        assert_err!(SubscriberTag::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn whitespace_only_is_rejected() {
+        assert_err!(SubscriberTag::parse(" ".into()));
+    }
+
+    #[test]
+    fn tag_with_spaces_is_rejected() {
+        assert_err!(SubscriberTag::parse("early adopters".into()));
+    }
+}
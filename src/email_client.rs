@@ -2,6 +2,7 @@ use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
 
 use crate::domain::SubscriberEmail;
+use crate::suppression_list::SuppressionList;
 
 
 #[derive(Clone, Debug)]
@@ -10,27 +11,55 @@ pub struct EmailClient {
     http_client: Client,
     base_url: String,
     authorization_token: Secret<String>,
+    suppression_list: SuppressionList,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendEmailError {
+    #[error("{0} is on the suppression list.")]
+    Suppressed(String),
+    #[error("Failed to check the suppression list.")]
+    SuppressionCheckFailed(#[source] sqlx::Error),
+    #[error(transparent)]
+    RequestFailed(#[from] reqwest::Error),
 }
 
 impl EmailClient {
-    pub fn new(base_url: String, sender: SubscriberEmail, authorization_token: Secret<String>, timeout: std::time::Duration) -> Self {
+    /// Every email is checked against `suppression_list` before it is sent.
+    pub fn new(
+        base_url: String,
+        sender: SubscriberEmail,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+        suppression_list: SuppressionList,
+    ) -> Self {
         let http_client = Client::builder()
             .timeout(timeout)
             .build()
             .expect("Failed to create HTTP client.");
         Self {
             sender,
-            http_client: http_client,
+            http_client,
             base_url,
             authorization_token,
+            suppression_list,
         }
     }
-    pub async fn send_email(&self, recipient: &SubscriberEmail, subject: &str, html_content: &str, text_content: &str) -> Result<(), reqwest::Error> {
+
+    pub async fn send_email(&self, recipient: &SubscriberEmail, subject: &str, html_content: &str, text_content: &str) -> Result<(), SendEmailError> {
+        if self
+            .suppression_list
+            .contains(recipient.as_ref())
+            .await
+            .map_err(SendEmailError::SuppressionCheckFailed)?
+        {
+            return Err(SendEmailError::Suppressed(recipient.to_string()));
+        }
         let url = format!("{}/email", self.base_url);
         let request_body = SendEmailRequest {
             from: self.sender.as_ref(),
             to: recipient.as_ref(),
-            subject: subject,
+            subject,
             html_body: html_content,
             text_body: text_content,
         };
@@ -65,6 +94,7 @@ mod tests {
     use wiremock::{Mock, MockServer, Request, ResponseTemplate};
     use secrecy::Secret;
     use crate::domain::SubscriberEmail;
+    use crate::suppression_list::SuppressionList;
     use claims::{assert_err, assert_ok};
 
     use super::EmailClient;
@@ -86,7 +116,8 @@ mod tests {
             base_url, 
             email(), 
             Secret::new(Faker.fake()), 
-            std::time::Duration::from_millis(200))
+            std::time::Duration::from_millis(200),
+            SuppressionList::Empty)
     }
 
     struct SendEmailBodyMatcher;
@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::content::{add_open_pixel, rewrite_links};
+use crate::email_client::{EmailClient, SendEmailError};
+use crate::routes::{
+    click_tracking_url, complete_issue_if_done, get_confirmed_subscriber, get_custom_fields, get_issue, PreparedIssue,
+};
+use crate::startup::{get_connection_pool, HmacSecret};
+
+/// A failed delivery is retried with an exponential backoff, this many times at most.
+const MAX_RETRIES: i32 = 3;
+const FIRST_RETRY_DELAY_SECONDS: i32 = 30;
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+/// The issue being delivered, prepared once and reused for each of its tasks.
+/// Only drafts can be edited, so an issue cannot change once its delivery has started.
+#[derive(Default)]
+pub struct PreparedIssueCache {
+    current: Option<CachedIssue>,
+}
+
+struct CachedIssue {
+    newsletter_issue_id: Uuid,
+    prepared: PreparedIssue,
+    track_opens: bool,
+}
+
+impl PreparedIssueCache {
+    async fn get(&mut self, newsletter_issue_id: Uuid, pool: &PgPool) -> Result<&CachedIssue, anyhow::Error> {
+        let cached = match self.current.take() {
+            Some(cached) if cached.newsletter_issue_id == newsletter_issue_id => cached,
+            _ => {
+                let issue = get_issue(newsletter_issue_id, pool)
+                    .await?
+                    .context("The newsletter issue has disappeared.")?;
+                let custom_fields = get_custom_fields(pool).await?;
+                let prepared = issue
+                    .prepare(&custom_fields)
+                    .context("The newsletter template is no longer valid.")?;
+                CachedIssue {
+                    newsletter_issue_id,
+                    prepared,
+                    track_opens: issue.track_opens,
+                }
+            }
+        };
+        Ok(self.current.insert(cached))
+    }
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client(connection_pool.clone());
+    let hmac_secret = HmacSecret(configuration.application.hmac_secret);
+    worker_loop(connection_pool, email_client, configuration.application.base_url, hmac_secret).await
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    hmac_secret: HmacSecret,
+) -> Result<(), anyhow::Error> {
+    let mut issues = PreparedIssueCache::default();
+    loop {
+        match try_execute_task(&pool, &email_client, &base_url, &hmac_secret, &mut issues).await {
+            Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(Duration::from_secs(10)).await,
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        newsletter_issue_id=tracing::field::Empty,
+        subscriber_email=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &HmacSecret,
+    issues: &mut PreparedIssueCache,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((mut transaction, task)) = dequeue_task(pool).await? else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("newsletter_issue_id", display(task.newsletter_issue_id))
+        .record("subscriber_email", display(&task.subscriber_email));
+    match deliver(&task, pool, email_client, base_url, hmac_secret, issues).await {
+        Ok(true) => finish_task(&mut transaction, &task, "delivered").await?,
+        Ok(false) => finish_task(&mut transaction, &task, "skipped").await?,
+        Err(e) if task.n_retries < MAX_RETRIES => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver issue to a confirmed subscriber. Retrying later."
+            );
+            retry_task(&mut transaction, &task, &format!("{:#}", e)).await?;
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver issue to a confirmed subscriber. Giving up."
+            );
+            fail_task(&mut transaction, &task, &format!("{:#}", e)).await?;
+        }
+    }
+    transaction.commit().await?;
+    complete_issue_if_done(task.newsletter_issue_id, pool).await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    subscriber_email: String,
+    tracking_token: Option<String>,
+    n_retries: i32,
+}
+
+/// Lock the next pending task of an issue that is being sent.
+/// Tasks of paused issues stay in the queue until the issue is resumed.
+async fn dequeue_task(pool: &PgPool) -> Result<Option<(Transaction<'static, Postgres>, Task)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        Task,
+        r#"
+        SELECT t.newsletter_issue_id, t.subscriber_id, t.subscriber_email, t.tracking_token, t.n_retries
+        FROM issue_delivery_tasks t
+        JOIN newsletter_issues i ON i.newsletter_issue_id = t.newsletter_issue_id
+        WHERE t.status = 'pending'
+          AND t.execute_after <= now()
+          AND i.status = 'sending'
+        ORDER BY t.execute_after
+        FOR UPDATE OF t
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    Ok(task.map(|task| (transaction, task)))
+}
+
+/// Render and send the issue to the task's subscriber.
+/// Returns `false` when the subscriber is no longer confirmed or their address is suppressed.
+async fn deliver(
+    task: &Task,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    hmac_secret: &HmacSecret,
+    issues: &mut PreparedIssueCache,
+) -> Result<bool, anyhow::Error> {
+    let Some(subscriber) = get_confirmed_subscriber(pool, task.subscriber_id).await? else {
+        return Ok(false);
+    };
+    let subscriber = subscriber?;
+    let issue = issues.get(task.newsletter_issue_id, pool).await?;
+    let mut content = issue.prepared.render(&subscriber.template_context(base_url));
+    if let Some(token) = &task.tracking_token {
+        content.html = rewrite_links(&content.html, |url| {
+            is_external_link(url, base_url).then(|| click_tracking_url(base_url, token, url, hmac_secret))
+        });
+        if issue.track_opens {
+            content.html = add_open_pixel(&content.html, &format!("{}/o/{}", base_url, token));
+        }
+    }
+    match email_client
+        .send_email(&subscriber.email, &content.title, &content.html, &content.text)
+        .await
+    {
+        Err(SendEmailError::Suppressed(_)) => Ok(false),
+        outcome => {
+            outcome.with_context(|| format!("Failed to send newsletter to {}", &subscriber.email))?;
+            Ok(true)
+        }
+    }
+}
+
+/// Links back to the application, such as the unsubscribe link, are not tracked.
+fn is_external_link(url: &str, base_url: &str) -> bool {
+    (url.starts_with("http://") || url.starts_with("https://")) && !url.starts_with(base_url)
+}
+
+async fn finish_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    status: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_tasks
+        SET status = $3, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+        status,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Give up on a task, keeping the error chain of the last attempt for the delivery report.
+async fn fail_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    error: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_tasks
+        SET status = 'failed', last_error = $3, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+        error,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+async fn retry_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    error: &str,
+) -> Result<(), anyhow::Error> {
+    let delay_seconds = FIRST_RETRY_DELAY_SECONDS << task.n_retries;
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_tasks
+        SET n_retries = n_retries + 1,
+            execute_after = now() + make_interval(secs => $3),
+            last_error = $4,
+            updated_at = now()
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+        f64::from(delay_seconds),
+        error,
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
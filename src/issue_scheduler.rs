@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use crate::configuration::Settings;
+use crate::routes::{enqueue_delivery_tasks, get_custom_fields, get_issue, get_segment, set_issue_status, IssueStatus, NewsletterIssue};
+use crate::startup::get_connection_pool;
+
+pub enum ExecutionOutcome {
+    IssueProcessed,
+    NoDueIssue,
+}
+
+pub async fn run_scheduler_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    scheduler_loop(connection_pool).await
+}
+
+async fn scheduler_loop(pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        match try_publish_due_issue(&pool).await {
+            Ok(ExecutionOutcome::NoDueIssue) => tokio::time::sleep(Duration::from_secs(10)).await,
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+            Ok(ExecutionOutcome::IssueProcessed) => {}
+        }
+    }
+}
+
+/// Hand the scheduled issue that has been due the longest, if any, over to the
+/// delivery worker. An issue that can no longer be sent goes back to drafts.
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id=tracing::field::Empty),
+    err
+)]
+pub async fn try_publish_due_issue(pool: &PgPool) -> Result<ExecutionOutcome, anyhow::Error> {
+    // The claim and the delivery tasks are committed together: if anything fails in between,
+    // the issue is still scheduled rather than stuck in `sending` with nobody to deliver it.
+    let mut transaction = pool.begin().await?;
+    let Some(issue_id) = claim_due_issue(&mut transaction).await? else {
+        return Ok(ExecutionOutcome::NoDueIssue);
+    };
+    Span::current().record("newsletter_issue_id", display(issue_id));
+    let issue = get_issue(issue_id, pool)
+        .await?
+        .context("The claimed newsletter issue has disappeared.")?;
+    match publish_issue(&issue, &mut transaction, pool).await {
+        Ok(()) => transaction.commit().await?,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to publish a scheduled newsletter issue. \
+                It has been moved back to drafts."
+            );
+            // Release the claim before updating the issue outside of it.
+            transaction.rollback().await?;
+            set_issue_status(issue_id, IssueStatus::Draft, pool).await?;
+        }
+    }
+    Ok(ExecutionOutcome::IssueProcessed)
+}
+
+/// Move a due issue from `scheduled` to `sending`, so that it is picked up by exactly one
+/// of the running instances. The issue stays locked until `transaction` ends.
+async fn claim_due_issue(transaction: &mut Transaction<'_, Postgres>) -> Result<Option<Uuid>, anyhow::Error> {
+    let claimed = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', updated_at = now()
+        WHERE newsletter_issue_id = (
+            SELECT newsletter_issue_id
+            FROM newsletter_issues
+            WHERE status = 'scheduled' AND scheduled_for <= now()
+            ORDER BY scheduled_for
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+        )
+        RETURNING newsletter_issue_id
+        "#
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to claim a due newsletter issue.")?;
+    Ok(claimed.map(|r| r.newsletter_issue_id))
+}
+
+async fn publish_issue(
+    issue: &NewsletterIssue,
+    transaction: &mut Transaction<'_, Postgres>,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let custom_fields = get_custom_fields(pool).await?;
+    issue
+        .prepare(&custom_fields)
+        .context("The newsletter template is no longer valid.")?;
+    let segment = match issue.segment_id {
+        Some(segment_id) => Some(
+            get_segment(segment_id, pool)
+                .await?
+                .context("The segment of the newsletter issue no longer exists.")?,
+        ),
+        None => None,
+    };
+    enqueue_delivery_tasks(transaction, issue.newsletter_issue_id, segment.as_ref()).await?;
+    Ok(())
+}
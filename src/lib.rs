@@ -1,10 +1,16 @@
 pub mod configuration;
+pub mod content;
 pub mod routes;
 pub mod startup;
 pub mod telemetry;
 pub mod domain;
 pub mod email_client;
 pub mod authentication;
+pub mod session_cleanup;
 pub mod session_state;
 mod utils;
-pub mod idempotency;
\ No newline at end of file
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod issue_scheduler;
+pub mod suppression_list;
+pub mod templating;
\ No newline at end of file
@@ -1,3 +1,9 @@
+use std::fmt::{Debug, Display};
+
+use tokio::task::JoinError;
+use zero2prod::issue_delivery_worker::run_worker_until_stopped;
+use zero2prod::issue_scheduler::run_scheduler_until_stopped;
+use zero2prod::session_cleanup::run_session_cleanup_until_stopped;
 use zero2prod::startup::Application;
 use zero2prod::configuration::get_configuration;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
@@ -9,7 +15,41 @@ async fn main() -> anyhow::Result<()> {
     init_subscriber(subscriber);
 
     let config = get_configuration().expect("Failed to read configuration.");
-    let application = Application::build(config).await?;
-    application.run_until_stopped().await?;
+    let application = Application::build(config.clone()).await?;
+    let application_task = tokio::spawn(application.run_until_stopped());
+    let scheduler_task = tokio::spawn(run_scheduler_until_stopped(config.clone()));
+    let session_cleanup_task = tokio::spawn(run_session_cleanup_until_stopped(config.clone()));
+    let worker_task = tokio::spawn(run_worker_until_stopped(config));
+
+    tokio::select! {
+        outcome = application_task => report_exit("API", outcome),
+        outcome = scheduler_task => report_exit("Newsletter scheduler", outcome),
+        outcome = worker_task => report_exit("Background worker", outcome),
+        outcome = session_cleanup_task => report_exit("Session cleanup", outcome),
+    };
     Ok(())
-}
\ No newline at end of file
+}
+
+fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} failed",
+                task_name
+            )
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} task failed to complete",
+                task_name
+            )
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::fmt::Write;
+
+use crate::authentication::{CsrfToken, UserId};
+use crate::domain::CustomField;
+use crate::utils::e500;
+
+pub async fn list_custom_fields(
+    _user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for field in get_custom_fields(&pool).await.map_err(e500)? {
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+            <td><form action=\"/admin/fields/delete\" method=\"post\">{}\
+            <input hidden type=\"text\" name=\"field_id\" value=\"{}\">\
+            <button type=\"submit\">Delete</button></form></td></tr>",
+            field.name,
+            field.field_type.as_str(),
+            htmlescape::encode_minimal(&field.options.join(", ")),
+            if field.required { "yes" } else { "no" },
+            csrf_field,
+            field.field_id,
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(include_str!("custom_fields.html"), msg_html = msg_html, rows_html = rows_html, csrf_field = csrf_field)))
+}
+
+#[tracing::instrument(
+    name="Get custom fields.",
+    skip(pool)
+)]
+pub async fn get_custom_fields(pool: &PgPool) -> anyhow::Result<Vec<CustomField>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT field_id, name, field_type, options, required
+        FROM custom_fields
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve custom fields.")?;
+
+    rows.into_iter()
+        .map(|r| {
+            let field_type = r.field_type.try_into().map_err(|e: String| anyhow::anyhow!(e))?;
+            Ok(CustomField {
+                field_id: r.field_id,
+                name: r.name,
+                field_type,
+                options: r.options,
+                required: r.required,
+            })
+        })
+        .collect()
+}
+
+/// Restrict a query over `subscriptions s` to subscribers whose `field_name` field holds `value`.
+pub fn push_field_value_condition<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    field_name: &'a str,
+    value: Option<&'a str>,
+) {
+    query
+        .push(
+            " AND EXISTS (SELECT 1 FROM subscriber_custom_fields v \
+            JOIN custom_fields f ON f.field_id = v.field_id \
+            WHERE v.subscriber_id = s.id AND f.name = ",
+        )
+        .push_bind(field_name)
+        .push(" AND v.value = ")
+        .push_bind(value)
+        .push(")");
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::{get_custom_fields, list_custom_fields, push_field_value_condition};
+pub use post::{create_custom_field, delete_custom_field};
@@ -0,0 +1,108 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::domain::{CustomFieldName, CustomFieldType};
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    field_type: String,
+    #[serde(default)]
+    options: String,
+    #[serde(default)]
+    required: bool,
+}
+
+#[tracing::instrument(
+    name="Create a custom field.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn create_custom_field(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData { name, field_type, options, required } = form.0;
+    let name = match CustomFieldName::parse(name) {
+        Ok(name) => name,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/fields"));
+        }
+    };
+    let field_type: CustomFieldType = match field_type.try_into() {
+        Ok(field_type) => field_type,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/fields"));
+        }
+    };
+    let options: Vec<String> = options
+        .split(',')
+        .map(|o| o.trim().to_owned())
+        .filter(|o| !o.is_empty())
+        .collect();
+    if field_type == CustomFieldType::Enum && options.is_empty() {
+        FlashMessage::error("Enum fields need at least one option.").send();
+        return Ok(see_other("/admin/fields"));
+    }
+    let options = if field_type == CustomFieldType::Enum { options } else { vec![] };
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO custom_fields (field_id, name, field_type, options, required, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        name.as_ref(),
+        field_type.as_str(),
+        &options,
+        required,
+        Utc::now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to insert custom field.")
+    .map_err(e500)?
+    .rows_affected();
+
+    if inserted == 0 {
+        FlashMessage::error(format!("A field named '{}' already exists.", name.as_ref())).send();
+    } else {
+        FlashMessage::info(format!("The field '{}' has been saved.", name.as_ref())).send();
+    }
+    Ok(see_other("/admin/fields"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    field_id: String,
+}
+
+#[tracing::instrument(
+    name="Delete a custom field.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn delete_custom_field(
+    form: web::Form<DeleteFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let field_id = Uuid::parse_str(&form.field_id).map_err(e400)?;
+    sqlx::query!(r#"DELETE FROM custom_fields WHERE field_id = $1"#, field_id)
+        .execute(pool.get_ref())
+        .await
+        .context("Failed to delete custom field.")
+        .map_err(e500)?;
+    FlashMessage::info("The field has been deleted.").send();
+    Ok(see_other("/admin/fields"))
+}
@@ -3,14 +3,29 @@ use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{authentication::UserId, utils::e500};
+use crate::{authentication::{CsrfToken, Role, UserId}, utils::e500};
 
-pub async fn admin_dashboard(user_id: web::ReqData<UserId>, pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
-    let username = get_username(*user_id.into_inner(), &pool).await.map_err(e500)?;
+pub async fn admin_dashboard(
+    user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let users_link = if user_id.role() == Role::Owner {
+        r#"<li> <a href="/admin/users">Manage users</a></li>"#
+    } else {
+        ""
+    };
             
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(format!(include_str!("dashboard.html"), username = username)
+        .body(format!(
+            include_str!("dashboard.html"),
+            username = username,
+            users_link = users_link,
+            csrf_field = csrf_token.form_field(),
+        )
         )
     )
 }
@@ -1,11 +1,14 @@
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
 
-use crate::{authentication::UserId, session_state::TypedSession, utils::see_other};
+use crate::{authentication::{revoke_session, UserId}, session_state::TypedSession, utils::{e500, see_other}};
 
-pub async fn log_out(_user_id: web::ReqData<UserId>, session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    
+pub async fn log_out(user_id: web::ReqData<UserId>, session: TypedSession, pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        revoke_session(**user_id, session_id, &pool).await.map_err(e500)?;
+    }
     session.log_out();
     FlashMessage::info("You have successfully logged out.").send(); 
     Ok(see_other("/login"))
-}
\ No newline at end of file
+}
@@ -1,9 +1,23 @@
+pub mod custom_fields;
 pub mod dashboard;
 pub mod password;
 pub mod logout;
 pub mod newsletters;
+pub mod segments;
+pub mod sessions;
+pub mod subscribers;
+pub mod suppressions;
+pub mod totp;
+pub mod users;
 
+pub use custom_fields::{create_custom_field, delete_custom_field, get_custom_fields, list_custom_fields};
 pub use dashboard::admin_dashboard;
 pub use password::*;
 pub use logout::log_out;
-pub use newsletters::*;
\ No newline at end of file
+pub use newsletters::*;
+pub use segments::{create_segment, delete_segment, get_segment, list_segments};
+pub use sessions::*;
+pub use subscribers::*;
+pub use suppressions::*;
+pub use totp::*;
+pub use users::*;
\ No newline at end of file
@@ -0,0 +1,133 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::utils::{e400, e500, see_other};
+
+use super::issue::get_delivery_progress;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    issue_id: String,
+}
+
+/// Hold the remaining deliveries of an issue that is being sent.
+#[tracing::instrument(
+    name="Pause a newsletter delivery.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn pause_delivery(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = Uuid::parse_str(&form.issue_id).map_err(e400)?;
+    let paused = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'paused', updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'sending'
+        RETURNING title
+        "#,
+        issue_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to pause newsletter delivery.")
+    .map_err(e500)?;
+    match paused {
+        Some(issue) => {
+            let progress = get_delivery_progress(issue_id, &pool).await.map_err(e500)?;
+            FlashMessage::info(format!(
+                "Sending '{}' is paused after {} of {} deliveries.",
+                issue.title, progress.delivered, progress.total
+            )).send();
+        }
+        None => FlashMessage::error("Only issues that are being sent can be paused.").send(),
+    }
+    Ok(see_other("/admin/newsletters/issues"))
+}
+
+#[tracing::instrument(
+    name="Resume a newsletter delivery.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn resume_delivery(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = Uuid::parse_str(&form.issue_id).map_err(e400)?;
+    let resumed = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sending', updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'paused'
+        RETURNING title
+        "#,
+        issue_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to resume newsletter delivery.")
+    .map_err(e500)?;
+    match resumed {
+        Some(issue) => FlashMessage::info(format!("Sending '{}' has resumed.", issue.title)).send(),
+        None => FlashMessage::error("Only paused issues can be resumed.").send(),
+    }
+    Ok(see_other("/admin/newsletters/issues"))
+}
+
+/// Stop an issue that is being sent or paused, discarding its remaining deliveries.
+#[tracing::instrument(
+    name="Cancel a newsletter delivery.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn cancel_delivery(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = Uuid::parse_str(&form.issue_id).map_err(e400)?;
+    let cancelled = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'cancelled', updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status IN ('sending', 'paused')
+        RETURNING title
+        "#,
+        issue_id,
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to cancel newsletter delivery.")
+    .map_err(e500)?;
+    let Some(issue) = cancelled else {
+        FlashMessage::error("Only issues that are being sent or paused can be cancelled.").send();
+        return Ok(see_other("/admin/newsletters/issues"));
+    };
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_tasks
+        SET status = 'cancelled', updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'pending'
+        "#,
+        issue_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to discard pending deliveries.")
+    .map_err(e500)?;
+    let progress = get_delivery_progress(issue_id, &pool).await.map_err(e500)?;
+    FlashMessage::info(format!(
+        "Sending '{}' has been cancelled after {} of {} deliveries. {} remaining deliveries were discarded.",
+        issue.title, progress.delivered, progress.total, progress.cancelled
+    )).send();
+    Ok(see_other("/admin/newsletters/issues"))
+}
@@ -0,0 +1,150 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::email_client::{EmailClient, SendEmailError};
+use crate::routes::admin::custom_fields::get_custom_fields;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e400, e500, see_other};
+
+use super::issue::{sample_context, save_issue, IssueStatus, NewsletterIssue};
+use super::post::FormData;
+
+#[tracing::instrument(
+    name="Save a newsletter draft.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn save_draft(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = match save_form_as_draft(&form, &pool).await? {
+        Ok(issue) => issue,
+        Err(response) => return Ok(response),
+    };
+    FlashMessage::info(format!("The draft '{}' has been saved.", issue.title)).send();
+    Ok(see_other(&format!("/admin/newsletters/issues/{}/edit", issue.newsletter_issue_id)))
+}
+
+/// Send the issue, rendered for a sample recipient, to the addresses listed in the form.
+/// The issue is rendered straight from the submitted form: nothing is saved, so a test
+/// send never touches the stored issue or its delivery state.
+#[tracing::instrument(
+    name="Send a test newsletter.",
+    skip(form, pool, email_client, base_url, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn send_test_newsletter(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = form.issue(IssueStatus::Draft).map_err(e400)?;
+    let edit_url = form.form_url().map_err(e400)?;
+    if issue.title.trim().is_empty() {
+        FlashMessage::error("The newsletter title cannot be empty.").send();
+        return Ok(see_other(&edit_url));
+    }
+    let recipients = match form.test_recipients() {
+        Ok(recipients) if recipients.is_empty() => {
+            FlashMessage::error("Enter at least one address to send the test email to.").send();
+            return Ok(see_other(&edit_url));
+        }
+        Ok(recipients) => recipients,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&edit_url));
+        }
+    };
+    let custom_fields = get_custom_fields(&pool).await.map_err(e500)?;
+    let prepared = match issue.prepare(&custom_fields) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            FlashMessage::error(format!("The newsletter template is invalid: {}", e)).send();
+            return Ok(see_other(&edit_url));
+        }
+    };
+    for warning in &prepared.warnings {
+        FlashMessage::warning(warning.clone()).send();
+    }
+    let mut sent_to = Vec::new();
+    for recipient in &recipients {
+        let content = prepared.render(&sample_context(&base_url.0, recipient.as_ref()));
+        let title = format!("[TEST] {}", content.title);
+        match email_client.send_email(recipient, &title, &content.html, &content.text).await {
+            Ok(()) => sent_to.push(recipient.as_ref()),
+            Err(SendEmailError::Suppressed(_)) => {
+                FlashMessage::warning(format!("{} is on the suppression list, no test email was sent to it.", recipient)).send();
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    "Failed to send a test newsletter to {}.", recipient
+                );
+                FlashMessage::error(format!("Failed to send the test email to {}.", recipient)).send();
+                return Ok(see_other(&edit_url));
+            }
+        }
+    }
+    if !sent_to.is_empty() {
+        FlashMessage::info(format!("A test email has been sent to {}.", sent_to.join(", "))).send();
+    }
+    Ok(see_other(&edit_url))
+}
+
+/// Save the form as a draft, or return the redirect explaining why it could not be saved.
+async fn save_form_as_draft(
+    form: &FormData,
+    pool: &PgPool,
+) -> Result<Result<NewsletterIssue, HttpResponse>, actix_web::Error> {
+    let issue = form.issue(IssueStatus::Draft).map_err(e400)?;
+    if issue.title.trim().is_empty() {
+        FlashMessage::error("The newsletter title cannot be empty.").send();
+        return Ok(Err(see_other(&form.form_url().map_err(e400)?)));
+    }
+    if !save_issue(&issue, pool).await.map_err(e500)? {
+        FlashMessage::error("Only drafts can be edited.").send();
+        return Ok(Err(see_other("/admin/newsletters/issues")));
+    }
+    Ok(Ok(issue))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    issue_id: String,
+}
+
+#[tracing::instrument(
+    name="Delete a newsletter draft.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn delete_draft(
+    form: web::Form<DeleteFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = Uuid::parse_str(&form.issue_id).map_err(e400)?;
+    let deleted = sqlx::query!(
+        r#"DELETE FROM newsletter_issues WHERE newsletter_issue_id = $1 AND status = 'draft'"#,
+        issue_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete newsletter draft.")
+    .map_err(e500)?
+    .rows_affected();
+    if deleted == 0 {
+        FlashMessage::error("Only drafts can be deleted.").send();
+    } else {
+        FlashMessage::info("The draft has been deleted.").send();
+    }
+    Ok(see_other("/admin/newsletters/issues"))
+}
@@ -1,19 +1,203 @@
 use actix_web::{http::header::ContentType, web, HttpResponse};
-use actix_web_flash_messages::IncomingFlashMessages;
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
 use std::fmt::Write;
+use uuid::Uuid;
 
-use crate::authentication::UserId;
+use crate::authentication::{CsrfToken, UserId};
+use crate::routes::admin::segments::get_segments;
+use crate::utils::{e404, e500, see_other};
 
+use super::count_confirmed_subscribers;
+use super::issue::{get_delivery_progress_by_issue, get_issue, get_issues, ContentFormat, IssueStatus, NewsletterIssue};
 
-pub async fn send_newsletter_form(_user_id: web::ReqData<UserId>, flash_messages: IncomingFlashMessages) -> Result<HttpResponse, actix_web::Error> {
+
+pub async fn send_newsletter_form(
+    _user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    newsletter_form(None, &csrf_token, &pool, &flash_messages).await
+}
+
+pub async fn edit_newsletter_form(
+    _user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    issue_id: web::Path<Uuid>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = get_issue(issue_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("There is no newsletter issue with the provided id."))?;
+    if issue.status != IssueStatus::Draft {
+        FlashMessage::error("Only drafts can be edited.").send();
+        return Ok(see_other("/admin/newsletters/issues"));
+    }
+    newsletter_form(Some(&issue), &csrf_token, &pool, &flash_messages).await
+}
+
+/// The newsletter form, pre-filled with `issue` when editing a draft.
+async fn newsletter_form(
+    issue: Option<&NewsletterIssue>,
+    csrf_token: &CsrfToken,
+    pool: &PgPool,
+    flash_messages: &IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf_token.form_field();
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let selected_segment = issue.and_then(|i| i.segment_id);
+    let mut segment_options = String::new();
+    let all_recipients = count_confirmed_subscribers(pool, None).await.map_err(e500)?;
+    writeln!(segment_options, "<option value=\"\">All confirmed subscribers ({} recipients)</option>", all_recipients).unwrap();
+    for segment in get_segments(pool).await.map_err(e500)? {
+        let recipients = count_confirmed_subscribers(pool, Some(&segment)).await.map_err(e500)?;
+        writeln!(
+            segment_options,
+            "<option value=\"{}\"{}>{} ({} recipients)</option>",
+            segment.segment_id,
+            selected(selected_segment == Some(segment.segment_id)),
+            encode_minimal(&segment.name),
+            recipients
+        ).unwrap();
+    }
+    let draft_links = match issue {
+        Some(issue) => format!(
+            "<p><a href=\"/admin/newsletters/issues/{id}/preview\">Preview</a></p>\
+            <form action=\"/admin/newsletters/issues/delete\" method=\"post\">{csrf_field}\
+            <input hidden type=\"text\" name=\"issue_id\" value=\"{id}\">\
+            <button type=\"submit\">Delete draft</button></form>",
+            id = issue.newsletter_issue_id,
+            csrf_field = csrf_field,
+        ),
+        None => String::new(),
+    };
+    let content_format = issue.map(|i| i.content_format).unwrap_or_default();
+    let utm_parameters = issue.and_then(|i| i.utm_parameters.as_ref());
     let idempotency_key = uuid::Uuid::new_v4();
     let response = HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(format!(include_str!("newsletter_form.html"), msg_html = msg_html, idempotency_key= idempotency_key));
+        .body(format!(
+            include_str!("newsletter_form.html"),
+            msg_html = msg_html,
+            title = encode_minimal(issue.map(|i| i.title.as_str()).unwrap_or_default()),
+            html_selected = selected(content_format == ContentFormat::Html),
+            markdown_selected = selected(content_format == ContentFormat::Markdown),
+            content_markdown = encode_minimal(issue.map(|i| i.content_markdown.as_str()).unwrap_or_default()),
+            content_html = encode_minimal(issue.map(|i| i.content_html.as_str()).unwrap_or_default()),
+            content_text = encode_minimal(issue.map(|i| i.content_text.as_str()).unwrap_or_default()),
+            segment_options = segment_options,
+            track_opens_checked = checked(issue.is_some_and(|i| i.track_opens)),
+            utm_checked = checked(utm_parameters.is_some()),
+            utm_source = encode_minimal(utm_parameters.map_or("newsletter", |u| u.source.as_str())),
+            utm_medium = encode_minimal(utm_parameters.map_or("email", |u| u.medium.as_str())),
+            utm_campaign = encode_minimal(utm_parameters.map_or("", |u| u.campaign.as_str())),
+            issue_id = issue.map(|i| i.newsletter_issue_id.to_string()).unwrap_or_default(),
+            idempotency_key = idempotency_key,
+            draft_links = draft_links,
+            csrf_field = csrf_field,
+        ));
 
     Ok(response)
-}
\ No newline at end of file
+}
+
+fn issue_action_form(issue_id: Uuid, action: &str, label: &str, csrf_field: &str) -> String {
+    format!(
+        "<form action=\"/admin/newsletters/issues/{}\" method=\"post\">{}\
+        <input hidden type=\"text\" name=\"issue_id\" value=\"{}\">\
+        <button type=\"submit\">{}</button></form>",
+        action, csrf_field, issue_id, label
+    )
+}
+
+fn selected(is_selected: bool) -> &'static str {
+    if is_selected { " selected" } else { "" }
+}
+
+fn checked(is_checked: bool) -> &'static str {
+    if is_checked { " checked" } else { "" }
+}
+
+pub async fn list_issues(
+    _user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut progress_by_issue = get_delivery_progress_by_issue(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for issue in get_issues(&pool).await.map_err(e500)? {
+        let id = issue.newsletter_issue_id;
+        let actions = match issue.status {
+            IssueStatus::Draft => format!(
+                "<a href=\"/admin/newsletters/issues/{id}/edit\">Edit</a> \
+                <a href=\"/admin/newsletters/issues/{id}/preview\">Preview</a>\
+                <form action=\"/admin/newsletters/issues/delete\" method=\"post\">{csrf_field}\
+                <input hidden type=\"text\" name=\"issue_id\" value=\"{id}\">\
+                <button type=\"submit\">Delete</button></form>"
+            ),
+            IssueStatus::Scheduled => format!(
+                "<a href=\"/admin/newsletters/issues/{id}/preview\">Preview</a>\
+                <form action=\"/admin/newsletters/issues/reschedule\" method=\"post\">{csrf_field}\
+                <input hidden type=\"text\" name=\"issue_id\" value=\"{id}\">\
+                <input type=\"datetime-local\" name=\"scheduled_for\">\
+                <input type=\"text\" name=\"timezone\" placeholder=\"UTC\">\
+                <button type=\"submit\">Reschedule</button></form>\
+                <form action=\"/admin/newsletters/issues/unschedule\" method=\"post\">{csrf_field}\
+                <input hidden type=\"text\" name=\"issue_id\" value=\"{id}\">\
+                <button type=\"submit\">Cancel schedule</button></form>"
+            ),
+            IssueStatus::Sending => format!(
+                "<a href=\"/admin/newsletters/issues/{id}\">Report</a> \
+                <a href=\"/admin/newsletters/issues/{id}/preview\">Preview</a>{}{}",
+                issue_action_form(id, "pause", "Pause", &csrf_field),
+                issue_action_form(id, "cancel", "Cancel sending", &csrf_field),
+            ),
+            IssueStatus::Paused => format!(
+                "<a href=\"/admin/newsletters/issues/{id}\">Report</a> \
+                <a href=\"/admin/newsletters/issues/{id}/preview\">Preview</a>{}{}",
+                issue_action_form(id, "resume", "Resume", &csrf_field),
+                issue_action_form(id, "cancel", "Cancel sending", &csrf_field),
+            ),
+            IssueStatus::Sent | IssueStatus::Cancelled => {
+                format!(
+                    "<a href=\"/admin/newsletters/issues/{id}\">Report</a> \
+                    <a href=\"/admin/newsletters/issues/{id}/preview\">Preview</a>"
+                )
+            }
+        };
+        let delivered = match issue.status {
+            IssueStatus::Draft | IssueStatus::Scheduled => String::new(),
+            _ => {
+                let progress = progress_by_issue.remove(&id).unwrap_or_default();
+                format!("{} of {}", progress.delivered, progress.total)
+            }
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            encode_minimal(&issue.title),
+            issue.status.as_str(),
+            delivered,
+            issue.scheduled_for.map(|d| d.format("%Y-%m-%d %H:%M UTC").to_string()).unwrap_or_default(),
+            issue.updated_at.format("%Y-%m-%d %H:%M"),
+            issue.published_at.map(|d| d.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default(),
+            actions,
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(include_str!("issues.html"), msg_html = msg_html, rows_html = rows_html)))
+}
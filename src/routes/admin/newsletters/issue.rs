@@ -0,0 +1,579 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::content::{html_to_text, lint_newsletter, markdown_to_content, sanitize_html, sanitize_rendered_html, UtmParameters};
+use crate::domain::CustomField;
+use crate::routes::admin::segments::Segment;
+use crate::templating::{NewsletterTemplate, RenderedNewsletter, TemplateError};
+use crate::utils::generate_token;
+
+use super::get_confirmed_subscribers;
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFormat {
+    #[default]
+    Html,
+    Markdown,
+}
+
+impl ContentFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentFormat::Html => "html",
+            ContentFormat::Markdown => "markdown",
+        }
+    }
+}
+
+impl TryFrom<String> for ContentFormat {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "html" => Ok(ContentFormat::Html),
+            "markdown" => Ok(ContentFormat::Markdown),
+            other => Err(format!("{} is not a supported content format.", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IssueStatus {
+    Draft,
+    Scheduled,
+    Sending,
+    Paused,
+    Sent,
+    Cancelled,
+}
+
+impl IssueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueStatus::Draft => "draft",
+            IssueStatus::Scheduled => "scheduled",
+            IssueStatus::Sending => "sending",
+            IssueStatus::Paused => "paused",
+            IssueStatus::Sent => "sent",
+            IssueStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl TryFrom<String> for IssueStatus {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "draft" => Ok(IssueStatus::Draft),
+            "scheduled" => Ok(IssueStatus::Scheduled),
+            "sending" => Ok(IssueStatus::Sending),
+            "paused" => Ok(IssueStatus::Paused),
+            "sent" => Ok(IssueStatus::Sent),
+            "cancelled" => Ok(IssueStatus::Cancelled),
+            other => Err(format!("{} is not a known issue status.", other)),
+        }
+    }
+}
+
+/// A newsletter issue as authored in the admin area.
+#[derive(Debug, Clone)]
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub content_format: ContentFormat,
+    pub content_markdown: String,
+    pub content_html: String,
+    pub content_text: String,
+    pub segment_id: Option<Uuid>,
+    pub track_opens: bool,
+    pub utm_parameters: Option<UtmParameters>,
+    pub status: IssueStatus,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// The template that goes out for an issue, along with any lint warnings.
+pub struct PreparedIssue {
+    pub template: NewsletterTemplate,
+    pub warnings: Vec<String>,
+    pub utm_parameters: Option<UtmParameters>,
+}
+
+impl PreparedIssue {
+    /// Render the issue for a recipient, tagging its external links when UTM parameters are set.
+    /// The recipient's unsubscribe link is left untouched.
+    pub fn render(&self, context: &HashMap<String, String>) -> RenderedNewsletter {
+        let mut content = self.template.render(context);
+        // Values are only known now, and can still smuggle a `javascript:` URL into an attribute.
+        content.html = sanitize_rendered_html(&content.html);
+        if let Some(utm) = &self.utm_parameters {
+            let unsubscribe_url = context.get("unsubscribe_url").map(String::as_str).unwrap_or_default();
+            let skip = |url: &str| !unsubscribe_url.is_empty() && url == unsubscribe_url;
+            content.html = utm.tag_html(&content.html, skip);
+            content.text = utm.tag_text(&content.text, skip);
+        }
+        content
+    }
+}
+
+impl NewsletterIssue {
+    /// Derive the sanitized HTML and text bodies from the authored content.
+    pub fn bodies(&self) -> (String, String) {
+        let (content_html, content_text) = match self.content_format {
+            ContentFormat::Markdown => {
+                let content = markdown_to_content(&self.content_markdown);
+                (content.html, content.text)
+            }
+            ContentFormat::Html if self.content_text.trim().is_empty() => {
+                (self.content_html.clone(), html_to_text(&self.content_html))
+            }
+            ContentFormat::Html => (self.content_html.clone(), self.content_text.clone()),
+        };
+        (sanitize_html(&content_html), content_text)
+    }
+
+    pub fn prepare(&self, custom_fields: &[CustomField]) -> Result<PreparedIssue, TemplateError> {
+        let (content_html, content_text) = self.bodies();
+        let custom_field_names: Vec<&str> = custom_fields.iter().map(|f| f.name.as_str()).collect();
+        let template = NewsletterTemplate::parse(&self.title, &content_html, &content_text, &custom_field_names)?;
+        let warnings = lint_newsletter(&content_html, &content_text);
+        Ok(PreparedIssue {
+            template,
+            warnings,
+            utm_parameters: self.utm_parameters.clone(),
+        })
+    }
+}
+
+struct IssueRow {
+    newsletter_issue_id: Uuid,
+    title: String,
+    content_format: String,
+    content_markdown: String,
+    content_html: String,
+    content_text: String,
+    segment_id: Option<Uuid>,
+    track_opens: bool,
+    utm_source: Option<String>,
+    utm_medium: Option<String>,
+    utm_campaign: Option<String>,
+    status: String,
+    scheduled_for: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
+    published_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<IssueRow> for NewsletterIssue {
+    type Error = anyhow::Error;
+
+    fn try_from(r: IssueRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            newsletter_issue_id: r.newsletter_issue_id,
+            title: r.title,
+            content_format: r.content_format.try_into().map_err(anyhow::Error::msg)?,
+            content_markdown: r.content_markdown,
+            content_html: r.content_html,
+            content_text: r.content_text,
+            segment_id: r.segment_id,
+            track_opens: r.track_opens,
+            utm_parameters: r.utm_source.map(|source| UtmParameters {
+                source,
+                medium: r.utm_medium.unwrap_or_default(),
+                campaign: r.utm_campaign.unwrap_or_default(),
+            }),
+            status: r.status.try_into().map_err(anyhow::Error::msg)?,
+            scheduled_for: r.scheduled_for,
+            updated_at: r.updated_at,
+            published_at: r.published_at,
+        })
+    }
+}
+
+/// Template variables for a sample recipient, used by previews and test emails.
+pub fn sample_context(base_url: &str, email: &str) -> HashMap<String, String> {
+    HashMap::from([
+        ("name".to_string(), "Jane Doe".to_string()),
+        ("email".to_string(), email.to_string()),
+        (
+            "unsubscribe_url".to_string(),
+            format!("{}/subscriptions/unsubscribe?unsubscribe_token=sample", base_url),
+        ),
+    ])
+}
+
+#[tracing::instrument(
+    name="Get newsletter issue.",
+    skip(pool)
+)]
+pub async fn get_issue(newsletter_issue_id: Uuid, pool: &PgPool) -> anyhow::Result<Option<NewsletterIssue>> {
+    let issue = sqlx::query_as!(
+        IssueRow,
+        r#"
+        SELECT newsletter_issue_id, title, content_format, content_markdown, content_html,
+               content_text, segment_id, track_opens, utm_source, utm_medium, utm_campaign,
+               status, scheduled_for, updated_at, published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve newsletter issue.")?;
+    issue.map(NewsletterIssue::try_from).transpose()
+}
+
+#[tracing::instrument(
+    name="Get newsletter issues.",
+    skip(pool)
+)]
+pub async fn get_issues(pool: &PgPool) -> anyhow::Result<Vec<NewsletterIssue>> {
+    let issues = sqlx::query_as!(
+        IssueRow,
+        r#"
+        SELECT newsletter_issue_id, title, content_format, content_markdown, content_html,
+               content_text, segment_id, track_opens, utm_source, utm_medium, utm_campaign,
+               status, scheduled_for, updated_at, published_at
+        FROM newsletter_issues
+        ORDER BY updated_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve newsletter issues.")?
+    .into_iter()
+    .map(NewsletterIssue::try_from)
+    .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(issues)
+}
+
+/// Insert `issue`, or overwrite the stored issue if it is still a draft.
+/// Returns `false` when the stored issue can no longer be edited.
+#[tracing::instrument(
+    name="Save newsletter issue.",
+    skip(issue, executor),
+    fields(newsletter_issue_id=%issue.newsletter_issue_id)
+)]
+pub async fn save_issue(issue: &NewsletterIssue, executor: impl PgExecutor<'_>) -> anyhow::Result<bool> {
+    let saved = sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, content_format, content_markdown, content_html,
+            content_text, segment_id, track_opens, utm_source, utm_medium, utm_campaign,
+            status, scheduled_for, created_at, updated_at, published_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, now(), now(), $14)
+        ON CONFLICT (newsletter_issue_id) DO UPDATE
+        SET title = EXCLUDED.title,
+            content_format = EXCLUDED.content_format,
+            content_markdown = EXCLUDED.content_markdown,
+            content_html = EXCLUDED.content_html,
+            content_text = EXCLUDED.content_text,
+            segment_id = EXCLUDED.segment_id,
+            track_opens = EXCLUDED.track_opens,
+            utm_source = EXCLUDED.utm_source,
+            utm_medium = EXCLUDED.utm_medium,
+            utm_campaign = EXCLUDED.utm_campaign,
+            status = EXCLUDED.status,
+            scheduled_for = EXCLUDED.scheduled_for,
+            updated_at = EXCLUDED.updated_at,
+            published_at = EXCLUDED.published_at
+        WHERE newsletter_issues.status = 'draft'
+        "#,
+        issue.newsletter_issue_id,
+        issue.title,
+        issue.content_format.as_str(),
+        issue.content_markdown,
+        issue.content_html,
+        issue.content_text,
+        issue.segment_id,
+        issue.track_opens,
+        issue.utm_parameters.as_ref().map(|utm| utm.source.as_str()),
+        issue.utm_parameters.as_ref().map(|utm| utm.medium.as_str()),
+        issue.utm_parameters.as_ref().map(|utm| utm.campaign.as_str()),
+        issue.status.as_str(),
+        issue.scheduled_for,
+        issue.published_at,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to save newsletter issue.")?
+    .rows_affected();
+    Ok(saved == 1)
+}
+
+/// Queue a delivery task for every confirmed subscriber in `segment`,
+/// returning the number of recipients. Runs in the transaction that moves the issue
+/// to `sending`, so that an issue is never left sending without its deliveries.
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    segment: Option<&Segment>,
+) -> Result<usize, anyhow::Error> {
+    let mut subscriber_ids = Vec::new();
+    let mut subscriber_emails = Vec::new();
+    let mut tracking_tokens = Vec::new();
+    for subscriber in get_confirmed_subscribers(&mut **transaction, segment).await? {
+        match subscriber {
+            Ok(subscriber) => {
+                subscriber_ids.push(subscriber.subscriber_id);
+                subscriber_emails.push(subscriber.email.to_string());
+                tracking_tokens.push(generate_token());
+            },
+            Err(error) => {
+                tracing::warn!(
+                    error.cause_chain = ?error,
+                    "Skipping a confirmed subscriber. \
+                    Their stored contact is invalid."
+                )
+            }
+        };
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_tasks (
+            newsletter_issue_id, subscriber_id, subscriber_email, tracking_token,
+            status, n_retries, execute_after, updated_at
+        )
+        SELECT $1, subscriber_id, subscriber_email, tracking_token, 'pending', 0, now(), now()
+        FROM UNNEST($2::uuid[], $3::text[], $4::text[])
+            AS recipients(subscriber_id, subscriber_email, tracking_token)
+        ON CONFLICT DO NOTHING
+        "#,
+        newsletter_issue_id,
+        &subscriber_ids,
+        &subscriber_emails,
+        &tracking_tokens,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to enqueue delivery tasks.")?;
+    // An issue without recipients is done straight away.
+    complete_issue_if_done(newsletter_issue_id, &mut **transaction).await?;
+    Ok(subscriber_ids.len())
+}
+
+/// Mark a sending issue as sent once none of its deliveries are pending.
+pub async fn complete_issue_if_done(newsletter_issue_id: Uuid, executor: impl PgExecutor<'_>) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'sent', updated_at = now(), published_at = now()
+        WHERE newsletter_issue_id = $1
+          AND status = 'sending'
+          AND NOT EXISTS (
+              SELECT 1 FROM issue_delivery_tasks
+              WHERE newsletter_issue_id = $1 AND status = 'pending'
+          )
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(executor)
+    .await
+    .context("Failed to complete the newsletter issue.")?;
+    Ok(())
+}
+
+/// How far the delivery of an issue has progressed, by task status.
+#[derive(Debug, Default)]
+pub struct DeliveryProgress {
+    pub total: i64,
+    pub delivered: i64,
+    pub pending: i64,
+    pub failed: i64,
+    pub skipped: i64,
+    pub cancelled: i64,
+    pub retried: i64,
+}
+
+#[tracing::instrument(
+    name="Get delivery progress.",
+    skip(pool)
+)]
+pub async fn get_delivery_progress(newsletter_issue_id: Uuid, pool: &PgPool) -> anyhow::Result<DeliveryProgress> {
+    let progress = sqlx::query_as!(
+        DeliveryProgress,
+        r#"
+        SELECT COUNT(*) AS "total!",
+               COUNT(*) FILTER (WHERE status = 'delivered') AS "delivered!",
+               COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+               COUNT(*) FILTER (WHERE status = 'failed') AS "failed!",
+               COUNT(*) FILTER (WHERE status = 'skipped') AS "skipped!",
+               COUNT(*) FILTER (WHERE status = 'cancelled') AS "cancelled!",
+               COUNT(*) FILTER (WHERE n_retries > 0) AS "retried!"
+        FROM issue_delivery_tasks
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve delivery progress.")?;
+    Ok(progress)
+}
+
+struct IssueProgressRow {
+    newsletter_issue_id: Uuid,
+    total: i64,
+    delivered: i64,
+    pending: i64,
+    failed: i64,
+    skipped: i64,
+    cancelled: i64,
+    retried: i64,
+}
+
+/// The delivery progress of every issue that has tasks, in a single query.
+#[tracing::instrument(
+    name="Get delivery progress of all issues.",
+    skip(pool)
+)]
+pub async fn get_delivery_progress_by_issue(pool: &PgPool) -> anyhow::Result<HashMap<Uuid, DeliveryProgress>> {
+    let rows = sqlx::query_as!(
+        IssueProgressRow,
+        r#"
+        SELECT newsletter_issue_id,
+               COUNT(*) AS "total!",
+               COUNT(*) FILTER (WHERE status = 'delivered') AS "delivered!",
+               COUNT(*) FILTER (WHERE status = 'pending') AS "pending!",
+               COUNT(*) FILTER (WHERE status = 'failed') AS "failed!",
+               COUNT(*) FILTER (WHERE status = 'skipped') AS "skipped!",
+               COUNT(*) FILTER (WHERE status = 'cancelled') AS "cancelled!",
+               COUNT(*) FILTER (WHERE n_retries > 0) AS "retried!"
+        FROM issue_delivery_tasks
+        GROUP BY newsletter_issue_id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve delivery progress.")?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let progress = DeliveryProgress {
+                total: r.total,
+                delivered: r.delivered,
+                pending: r.pending,
+                failed: r.failed,
+                skipped: r.skipped,
+                cancelled: r.cancelled,
+                retried: r.retried,
+            };
+            (r.newsletter_issue_id, progress)
+        })
+        .collect())
+}
+
+pub struct OpenCounts {
+    pub unique: i64,
+    pub total: i64,
+}
+
+#[tracing::instrument(
+    name="Get open counts.",
+    skip(pool)
+)]
+pub async fn get_open_counts(newsletter_issue_id: Uuid, pool: &PgPool) -> anyhow::Result<OpenCounts> {
+    let counts = sqlx::query_as!(
+        OpenCounts,
+        r#"
+        SELECT COUNT(DISTINCT subscriber_id) AS "unique!",
+               COUNT(*) AS "total!"
+        FROM issue_opens
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve open counts.")?;
+    Ok(counts)
+}
+
+/// Clicks on one of the links of an issue.
+pub struct LinkClicks {
+    pub url: String,
+    pub unique: i64,
+    pub total: i64,
+}
+
+#[tracing::instrument(
+    name="Get link click counts.",
+    skip(pool)
+)]
+pub async fn get_link_clicks(newsletter_issue_id: Uuid, pool: &PgPool) -> anyhow::Result<Vec<LinkClicks>> {
+    let clicks = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT url,
+               COUNT(DISTINCT subscriber_id) AS "unique!",
+               COUNT(*) AS "total!"
+        FROM issue_clicks
+        WHERE newsletter_issue_id = $1
+        GROUP BY url
+        ORDER BY COUNT(*) DESC, url
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve link click counts.")?;
+    Ok(clicks)
+}
+
+/// A recipient the delivery worker gave up on, with the error of its last attempt.
+pub struct DeliveryFailure {
+    pub subscriber_email: String,
+    pub n_retries: i32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(
+    name="Get delivery failures.",
+    skip(pool)
+)]
+pub async fn get_delivery_failures(newsletter_issue_id: Uuid, pool: &PgPool) -> anyhow::Result<Vec<DeliveryFailure>> {
+    let failures = sqlx::query_as!(
+        DeliveryFailure,
+        r#"
+        SELECT subscriber_email, n_retries,
+               COALESCE(last_error, '') AS "last_error!",
+               updated_at AS failed_at
+        FROM issue_delivery_tasks
+        WHERE newsletter_issue_id = $1 AND status = 'failed'
+        ORDER BY subscriber_email
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve delivery failures.")?;
+    Ok(failures)
+}
+
+/// Move an issue to `status`. Drafts lose their schedule.
+pub async fn set_issue_status(newsletter_issue_id: Uuid, status: IssueStatus, pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = $2,
+            updated_at = now(),
+            scheduled_for = CASE WHEN $2 = 'draft' THEN NULL ELSE scheduled_for END
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        status.as_str(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the newsletter issue status.")?;
+    Ok(())
+}
@@ -1,5 +1,17 @@
+mod delivery;
+mod drafts;
+mod issue;
 mod post;
 mod get;
+mod preview;
+mod report;
+mod schedule;
 
-pub use post::publish_newsletter;
-pub use get::send_newsletter_form;
\ No newline at end of file
+pub use delivery::{cancel_delivery, pause_delivery, resume_delivery};
+pub use drafts::{delete_draft, save_draft, send_test_newsletter};
+pub use issue::{complete_issue_if_done, enqueue_delivery_tasks, get_delivery_progress, get_issue, get_issues, set_issue_status, ContentFormat, DeliveryProgress, IssueStatus, NewsletterIssue, PreparedIssue};
+pub use post::{count_confirmed_subscribers, get_confirmed_subscriber, get_confirmed_subscribers, publish_newsletter, schedule_newsletter, ConfirmedSubscribers};
+pub use get::{edit_newsletter_form, list_issues, send_newsletter_form};
+pub use preview::preview_newsletter;
+pub use report::{issue_failures_csv, issue_report};
+pub use schedule::{reschedule_issue, unschedule_issue};
@@ -1,94 +1,379 @@
+use std::collections::HashMap;
+
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
-use sqlx::PgPool;
-
+use chrono::Utc;
+use sqlx::{PgExecutor, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
 
 use crate::authentication::UserId;
+use crate::content::UtmParameters;
 use crate::utils::{e400, e500, see_other};
-use crate::email_client::EmailClient;
-use crate::domain::SubscriberEmail;
+use crate::domain::{SendTime, SubscriberEmail};
 use crate::idempotency::{save_response, get_saved_response, IdempotencyKey};
+use crate::routes::admin::custom_fields::{get_custom_fields, push_field_value_condition};
+use crate::routes::admin::segments::{get_segment, Segment};
+use crate::routes::push_engagement_condition;
+use crate::suppression_list::push_not_suppressed_condition;
+
+use super::issue::{enqueue_delivery_tasks, save_issue, ContentFormat, IssueStatus, NewsletterIssue, PreparedIssue};
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
+    #[serde(default)]
+    issue_id: String,
     title: String,
+    #[serde(default)]
+    content_format: ContentFormat,
+    #[serde(default)]
+    content_markdown: String,
+    #[serde(default)]
     content_html: String,
+    #[serde(default)]
     content_text: String,
+    #[serde(default)]
     idempotency_key: String,
+    #[serde(default)]
+    segment_id: String,
+    #[serde(default)]
+    track_opens: bool,
+    #[serde(default)]
+    add_utm_parameters: bool,
+    #[serde(default)]
+    utm_source: String,
+    #[serde(default)]
+    utm_medium: String,
+    #[serde(default)]
+    utm_campaign: String,
+    #[serde(default)]
+    ignore_warnings: bool,
+    #[serde(default)]
+    test_recipients: String,
+    #[serde(default)]
+    scheduled_for: String,
+    #[serde(default)]
+    timezone: String,
+}
+
+impl FormData {
+    /// The id of the draft being edited, if any.
+    pub fn issue_id(&self) -> Result<Option<Uuid>, uuid::Error> {
+        parse_optional_id(&self.issue_id)
+    }
+
+    /// The page the form was submitted from.
+    pub fn form_url(&self) -> Result<String, uuid::Error> {
+        match self.issue_id()? {
+            Some(issue_id) => Ok(format!("/admin/newsletters/issues/{}/edit", issue_id)),
+            None => Ok("/admin/newsletters".to_string()),
+        }
+    }
+
+    /// The issue described by the form. A new id is assigned unless an existing draft is being edited.
+    pub fn issue(&self, status: IssueStatus) -> Result<NewsletterIssue, uuid::Error> {
+        Ok(NewsletterIssue {
+            newsletter_issue_id: self.issue_id()?.unwrap_or_else(Uuid::new_v4),
+            title: self.title.clone(),
+            content_format: self.content_format,
+            content_markdown: self.content_markdown.clone(),
+            content_html: self.content_html.clone(),
+            content_text: self.content_text.clone(),
+            segment_id: parse_optional_id(&self.segment_id)?,
+            track_opens: self.track_opens,
+            utm_parameters: self.add_utm_parameters.then(|| UtmParameters {
+                source: self.utm_source.trim().to_string(),
+                medium: self.utm_medium.trim().to_string(),
+                campaign: self.utm_campaign.trim().to_string(),
+            }),
+            status,
+            scheduled_for: None,
+            updated_at: Utc::now(),
+            published_at: None,
+        })
+    }
+
+    /// The comma or whitespace separated addresses a test email should go to.
+    pub fn test_recipients(&self) -> Result<Vec<SubscriberEmail>, String> {
+        self.test_recipients
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| SubscriberEmail::parse(s.to_owned()))
+            .collect()
+    }
+}
+
+fn parse_optional_id(s: &str) -> Result<Option<Uuid>, uuid::Error> {
+    match s.trim() {
+        "" => Ok(None),
+        s => Uuid::parse_str(s).map(Some),
+    }
 }
 
 #[tracing::instrument(
     name="Publish a newsletter to confirmed subscribers.",
-    skip(form, pool, email_client, user_id)
+    skip(form, pool, user_id)
     fields(user_id=%&*user_id)
 )]
 pub async fn publish_newsletter(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
     user_id: web::ReqData<UserId>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
-    let FormData {title, content_html, content_text, idempotency_key} = form.0;
-    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let form = form.into_inner();
+    let idempotency_key: IdempotencyKey = form.idempotency_key.clone().try_into().map_err(e400)?;
     if let Some(saved_response) = get_saved_response(*user_id, &idempotency_key, &pool).await.map_err(e500)? {
-        FlashMessage::info(format!("Your newsletter '{}' has been published.", title)).send();
-        dbg!("went through flow?");
+        FlashMessage::info(format!("Your newsletter '{}' has been published.", form.title)).send();
         return Ok(saved_response)
     }
-    let subscribers = get_confirmed_subscribers(&pool).await.map_err(e500)?;
-    for subscriber in subscribers {
-        match subscriber {
-            Ok(subscriber) => {
-                email_client.send_email(
-                        &subscriber.email,
-                        &title,
-                        &content_html, 
-                        &content_text)
-                        .await
-                        .with_context(|| {format!("Failed to send newsletter to {}", &subscriber.email)})
-                        .map_err(e500)?;
-            },
-            Err(error) => {
-                tracing::warn!(
-                    error.cause_chain = ?error,
-                    "Skipping a confirmed subscriber. \
-                    Their stored contact is invalid."
-                )
-            }    
-        };
-    }
-    FlashMessage::info(format!("Your newsletter '{}' has been published.", title)).send();
+    let issue = form.issue(IssueStatus::Sending).map_err(e400)?;
+    let segment = match check_issue(&issue, &form, &pool).await? {
+        Ok((_, segment)) => segment,
+        Err(response) => return Ok(response),
+    };
+    // The issue only leaves drafts together with its delivery tasks.
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")
+        .map_err(e500)?;
+    if !save_issue(&issue, &mut *transaction).await.map_err(e500)? {
+        FlashMessage::error("This newsletter issue has already been published.").send();
+        return Ok(see_other("/admin/newsletters/issues"));
+    }
+    enqueue_delivery_tasks(&mut transaction, issue.newsletter_issue_id, segment.as_ref())
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the published newsletter issue.")
+        .map_err(e500)?;
+    FlashMessage::info(format!("Your newsletter '{}' has been published.", issue.title)).send();
+    FlashMessage::info(format!(
+        "Follow its delivery on the <a href=\"/admin/newsletters/issues/{}\">delivery report</a>.",
+        issue.newsletter_issue_id
+    )).send();
     let response = see_other("/admin/newsletters");
     let response = save_response(*user_id, &idempotency_key, &pool, response).await.map_err(e500)?;
     Ok(response)
 }
 
+#[tracing::instrument(
+    name="Schedule a newsletter.",
+    skip(form, pool, user_id)
+    fields(user_id=%&*user_id)
+)]
+pub async fn schedule_newsletter(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let send_time = match SendTime::parse(&form.scheduled_for, &form.timezone, Utc::now()) {
+        Ok(send_time) => send_time,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&form.form_url().map_err(e400)?));
+        }
+    };
+    let mut issue = form.issue(IssueStatus::Scheduled).map_err(e400)?;
+    issue.scheduled_for = Some(*send_time.as_ref());
+    if let Err(response) = check_issue(&issue, &form, &pool).await? {
+        return Ok(response);
+    }
+    if !save_issue(&issue, pool.get_ref()).await.map_err(e500)? {
+        FlashMessage::error("Only drafts can be scheduled.").send();
+        return Ok(see_other("/admin/newsletters/issues"));
+    }
+    FlashMessage::info(format!(
+        "Your newsletter '{}' is scheduled for {}.",
+        issue.title,
+        send_time.as_ref().format("%Y-%m-%d %H:%M UTC")
+    )).send();
+    Ok(see_other("/admin/newsletters/issues"))
+}
 
-struct ConfirmedSubscribers {
-    email: SubscriberEmail,
+/// Check that `issue` is ready to go out, or return the redirect explaining why it is not.
+async fn check_issue(
+    issue: &NewsletterIssue,
+    form: &FormData,
+    pool: &PgPool,
+) -> Result<Result<(PreparedIssue, Option<Segment>), HttpResponse>, actix_web::Error> {
+    let form_url = form.form_url().map_err(e400)?;
+    let custom_fields = get_custom_fields(pool).await.map_err(e500)?;
+    let prepared = match issue.prepare(&custom_fields) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            FlashMessage::error(format!("The newsletter template is invalid: {}", e)).send();
+            return Ok(Err(see_other(&form_url)));
+        }
+    };
+    if !prepared.warnings.is_empty() && !form.ignore_warnings {
+        for warning in &prepared.warnings {
+            FlashMessage::warning(warning.clone()).send();
+        }
+        FlashMessage::warning("Fix the issues above or tick 'Send despite warnings' to publish anyway.").send();
+        return Ok(Err(see_other(&form_url)));
+    }
+    let segment = match issue.segment_id {
+        None => None,
+        Some(segment_id) => {
+            let segment = get_segment(segment_id, pool)
+                .await
+                .map_err(e500)?
+                .ok_or_else(|| e400("There is no segment with the provided id."))?;
+            Some(segment)
+        }
+    };
+    Ok(Ok((prepared, segment)))
 }
 
 
+pub struct ConfirmedSubscribers {
+    pub subscriber_id: Uuid,
+    pub email: SubscriberEmail,
+    pub name: String,
+    pub unsubscribe_token: String,
+    pub custom_fields: HashMap<String, String>,
+}
+
+impl ConfirmedSubscribers {
+    /// Variables available to newsletter templates for this subscriber.
+    pub fn template_context(&self, base_url: &str) -> HashMap<String, String> {
+        let mut context = self.custom_fields.clone();
+        context.insert("name".into(), self.name.clone());
+        context.insert("email".into(), self.email.to_string());
+        let unsubscribe_url = format!(
+            "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+            base_url, self.unsubscribe_token
+        );
+        context.insert("unsubscribe_url".into(), unsubscribe_url);
+        context
+    }
+}
+
+
+/// Confirmed subscribers matching `segment`, or all of them when no segment is given.
 #[tracing::instrument(
     name="Retrieve confirmed subscribers.",
+    skip(executor)
+)]
+pub async fn get_confirmed_subscribers(executor: impl PgExecutor<'_>, segment: Option<&Segment>) -> Result<Vec<Result<ConfirmedSubscribers, anyhow::Error>>, anyhow::Error> {
+    fetch_confirmed_subscribers(executor, segment, None).await
+}
+
+/// The subscriber with `subscriber_id`, provided they are still confirmed.
+#[tracing::instrument(
+    name="Retrieve a confirmed subscriber.",
+    skip(pool)
 )]
-async fn get_confirmed_subscribers(pool: &PgPool) -> Result<Vec<Result<ConfirmedSubscribers, anyhow::Error>>, anyhow::Error> {
+pub async fn get_confirmed_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<Option<Result<ConfirmedSubscribers, anyhow::Error>>, anyhow::Error> {
+    Ok(fetch_confirmed_subscribers(pool, None, Some(subscriber_id)).await?.pop())
+}
 
-    let confirmed_subscribers = sqlx::query!(
+/// Restrict a query over `subscriptions s` to the confirmed subscribers matching `segment`,
+/// or to all of them when no segment is given. Shared by the count and the fan-out so that
+/// the preview always agrees with who receives the issue.
+fn push_recipient_conditions<'a>(query: &mut QueryBuilder<'a, Postgres>, segment: Option<&'a Segment>) {
+    query.push(" WHERE s.status = 'confirmed'");
+    push_not_suppressed_condition(query);
+    let Some(segment) = segment else {
+        return;
+    };
+    if let Some(tag) = segment.tag.as_deref() {
+        query
+            .push(" AND EXISTS (SELECT 1 FROM subscriber_tags t WHERE t.subscriber_id = s.id AND t.tag = ")
+            .push_bind(tag)
+            .push(")");
+    }
+    if let Some(subscribed_from) = segment.subscribed_from {
+        query.push(" AND s.subscribed_at::date >= ").push_bind(subscribed_from);
+    }
+    if let Some(subscribed_until) = segment.subscribed_until {
+        query.push(" AND s.subscribed_at::date <= ").push_bind(subscribed_until);
+    }
+    if let Some(field_name) = segment.field_name.as_deref() {
+        push_field_value_condition(query, field_name, segment.field_value.as_deref());
+    }
+    if let Some(list_name) = segment.list_name.as_deref() {
+        query
+            .push(" AND EXISTS (SELECT 1 FROM subscriber_lists l WHERE l.subscriber_id = s.id AND l.list_name = ")
+            .push_bind(list_name)
+            .push(")");
+    }
+    if let Some(days) = segment.engaged_within_days {
+        push_engagement_condition(query, days);
+    }
+}
+
+/// How many confirmed subscribers match `segment`, without loading them.
+#[tracing::instrument(
+    name="Count confirmed subscribers.",
+    skip(pool)
+)]
+pub async fn count_confirmed_subscribers(pool: &PgPool, segment: Option<&Segment>) -> Result<i64, anyhow::Error> {
+    let mut query = QueryBuilder::new("SELECT COUNT(*) FROM subscriptions s");
+    push_recipient_conditions(&mut query, segment);
+    let count = query
+        .build_query_scalar::<i64>()
+        .fetch_one(pool)
+        .await
+        .context("Failed to count confirmed subscribers.")?;
+    Ok(count)
+}
+
+#[derive(sqlx::FromRow)]
+struct ConfirmedSubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    unsubscribe_token: String,
+    field_names: Vec<String>,
+    field_values: Vec<String>,
+}
+
+async fn fetch_confirmed_subscribers(
+    executor: impl PgExecutor<'_>,
+    segment: Option<&Segment>,
+    subscriber_id: Option<Uuid>,
+) -> Result<Vec<Result<ConfirmedSubscribers, anyhow::Error>>, anyhow::Error> {
+    let mut query = QueryBuilder::new(
         r#"
-        SELECT email 
-        FROM subscriptions 
-        WHERE status = 'confirmed'"#)
-    .fetch_all(pool)
-    .await?
-    .into_iter()
+        SELECT s.id, s.email, s.name, s.unsubscribe_token,
+               ARRAY(
+                   SELECT f.name FROM subscriber_custom_fields v
+                   JOIN custom_fields f ON f.field_id = v.field_id
+                   WHERE v.subscriber_id = s.id
+                   ORDER BY f.name
+               ) AS field_names,
+               ARRAY(
+                   SELECT v.value FROM subscriber_custom_fields v
+                   JOIN custom_fields f ON f.field_id = v.field_id
+                   WHERE v.subscriber_id = s.id
+                   ORDER BY f.name
+               ) AS field_values
+        FROM subscriptions s"#,
+    );
+    push_recipient_conditions(&mut query, segment);
+    if let Some(subscriber_id) = subscriber_id {
+        query.push(" AND s.id = ").push_bind(subscriber_id);
+    }
+    let confirmed_subscribers = query
+        .build_query_as::<ConfirmedSubscriberRow>()
+        .fetch_all(executor)
+        .await?
+        .into_iter()
         .map(|r| match SubscriberEmail::parse(r.email) {
-            Ok(email) => Ok(ConfirmedSubscribers {email}),
+            Ok(email) => Ok(ConfirmedSubscribers {
+                subscriber_id: r.id,
+                email,
+                name: r.name,
+                unsubscribe_token: r.unsubscribe_token,
+                custom_fields: r.field_names.into_iter().zip(r.field_values).collect(),
+            }),
             Err(error) => Err(anyhow::anyhow!(error)),
         })
         .collect();
     Ok(confirmed_subscribers)
-
 }
\ No newline at end of file
@@ -0,0 +1,104 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::routes::admin::custom_fields::get_custom_fields;
+use crate::routes::admin::segments::get_segment;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e404, e500};
+
+use super::get_confirmed_subscribers;
+use super::issue::{get_issue, sample_context, IssueStatus};
+
+/// Render an issue the way its first recipient will see it, or for a sample
+/// recipient when the issue has none yet.
+#[tracing::instrument(
+    name="Preview a newsletter issue.",
+    skip(pool, base_url, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn preview_newsletter(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = get_issue(issue_id.into_inner(), &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("There is no newsletter issue with the provided id."))?;
+    let custom_fields = get_custom_fields(&pool).await.map_err(e500)?;
+    let mut msg_html = String::new();
+    let prepared = match issue.prepare(&custom_fields) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            writeln!(msg_html, "<p><i>The newsletter template is invalid: {}</i></p>", e).unwrap();
+            return Ok(preview_page(&issue.newsletter_issue_id, issue.status, &msg_html, "", "", ""));
+        }
+    };
+    for warning in &prepared.warnings {
+        writeln!(msg_html, "<p><i>{}</i></p>", warning).unwrap();
+    }
+    let segment = match issue.segment_id {
+        Some(segment_id) => {
+            let segment = get_segment(segment_id, &pool).await.map_err(e500)?;
+            if segment.is_none() {
+                writeln!(msg_html, "<p><i>The selected segment no longer exists.</i></p>").unwrap();
+            }
+            segment
+        }
+        None => None,
+    };
+    let recipient = get_confirmed_subscribers(pool.get_ref(), segment.as_ref())
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .find_map(Result::ok);
+    let (recipient_html, context) = match recipient {
+        Some(subscriber) => (
+            format!("<p>Previewing as {}.</p>", encode_minimal(subscriber.email.as_ref())),
+            subscriber.template_context(&base_url.0),
+        ),
+        None => (
+            "<p>There are no recipients yet, previewing with sample values.</p>".to_string(),
+            sample_context(&base_url.0, "jane.doe@example.com"),
+        ),
+    };
+    msg_html.push_str(&recipient_html);
+    let content = prepared.render(&context);
+    Ok(preview_page(
+        &issue.newsletter_issue_id,
+        issue.status,
+        &msg_html,
+        &content.title,
+        &content.html,
+        &content.text,
+    ))
+}
+
+fn preview_page(
+    issue_id: &Uuid,
+    status: IssueStatus,
+    msg_html: &str,
+    title: &str,
+    content_html: &str,
+    content_text: &str,
+) -> HttpResponse {
+    let edit_link = match status {
+        IssueStatus::Draft => format!("<p><a href=\"/admin/newsletters/issues/{}/edit\">Edit</a></p>", issue_id),
+        _ => String::new(),
+    };
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("preview.html"),
+            msg_html = msg_html,
+            title = encode_minimal(title),
+            content_html = encode_minimal(content_html),
+            content_text = encode_minimal(content_text),
+            edit_link = edit_link,
+        ))
+}
@@ -0,0 +1,169 @@
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use htmlescape::encode_minimal;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::utils::{e404, e500};
+
+use super::issue::{get_delivery_failures, get_delivery_progress, get_issue, get_link_clicks, get_open_counts, IssueStatus, NewsletterIssue};
+
+/// How often the report reloads itself while the issue is being sent.
+const REFRESH_SECONDS: u32 = 5;
+
+/// Delivery counts and failures of a published issue.
+#[tracing::instrument(
+    name="Show a newsletter delivery report.",
+    skip(pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn issue_report(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = published_issue(issue_id.into_inner(), &pool).await?;
+    let progress = get_delivery_progress(issue.newsletter_issue_id, &pool).await.map_err(e500)?;
+    let failures = get_delivery_failures(issue.newsletter_issue_id, &pool).await.map_err(e500)?;
+    let failures_html = if failures.is_empty() {
+        "<p>No delivery has failed.</p>".to_string()
+    } else {
+        let mut rows_html = String::new();
+        for failure in &failures {
+            writeln!(
+                rows_html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                encode_minimal(&failure.subscriber_email),
+                failure.n_retries,
+                encode_minimal(&failure.last_error),
+                failure.failed_at.format("%Y-%m-%d %H:%M"),
+            ).unwrap();
+        }
+        format!(
+            "<table><tr><th>Recipient</th><th>Retries</th><th>Reason</th><th>Failed at</th></tr>{}</table>",
+            rows_html
+        )
+    };
+    let opens_html = if issue.track_opens {
+        let opens = get_open_counts(issue.newsletter_issue_id, &pool).await.map_err(e500)?;
+        format!(
+            "<table><tr><th>Unique opens</th><td>{}</td></tr><tr><th>Total opens</th><td>{}</td></tr></table>",
+            opens.unique, opens.total
+        )
+    } else {
+        "<p>Opens are not tracked for this issue.</p>".to_string()
+    };
+    let clicks = get_link_clicks(issue.newsletter_issue_id, &pool).await.map_err(e500)?;
+    let clicks_html = if clicks.is_empty() {
+        "<p>No link has been clicked.</p>".to_string()
+    } else {
+        let mut rows_html = String::new();
+        for link in &clicks {
+            writeln!(
+                rows_html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                encode_minimal(&link.url),
+                link.unique,
+                link.total,
+            ).unwrap();
+        }
+        format!(
+            "<table><tr><th>Link</th><th>Unique clicks</th><th>Total clicks</th></tr>{}</table>",
+            rows_html
+        )
+    };
+    let refresh = match issue.status {
+        IssueStatus::Sending => format!("<meta http-equiv=\"refresh\" content=\"{}\">", REFRESH_SECONDS),
+        _ => String::new(),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("report.html"),
+            refresh = refresh,
+            title = encode_minimal(&issue.title),
+            status = issue.status.as_str(),
+            total = progress.total,
+            delivered = progress.delivered,
+            pending = progress.pending,
+            failed = progress.failed,
+            retried = progress.retried,
+            skipped = progress.skipped,
+            cancelled = progress.cancelled,
+            opens_html = opens_html,
+            clicks_html = clicks_html,
+            failures_html = failures_html,
+            issue_id = issue.newsletter_issue_id,
+        )))
+}
+
+#[tracing::instrument(
+    name="Download newsletter delivery failures.",
+    skip(pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn issue_failures_csv(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = published_issue(issue_id.into_inner(), &pool).await?;
+    let failures = get_delivery_failures(issue.newsletter_issue_id, &pool).await.map_err(e500)?;
+    let mut csv = String::from("email,retries,reason,failed_at\r\n");
+    for failure in &failures {
+        write!(
+            csv,
+            "{},{},{},{}\r\n",
+            csv_field(&failure.subscriber_email),
+            failure.n_retries,
+            csv_field(&failure.last_error),
+            failure.failed_at.to_rfc3339(),
+        ).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "delivery-failures-{}.csv",
+                issue.newsletter_issue_id
+            ))],
+        })
+        .body(csv))
+}
+
+/// Only issues that have been handed over for delivery have a report.
+async fn published_issue(issue_id: Uuid, pool: &PgPool) -> Result<NewsletterIssue, actix_web::Error> {
+    match get_issue(issue_id, pool).await.map_err(e500)? {
+        Some(issue) if !matches!(issue.status, IssueStatus::Draft | IssueStatus::Scheduled) => Ok(issue),
+        _ => Err(e404("There is no published newsletter issue with the provided id.")),
+    }
+}
+
+/// Quote a value when it contains a separator, a quote or a line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::csv_field;
+
+    #[test]
+    fn plain_values_are_not_quoted() {
+        assert_eq!(csv_field("ursula@mail.com"), "ursula@mail.com");
+    }
+
+    #[test]
+    fn values_with_separators_or_quotes_are_quoted() {
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+}
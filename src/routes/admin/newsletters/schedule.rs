@@ -0,0 +1,99 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::domain::SendTime;
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct RescheduleFormData {
+    issue_id: String,
+    scheduled_for: String,
+    #[serde(default)]
+    timezone: String,
+}
+
+#[tracing::instrument(
+    name="Reschedule a newsletter issue.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn reschedule_issue(
+    form: web::Form<RescheduleFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = Uuid::parse_str(&form.issue_id).map_err(e400)?;
+    let send_time = match SendTime::parse(&form.scheduled_for, &form.timezone, Utc::now()) {
+        Ok(send_time) => send_time,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/newsletters/issues"));
+        }
+    };
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET scheduled_for = $2, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        issue_id,
+        send_time.as_ref(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to reschedule newsletter issue.")
+    .map_err(e500)?
+    .rows_affected();
+    if updated == 0 {
+        FlashMessage::error("Only scheduled issues can be rescheduled.").send();
+    } else {
+        FlashMessage::info(format!(
+            "The newsletter is now scheduled for {}.",
+            send_time.as_ref().format("%Y-%m-%d %H:%M UTC")
+        )).send();
+    }
+    Ok(see_other("/admin/newsletters/issues"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnscheduleFormData {
+    issue_id: String,
+}
+
+/// Cancel a schedule before it fires, moving the issue back to drafts.
+#[tracing::instrument(
+    name="Unschedule a newsletter issue.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn unschedule_issue(
+    form: web::Form<UnscheduleFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = Uuid::parse_str(&form.issue_id).map_err(e400)?;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'draft', scheduled_for = NULL, updated_at = now()
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        issue_id,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to unschedule newsletter issue.")
+    .map_err(e500)?
+    .rows_affected();
+    if updated == 0 {
+        FlashMessage::error("Only scheduled issues can be cancelled.").send();
+    } else {
+        FlashMessage::info("The schedule has been cancelled. The issue is back in drafts.").send();
+    }
+    Ok(see_other("/admin/newsletters/issues"))
+}
@@ -2,9 +2,9 @@ use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
-use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{authentication::{validate_credentials, AuthError, Credentials, UserId}, routes::dashboard::get_username, utils::see_other};
+use crate::{authentication::{revoke_all_sessions, validate_credentials, validate_new_password, AuthError, Credentials, DummyPasswordHash, UserId}, routes::dashboard::get_username, session_state::TypedSession, utils::see_other};
+use crate::configuration::PasswordHashingSettings;
 use crate::utils::e500;
 
 
@@ -17,11 +17,14 @@ pub struct FormData {
 
 #[tracing::instrument(
     name="Change user password",
-    skip(form, pool),
+    skip(form, pool, hashing, dummy_hash, session),
 )]
 pub async fn change_password(
     form: web::Form<FormData>, 
     pool: web::Data<PgPool>,
+    hashing: web::Data<PasswordHashingSettings>,
+    dummy_hash: web::Data<DummyPasswordHash>,
+    session: TypedSession,
     user_id: web::ReqData<UserId>
 ) -> Result<HttpResponse, actix_web::Error> {
     if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
@@ -31,27 +34,28 @@ pub async fn change_password(
     let user_id = user_id.into_inner();
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
     let credentials = Credentials {
-        username,
+        username: username.clone(),
         password: form.0.current_password,
     };
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &hashing, &dummy_hash, &pool).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
                 Ok(see_other("/admin/password"))
             },
-            AuthError::UnexpectedError(e) => Err(e500(e).into())
+            AuthError::UnexpectedError(e) => Err(e500(e))
         }
     };
 
-    let password_length = form.0.new_password.expose_secret().graphemes(true).count();
-    
-    if (password_length < 12) || (password_length > 129) {
-        FlashMessage::error("The new password should be between 12 and 129 characters.").send();
+    if let Err(e) = validate_new_password(&form.0.new_password, &username) {
+        FlashMessage::error(e).send();
         return Ok(see_other("/admin/password"))
     }
     
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool).await.map_err(e500)?;
+    crate::authentication::change_password(*user_id, form.0.new_password, &hashing, &pool).await.map_err(e500)?;
+    // Whoever else knew the old password is signed out.
+    let session_id = session.get_session_id().map_err(e500)?;
+    revoke_all_sessions(*user_id, session_id, &pool).await.map_err(e500)?;
     FlashMessage::error("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
 }
@@ -0,0 +1,51 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{CsrfToken, UserId};
+use crate::routes::admin::newsletters::count_confirmed_subscribers;
+use crate::utils::e500;
+
+use super::get_segments;
+
+pub async fn list_segments(
+    _user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for segment in get_segments(&pool).await.map_err(e500)? {
+        let recipients = count_confirmed_subscribers(&pool, Some(&segment)).await.map_err(e500)?;
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+            <td><form action=\"/admin/segments/delete\" method=\"post\">{}\
+            <input hidden type=\"text\" name=\"segment_id\" value=\"{}\">\
+            <button type=\"submit\">Delete</button></form></td></tr>",
+            htmlescape::encode_minimal(&segment.name),
+            htmlescape::encode_minimal(segment.tag.as_deref().unwrap_or("")),
+            segment.subscribed_from.map(|d| d.to_string()).unwrap_or_default(),
+            segment.subscribed_until.map(|d| d.to_string()).unwrap_or_default(),
+            match (&segment.field_name, &segment.field_value) {
+                (Some(name), Some(value)) => htmlescape::encode_minimal(&format!("{} = {}", name, value)),
+                _ => String::new(),
+            },
+            htmlescape::encode_minimal(segment.list_name.as_deref().unwrap_or("")),
+            segment.engaged_within_days.map(|d| format!("last {} days", d)).unwrap_or_default(),
+            recipients,
+            csrf_field,
+            segment.segment_id,
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(include_str!("segments.html"), msg_html = msg_html, rows_html = rows_html, csrf_field = csrf_field)))
+}
@@ -0,0 +1,7 @@
+mod get;
+mod post;
+mod segment;
+
+pub use get::list_segments;
+pub use post::{create_segment, delete_segment};
+pub use segment::{get_segment, get_segments, Segment};
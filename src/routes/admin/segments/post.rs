@@ -0,0 +1,175 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::domain::{SubscriberList, SubscriberTag};
+use crate::routes::admin::custom_fields::get_custom_fields;
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    #[serde(default)]
+    tag: String,
+    #[serde(default)]
+    subscribed_from: String,
+    #[serde(default)]
+    subscribed_until: String,
+    #[serde(default)]
+    field_name: String,
+    #[serde(default)]
+    field_value: String,
+    #[serde(default)]
+    list_name: String,
+    #[serde(default)]
+    engaged_within_days: String,
+}
+
+#[tracing::instrument(
+    name="Create a segment.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn create_segment(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        name,
+        tag,
+        subscribed_from,
+        subscribed_until,
+        field_name,
+        field_value,
+        list_name,
+        engaged_within_days,
+    } = form.0;
+    let name = name.trim().to_owned();
+    if name.is_empty() {
+        FlashMessage::error("The segment name cannot be empty.").send();
+        return Ok(see_other("/admin/segments"));
+    }
+    let tag = match non_empty(tag).map(SubscriberTag::parse).transpose() {
+        Ok(tag) => tag,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+    let list_name = match non_empty(list_name).map(SubscriberList::parse).transpose() {
+        Ok(list_name) => list_name,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+    let engaged_within_days = match non_empty(engaged_within_days).map(|d| d.parse::<i32>()).transpose() {
+        Ok(days) if days.is_none_or(|d| d > 0) => days,
+        _ => {
+            FlashMessage::error("The engagement period must be a positive number of days.").send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+    let (subscribed_from, subscribed_until) = match (parse_date(subscribed_from), parse_date(subscribed_until)) {
+        (Ok(from), Ok(until)) => (from, until),
+        _ => {
+            FlashMessage::error("Dates must be in the YYYY-MM-DD format.").send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+    let (field_name, field_value) = match non_empty(field_name) {
+        None => (None, None),
+        Some(field_name) => {
+            let custom_fields = get_custom_fields(&pool).await.map_err(e500)?;
+            let value = match custom_fields.iter().find(|f| f.name == field_name) {
+                Some(field) => field.parse_value(&field_value),
+                None => Err(format!("There is no custom field named '{}'.", field_name)),
+            };
+            match value {
+                Ok(value) => (Some(field_name), Some(value)),
+                Err(e) => {
+                    FlashMessage::error(e).send();
+                    return Ok(see_other("/admin/segments"));
+                }
+            }
+        }
+    };
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO segments (
+            segment_id, name, tag, subscribed_from, subscribed_until, field_name, field_value,
+            list_name, engaged_within_days, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        name,
+        tag.as_ref().map(|t| t.as_ref()),
+        subscribed_from,
+        subscribed_until,
+        field_name,
+        field_value,
+        list_name.as_ref().map(|l| l.as_ref()),
+        engaged_within_days,
+        Utc::now(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to insert segment.")
+    .map_err(e500)?
+    .rows_affected();
+
+    if inserted == 0 {
+        FlashMessage::error(format!("A segment named '{}' already exists.", name)).send();
+    } else {
+        FlashMessage::info(format!("The segment '{}' has been saved.", name)).send();
+    }
+    Ok(see_other("/admin/segments"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteFormData {
+    segment_id: String,
+}
+
+#[tracing::instrument(
+    name="Delete a segment.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn delete_segment(
+    form: web::Form<DeleteFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let segment_id = Uuid::parse_str(&form.segment_id).map_err(e400)?;
+    sqlx::query!(r#"DELETE FROM segments WHERE segment_id = $1"#, segment_id)
+        .execute(pool.get_ref())
+        .await
+        .context("Failed to delete segment.")
+        .map_err(e500)?;
+    FlashMessage::info("The segment has been deleted.").send();
+    Ok(see_other("/admin/segments"))
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.trim();
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_owned())
+    }
+}
+
+fn parse_date(s: String) -> Result<Option<NaiveDate>, chrono::ParseError> {
+    non_empty(s)
+        .map(|s| NaiveDate::parse_from_str(&s, "%Y-%m-%d"))
+        .transpose()
+}
@@ -0,0 +1,59 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct Segment {
+    pub segment_id: Uuid,
+    pub name: String,
+    pub tag: Option<String>,
+    pub subscribed_from: Option<NaiveDate>,
+    pub subscribed_until: Option<NaiveDate>,
+    pub field_name: Option<String>,
+    pub field_value: Option<String>,
+    pub list_name: Option<String>,
+    /// Only subscribers who opened or clicked an issue in the last this many days.
+    pub engaged_within_days: Option<i32>,
+}
+
+#[tracing::instrument(
+    name="Get segment.",
+    skip(pool)
+)]
+pub async fn get_segment(segment_id: Uuid, pool: &PgPool) -> anyhow::Result<Option<Segment>> {
+    let segment = sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT segment_id, name, tag, subscribed_from, subscribed_until, field_name, field_value,
+               list_name, engaged_within_days
+        FROM segments
+        WHERE segment_id = $1
+        "#,
+        segment_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve segment.")?;
+    Ok(segment)
+}
+
+#[tracing::instrument(
+    name="Get segments.",
+    skip(pool)
+)]
+pub async fn get_segments(pool: &PgPool) -> anyhow::Result<Vec<Segment>> {
+    let segments = sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT segment_id, name, tag, subscribed_from, subscribed_until, field_name, field_value,
+               list_name, engaged_within_days
+        FROM segments
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve segments.")?;
+    Ok(segments)
+}
@@ -0,0 +1,82 @@
+use std::fmt::Write;
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+
+use crate::authentication::{get_active_sessions, CsrfToken, UserId};
+use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+
+use super::session_owner;
+
+#[derive(serde::Deserialize)]
+pub struct QueryParams {
+    user_id: Option<String>,
+}
+
+#[tracing::instrument(
+    name="List the sessions of a user",
+    skip(query, user_id, csrf_token, session, pool, flash_messages),
+    fields(user_id=%&*user_id)
+)]
+pub async fn list_sessions(
+    query: web::Query<QueryParams>,
+    user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let target = session_owner(query.user_id.as_deref(), &user_id)?;
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for user_session in get_active_sessions(target, &pool).await.map_err(e500)? {
+        let action_html = if Some(user_session.session_id) == current_session_id {
+            "This session".to_string()
+        } else {
+            format!(
+                "<form action=\"/admin/sessions/revoke\" method=\"post\">{}\
+                <input hidden type=\"text\" name=\"user_id\" value=\"{}\">\
+                <input hidden type=\"text\" name=\"session_id\" value=\"{}\">\
+                <button type=\"submit\">Revoke</button></form>",
+                csrf_field, target, user_session.session_id
+            )
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(user_session.user_agent.as_deref().unwrap_or("Unknown device")),
+            htmlescape::encode_minimal(user_session.ip.as_deref().unwrap_or("")),
+            user_session.created_at.format("%Y-%m-%d %H:%M UTC"),
+            user_session.last_seen_at.format("%Y-%m-%d %H:%M UTC"),
+            action_html,
+        ).unwrap();
+    }
+    let (revoke_all_label, back_link) = if target == *user_id {
+        ("Sign out all other sessions", "/admin/dashboard")
+    } else {
+        ("Sign out everywhere", "/admin/users")
+    };
+    let username = get_username(target, &pool).await.map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("sessions.html"),
+            msg_html = msg_html,
+            username = htmlescape::encode_minimal(&username),
+            rows_html = rows_html,
+            user_id = target,
+            revoke_all_label = revoke_all_label,
+            back_link = back_link,
+            csrf_field = csrf_field,
+        )))
+}
@@ -0,0 +1,31 @@
+mod get;
+mod post;
+
+use uuid::Uuid;
+
+use crate::authentication::{Role, UserId};
+use crate::utils::{e400, e403};
+
+pub use get::list_sessions;
+pub use post::{revoke_all_user_sessions, revoke_user_session};
+
+/// The user whose sessions are managed: the logged-in user unless another one is requested,
+/// which only owners may do.
+fn session_owner(requested: Option<&str>, user_id: &UserId) -> Result<Uuid, actix_web::Error> {
+    let Some(requested) = requested else {
+        return Ok(**user_id);
+    };
+    let target = Uuid::parse_str(requested).map_err(e400)?;
+    if target != **user_id && user_id.role() != Role::Owner {
+        return Err(e403("Only owners can manage the sessions of other users."));
+    }
+    Ok(target)
+}
+
+fn sessions_url(target: Uuid, user_id: &UserId) -> String {
+    if target == **user_id {
+        "/admin/sessions".into()
+    } else {
+        format!("/admin/sessions?user_id={}", target)
+    }
+}
@@ -0,0 +1,64 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{revoke_all_sessions, revoke_session, UserId};
+use crate::session_state::TypedSession;
+use crate::utils::{e400, e500, see_other};
+
+use super::{session_owner, sessions_url};
+
+#[derive(serde::Deserialize)]
+pub struct RevokeFormData {
+    user_id: Option<String>,
+    session_id: String,
+}
+
+#[tracing::instrument(
+    name="Revoke a session.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn revoke_user_session(
+    form: web::Form<RevokeFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = session_owner(form.user_id.as_deref(), &user_id)?;
+    let session_id = Uuid::parse_str(&form.session_id).map_err(e400)?;
+    if revoke_session(target, session_id, &pool).await.map_err(e500)? {
+        FlashMessage::info("The session has been revoked.").send();
+    } else {
+        FlashMessage::error("There is no active session with the provided id.").send();
+    }
+    Ok(see_other(&sessions_url(target, &user_id)))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RevokeAllFormData {
+    user_id: Option<String>,
+}
+
+/// Revoke all the sessions of a user, except the current one when they are the user's own.
+#[tracing::instrument(
+    name="Revoke all the sessions of a user.",
+    skip(form, pool, session, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn revoke_all_user_sessions(
+    form: web::Form<RevokeAllFormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target = session_owner(form.user_id.as_deref(), &user_id)?;
+    let except = if target == **user_id {
+        session.get_session_id().map_err(e500)?
+    } else {
+        None
+    };
+    let revoked = revoke_all_sessions(target, except, &pool).await.map_err(e500)?;
+    FlashMessage::info(format!("{} session(s) have been revoked.", revoked)).send();
+    Ok(see_other(&sessions_url(target, &user_id)))
+}
@@ -0,0 +1,89 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{authentication::{CsrfToken, UserId}, utils::e500};
+
+pub async fn list_subscribers(
+    _user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for subscriber in get_subscribers(&pool).await.map_err(e500)? {
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(&subscriber.email),
+            htmlescape::encode_minimal(&subscriber.name),
+            subscriber.status,
+            subscriber.subscribed_at.format("%Y-%m-%d %H:%M"),
+            htmlescape::encode_minimal(&subscriber.tags.join(", ")),
+            htmlescape::encode_minimal(&subscriber.lists.join(", ")),
+            htmlescape::encode_minimal(&subscriber.custom_fields.join(", ")),
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("subscribers.html"),
+            msg_html = msg_html,
+            rows_html = rows_html,
+            csrf_field = csrf_token.form_field(),
+        )))
+}
+
+struct SubscriberRow {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    tags: Vec<String>,
+    lists: Vec<String>,
+    custom_fields: Vec<String>,
+}
+
+#[tracing::instrument(
+    name="Retrieve subscribers with their tags, lists and custom fields.",
+    skip(pool)
+)]
+async fn get_subscribers(pool: &PgPool) -> anyhow::Result<Vec<SubscriberRow>> {
+    let subscribers = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT s.email, s.name, s.status, s.subscribed_at,
+               ARRAY(
+                   SELECT t.tag FROM subscriber_tags t
+                   WHERE t.subscriber_id = s.id
+                   ORDER BY t.tag
+               ) AS "tags!",
+               ARRAY(
+                   SELECT l.list_name FROM subscriber_lists l
+                   WHERE l.subscriber_id = s.id
+                   ORDER BY l.list_name
+               ) AS "lists!",
+               ARRAY(
+                   SELECT f.name || ' = ' || v.value
+                   FROM subscriber_custom_fields v
+                   JOIN custom_fields f ON f.field_id = v.field_id
+                   WHERE v.subscriber_id = s.id
+                   ORDER BY f.name
+               ) AS "custom_fields!"
+        FROM subscriptions s
+        ORDER BY s.subscribed_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscribers.")?;
+    Ok(subscribers)
+}
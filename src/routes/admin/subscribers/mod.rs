@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::list_subscribers;
+pub use post::{add_subscriber_tag, add_subscriber_to_list, remove_subscriber_from_list, remove_subscriber_tag};
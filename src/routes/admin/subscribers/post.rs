@@ -0,0 +1,180 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::domain::{SubscriberList, SubscriberTag};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    tag: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListFormData {
+    email: String,
+    list_name: String,
+}
+
+#[tracing::instrument(
+    name="Add a tag to a subscriber.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn add_subscriber_tag(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData { email, tag } = form.into_inner();
+    let (subscriber_id, tag) = match parse_form(&email, tag, SubscriberTag::parse, &pool).await.map_err(e500)? {
+        Some(parsed) => parsed,
+        None => return Ok(see_other("/admin/subscribers")),
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        tag.as_ref(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to insert subscriber tag.")
+    .map_err(e500)?;
+    FlashMessage::info(format!("The tag '{}' has been added.", tag)).send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(
+    name="Remove a tag from a subscriber.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn remove_subscriber_tag(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData { email, tag } = form.into_inner();
+    let (subscriber_id, tag) = match parse_form(&email, tag, SubscriberTag::parse, &pool).await.map_err(e500)? {
+        Some(parsed) => parsed,
+        None => return Ok(see_other("/admin/subscribers")),
+    };
+    sqlx::query!(
+        r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1 AND tag = $2"#,
+        subscriber_id,
+        tag.as_ref(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete subscriber tag.")
+    .map_err(e500)?;
+    FlashMessage::info(format!("The tag '{}' has been removed.", tag)).send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(
+    name="Add a subscriber to a list.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn add_subscriber_to_list(
+    form: web::Form<ListFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ListFormData { email, list_name } = form.into_inner();
+    let (subscriber_id, list) = match parse_form(&email, list_name, SubscriberList::parse, &pool).await.map_err(e500)? {
+        Some(parsed) => parsed,
+        None => return Ok(see_other("/admin/subscribers")),
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_lists (subscriber_id, list_name)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        list.as_ref(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to add a subscriber to a list.")
+    .map_err(e500)?;
+    FlashMessage::info(format!("The subscriber has been added to the list '{}'.", list)).send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(
+    name="Remove a subscriber from a list.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn remove_subscriber_from_list(
+    form: web::Form<ListFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ListFormData { email, list_name } = form.into_inner();
+    let (subscriber_id, list) = match parse_form(&email, list_name, SubscriberList::parse, &pool).await.map_err(e500)? {
+        Some(parsed) => parsed,
+        None => return Ok(see_other("/admin/subscribers")),
+    };
+    sqlx::query!(
+        r#"DELETE FROM subscriber_lists WHERE subscriber_id = $1 AND list_name = $2"#,
+        subscriber_id,
+        list.as_ref(),
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to remove a subscriber from a list.")
+    .map_err(e500)?;
+    FlashMessage::info(format!("The subscriber has been removed from the list '{}'.", list)).send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+/// Resolve the subscriber and validate the tag or list name, sending an error flash message
+/// and returning `None` when either is invalid.
+async fn parse_form<T>(
+    email: &str,
+    value: String,
+    parse: impl FnOnce(String) -> Result<T, String>,
+    pool: &PgPool,
+) -> anyhow::Result<Option<(Uuid, T)>> {
+    let value = match parse(value) {
+        Ok(value) => value,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(None);
+        }
+    };
+    match get_subscriber_id_by_email(email.trim(), pool).await? {
+        Some(subscriber_id) => Ok(Some((subscriber_id, value))),
+        None => {
+            FlashMessage::error("There is no subscriber with this email address.").send();
+            Ok(None)
+        }
+    }
+}
+
+#[tracing::instrument(
+    name="Get subscriber id by email.",
+    skip(pool)
+)]
+pub async fn get_subscriber_id_by_email(email: &str, pool: &PgPool) -> anyhow::Result<Option<Uuid>> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE email = $1"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber id.")?;
+    Ok(row.map(|r| r.id))
+}
@@ -0,0 +1,42 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::authentication::{CsrfToken, UserId};
+use crate::suppression_list::get_suppressions;
+use crate::utils::e500;
+
+pub async fn list_suppressions(
+    _user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for suppression in get_suppressions(&pool).await.map_err(e500)? {
+        let email = htmlescape::encode_minimal(&suppression.email);
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+            <td><form action=\"/admin/suppressions/delete\" method=\"post\">{}\
+            <input hidden type=\"text\" name=\"email\" value=\"{}\">\
+            <button type=\"submit\">Remove</button></form></td></tr>",
+            email,
+            suppression.reason.as_str(),
+            htmlescape::encode_minimal(&suppression.source),
+            suppression.created_at.format("%Y-%m-%d %H:%M"),
+            csrf_field,
+            email,
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(include_str!("suppressions.html"), msg_html = msg_html, rows_html = rows_html, csrf_field = csrf_field)))
+}
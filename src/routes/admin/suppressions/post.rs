@@ -0,0 +1,63 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::authentication::UserId;
+use crate::domain::SubscriberEmail;
+use crate::utils::{e500, see_other};
+
+use crate::suppression_list::{add_suppression, remove_suppression, SuppressionReason};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+}
+
+#[tracing::instrument(
+    name="Block an address.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn create_suppression(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match SubscriberEmail::parse(form.0.email.trim().to_owned()) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/suppressions"));
+        }
+    };
+    let inserted = add_suppression(email.as_ref(), SuppressionReason::Blocked, "admin", &pool)
+        .await
+        .map_err(e500)?;
+    if inserted {
+        FlashMessage::info(format!("{} has been added to the suppression list.", email)).send();
+    } else {
+        FlashMessage::error(format!("{} is already on the suppression list.", email)).send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
+
+#[tracing::instrument(
+    name="Remove an address from the suppression list.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn delete_suppression(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = form.0.email.trim().to_owned();
+    let removed = remove_suppression(&email, &pool).await.map_err(e500)?;
+    let email = htmlescape::encode_minimal(&email);
+    if removed {
+        FlashMessage::info(format!("{} has been removed from the suppression list.", email)).send();
+    } else {
+        FlashMessage::error(format!("{} is not on the suppression list.", email)).send();
+    }
+    Ok(see_other("/admin/suppressions"))
+}
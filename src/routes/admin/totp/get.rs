@@ -0,0 +1,81 @@
+use std::fmt::Write;
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+
+use crate::authentication::totp::{base32_encode, otpauth_uri};
+use crate::authentication::{get_totp_state, CsrfToken, Role, TotpState, UserId};
+use crate::configuration::TotpSettings;
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::e500;
+
+/// Shown as the account issuer in authenticator apps.
+const TOTP_ISSUER: &str = "Newsletter";
+
+#[tracing::instrument(
+    name="Show the two-factor authentication settings",
+    skip(user_id, csrf_token, pool, totp_settings, flash_messages),
+    fields(user_id=%&*user_id)
+)]
+pub async fn totp_form(
+    user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    totp_settings: web::Data<TotpSettings>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let state_html = match get_totp_state(*user_id, &totp_settings, &pool).await.map_err(e500)? {
+        TotpState::Disabled => format!(
+            r#"<p>Two-factor authentication is off.</p>
+        <form action="/admin/totp/enroll" method="post">
+            {csrf_field}
+            <button type="submit">Set up two-factor authentication</button>
+        </form>"#
+        ),
+        TotpState::Pending(secret) => {
+            let username = get_username(*user_id, &pool).await.map_err(e500)?;
+            let uri = otpauth_uri(TOTP_ISSUER, &username, &secret);
+            format!(
+                r#"<p>Scan <a href="{uri}">this link</a> with your authenticator app, or enter the key <code>{key}</code>.</p>
+        <p><code>{uri}</code></p>
+        <form action="/admin/totp/confirm" method="post">
+            {csrf_field}
+            <label>Code from your authenticator app
+                <input type="text" inputmode="numeric" autocomplete="one-time-code" name="code">
+            </label>
+            <button type="submit">Turn on</button>
+        </form>"#,
+                uri = htmlescape::encode_minimal(&uri),
+                key = base32_encode(&secret),
+                csrf_field = csrf_field,
+            )
+        }
+        TotpState::Enabled if totp_settings.required_for_owners && user_id.role() == Role::Owner => {
+            "<p>Two-factor authentication is on. It is required for owners.</p>".to_string()
+        }
+        TotpState::Enabled => format!(
+            r#"<p>Two-factor authentication is on.</p>
+        <form action="/admin/totp/disable" method="post">
+            {csrf_field}
+            <label>Code from your authenticator app, or a recovery code
+                <input type="text" autocomplete="one-time-code" name="code">
+            </label>
+            <button type="submit">Turn off</button>
+        </form>"#
+        ),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("totp.html"),
+            msg_html = msg_html,
+            state_html = state_html,
+        )))
+}
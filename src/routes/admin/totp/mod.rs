@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::totp_form;
+pub use post::{confirm_totp, enroll_totp, turn_off_totp};
@@ -0,0 +1,83 @@
+use std::fmt::Write;
+
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::authentication::{confirm_totp_enrollment, disable_totp, start_totp_enrollment, verify_second_factor, Role, UserId};
+use crate::configuration::{PasswordHashingSettings, TotpSettings};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name="Start a two-factor authentication enrollment",
+    skip(user_id, pool, totp_settings),
+    fields(user_id=%&*user_id)
+)]
+pub async fn enroll_totp(
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    totp_settings: web::Data<TotpSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    start_totp_enrollment(**user_id, &totp_settings, &pool).await.map_err(e500)?;
+    Ok(see_other("/admin/totp"))
+}
+
+/// Turn two-factor authentication on and show the recovery codes, once.
+#[tracing::instrument(
+    name="Confirm a two-factor authentication enrollment",
+    skip(form, user_id, pool, totp_settings, hashing),
+    fields(user_id=%&*user_id)
+)]
+pub async fn confirm_totp(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    totp_settings: web::Data<TotpSettings>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let recovery_codes = confirm_totp_enrollment(**user_id, &form.code, &totp_settings, &hashing, &pool)
+        .await
+        .map_err(e500)?;
+    let Some(recovery_codes) = recovery_codes else {
+        FlashMessage::error("The code is invalid, check the clock of your device and try again.").send();
+        return Ok(see_other("/admin/totp"));
+    };
+    let mut codes_html = String::new();
+    for code in recovery_codes {
+        writeln!(codes_html, "<li><code>{}</code></li>", code).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(format!(include_str!("recovery_codes.html"), codes_html = codes_html)))
+}
+
+#[tracing::instrument(
+    name="Turn off two-factor authentication",
+    skip(form, user_id, pool, totp_settings),
+    fields(user_id=%&*user_id)
+)]
+pub async fn turn_off_totp(
+    form: web::Form<FormData>,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    totp_settings: web::Data<TotpSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if totp_settings.required_for_owners && user_id.role() == Role::Owner {
+        FlashMessage::error("Two-factor authentication is required for owners.").send();
+        return Ok(see_other("/admin/totp"));
+    }
+    if !verify_second_factor(**user_id, &form.code, &totp_settings, &pool).await.map_err(e500)? {
+        FlashMessage::error("The code is invalid or has already been used.").send();
+        return Ok(see_other("/admin/totp"));
+    }
+    disable_totp(**user_id, &pool).await.map_err(e500)?;
+    FlashMessage::info("Two-factor authentication has been turned off.").send();
+    Ok(see_other("/admin/totp"))
+}
@@ -0,0 +1,103 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::authentication::{CsrfToken, Role, UserId};
+use crate::utils::e500;
+
+use super::get_users;
+use super::invitation::get_pending_invitations;
+
+pub async fn list_users(
+    user_id: web::ReqData<UserId>,
+    csrf_token: web::ReqData<CsrfToken>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_field = csrf_token.form_field();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let mut rows_html = String::new();
+    for user in get_users(&pool).await.map_err(e500)? {
+        // Users cannot lock themselves out.
+        let sessions_link = format!("<a href=\"/admin/sessions?user_id={}\">Sessions</a>", user.user_id);
+        let actions_html = if user.user_id == **user_id {
+            sessions_link
+        } else if user.is_active {
+            sessions_link
+                + &role_form(user.user_id, user.role, &csrf_field)
+                + &user_action_form(user.user_id, "deactivate", "Deactivate", &csrf_field)
+                + &user_action_form(user.user_id, "delete", "Delete", &csrf_field)
+        } else {
+            sessions_link
+                + &role_form(user.user_id, user.role, &csrf_field)
+                + &user_action_form(user.user_id, "activate", "Reactivate", &csrf_field)
+                + &user_action_form(user.user_id, "delete", "Delete", &csrf_field)
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(&user.username),
+            htmlescape::encode_minimal(user.email.as_deref().unwrap_or("")),
+            user.role,
+            if user.is_active { "active" } else { "deactivated" },
+            actions_html,
+        ).unwrap();
+    }
+    let mut invitations_html = String::new();
+    for invitation in get_pending_invitations(&pool).await.map_err(e500)? {
+        writeln!(
+            invitations_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            htmlescape::encode_minimal(&invitation.email),
+            invitation.role,
+            invitation.expires_at.format("%Y-%m-%d %H:%M UTC"),
+        ).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("users.html"),
+            msg_html = msg_html,
+            rows_html = rows_html,
+            invitations_html = invitations_html,
+            role_options = role_options(Role::Editor),
+            csrf_field = csrf_field,
+        )))
+}
+
+fn role_options(selected: Role) -> String {
+    Role::ALL
+        .iter()
+        .map(|role| format!(
+            "<option value=\"{}\"{}>{}</option>",
+            role,
+            if *role == selected { " selected" } else { "" },
+            role,
+        ))
+        .collect()
+}
+
+fn role_form(user_id: Uuid, role: Role, csrf_field: &str) -> String {
+    format!(
+        "<form action=\"/admin/users/role\" method=\"post\">{}\
+        <input hidden type=\"text\" name=\"user_id\" value=\"{}\">\
+        <select name=\"role\">{}</select>\
+        <button type=\"submit\">Change role</button></form>",
+        csrf_field, user_id, role_options(role)
+    )
+}
+
+fn user_action_form(user_id: Uuid, action: &str, label: &str, csrf_field: &str) -> String {
+    format!(
+        "<form action=\"/admin/users/{}\" method=\"post\">{}\
+        <input hidden type=\"text\" name=\"user_id\" value=\"{}\">\
+        <button type=\"submit\">{}</button></form>",
+        action, csrf_field, user_id, label
+    )
+}
@@ -0,0 +1,122 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::authentication::Role;
+use crate::utils::generate_token;
+
+/// How long an invitee has to set up their account.
+const INVITATION_TTL_HOURS: i64 = 72;
+
+pub struct Invitation {
+    pub invitation_token: String,
+    pub email: String,
+    pub role: Role,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Store a new invitation for `email` and return its token.
+#[tracing::instrument(
+    name="Create an invitation.",
+    skip(pool)
+)]
+pub async fn create_invitation(email: &str, role: Role, invited_by: Uuid, pool: &PgPool) -> anyhow::Result<Invitation> {
+    let invitation = Invitation {
+        invitation_token: generate_token(),
+        email: email.to_owned(),
+        role,
+        expires_at: Utc::now() + Duration::hours(INVITATION_TTL_HOURS),
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO user_invitations (invitation_token, email, role, invited_by, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, now(), $5)
+        "#,
+        invitation.invitation_token,
+        invitation.email,
+        invitation.role.as_str(),
+        invited_by,
+        invitation.expires_at,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store an invitation.")?;
+    Ok(invitation)
+}
+
+/// Invitations that have been neither accepted nor expired.
+#[tracing::instrument(
+    name="Get pending invitations.",
+    skip(pool)
+)]
+pub async fn get_pending_invitations(pool: &PgPool) -> anyhow::Result<Vec<Invitation>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT invitation_token, email, role, expires_at
+        FROM user_invitations
+        WHERE accepted_at IS NULL AND expires_at > now()
+        ORDER BY expires_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve pending invitations.")?;
+    rows.into_iter()
+        .map(|r| Ok(Invitation {
+            invitation_token: r.invitation_token,
+            email: r.email,
+            role: Role::try_from(r.role).map_err(anyhow::Error::msg)?,
+            expires_at: r.expires_at,
+        }))
+        .collect()
+}
+
+/// The invitation with `invitation_token`, provided it can still be accepted.
+#[tracing::instrument(
+    name="Get a pending invitation.",
+    skip(invitation_token, pool)
+)]
+pub async fn get_pending_invitation(invitation_token: &str, pool: &PgPool) -> anyhow::Result<Option<Invitation>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT invitation_token, email, role, expires_at
+        FROM user_invitations
+        WHERE invitation_token = $1 AND accepted_at IS NULL AND expires_at > now()
+        "#,
+        invitation_token
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve an invitation.")?;
+    row.map(|r| Ok(Invitation {
+        invitation_token: r.invitation_token,
+        email: r.email,
+        role: Role::try_from(r.role).map_err(anyhow::Error::msg)?,
+        expires_at: r.expires_at,
+    }))
+    .transpose()
+}
+
+/// Mark the invitation as used. Returns `false` when it was accepted concurrently or has expired.
+#[tracing::instrument(
+    name="Accept an invitation.",
+    skip(invitation_token, transaction)
+)]
+pub async fn mark_invitation_accepted(
+    invitation_token: &str,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> anyhow::Result<bool> {
+    let accepted = sqlx::query!(
+        r#"
+        UPDATE user_invitations SET accepted_at = now()
+        WHERE invitation_token = $1 AND accepted_at IS NULL AND expires_at > now()
+        "#,
+        invitation_token
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to accept an invitation.")?
+    .rows_affected();
+    Ok(accepted == 1)
+}
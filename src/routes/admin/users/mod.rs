@@ -0,0 +1,9 @@
+mod get;
+mod invitation;
+mod post;
+mod user;
+
+pub use get::list_users;
+pub use invitation::{mark_invitation_accepted, get_pending_invitation, Invitation};
+pub use post::{activate_user, change_user_role, deactivate_user, delete_user, invite_user};
+pub use user::{get_active_user, get_users, ActiveUser, AdminUser};
@@ -0,0 +1,185 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{Role, UserId};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e400, e500, see_other};
+
+use super::get_users;
+use super::invitation::create_invitation;
+use super::user::{remove_user, set_user_active, set_user_role};
+
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    email: String,
+    role: String,
+}
+
+#[tracing::instrument(
+    name="Invite a user.",
+    skip(form, pool, email_client, base_url, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn invite_user(
+    form: web::Form<InviteFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let role = Role::try_from(form.0.role).map_err(e400)?;
+    let email = match SubscriberEmail::parse(form.0.email.trim().to_owned()) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    let users = get_users(&pool).await.map_err(e500)?;
+    if users.iter().any(|u| u.email.as_deref() == Some(email.as_ref())) {
+        FlashMessage::error(format!("{} already has an account.", email)).send();
+        return Ok(see_other("/admin/users"));
+    }
+    let invitation = create_invitation(email.as_ref(), role, **user_id, &pool).await.map_err(e500)?;
+    let invitation_link = format!(
+        "{}/invitations/accept?invitation_token={}",
+        base_url.0, invitation.invitation_token
+    );
+    let html_body = format!(
+        "You have been invited to manage our newsletter.<br />\
+        Click <a href=\"{}\">here</a> to set up your account before {}.",
+        invitation_link,
+        invitation.expires_at.format("%Y-%m-%d %H:%M UTC"),
+    );
+    let text_body = format!(
+        "You have been invited to manage our newsletter.\nVisit {} to set up your account before {}.",
+        invitation_link,
+        invitation.expires_at.format("%Y-%m-%d %H:%M UTC"),
+    );
+    if let Err(e) = email_client.send_email(&email, "You have been invited", &html_body, &text_body).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            "Failed to send an invitation to {}.", email
+        );
+        FlashMessage::error(format!("Failed to send the invitation to {}.", email)).send();
+        return Ok(see_other("/admin/users"));
+    }
+    FlashMessage::info(format!("An invitation has been sent to {}.", email)).send();
+    Ok(see_other("/admin/users"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RoleFormData {
+    user_id: String,
+    role: String,
+}
+
+#[tracing::instrument(
+    name="Change the role of a user.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn change_user_role(
+    form: web::Form<RoleFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.into_inner();
+    let target = Uuid::parse_str(&form.user_id).map_err(e400)?;
+    let role = Role::try_from(form.role).map_err(e400)?;
+    // Owners cannot demote themselves, so there always is one left.
+    if target == **user_id {
+        FlashMessage::error("You cannot change your own role.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    if set_user_role(target, role, &pool).await.map_err(e500)? {
+        FlashMessage::info(format!("The role of the user has been changed to {}.", role)).send();
+    } else {
+        FlashMessage::error("There is no user with the provided id.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UserFormData {
+    user_id: String,
+}
+
+impl UserFormData {
+    /// The user the action applies to, who cannot be the one performing it.
+    fn target(&self, user_id: &UserId) -> Result<Option<Uuid>, actix_web::Error> {
+        let target = Uuid::parse_str(&self.user_id).map_err(e400)?;
+        if target == **user_id {
+            FlashMessage::error("You cannot deactivate or delete your own account.").send();
+            return Ok(None);
+        }
+        Ok(Some(target))
+    }
+}
+
+#[tracing::instrument(
+    name="Deactivate a user.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn deactivate_user(
+    form: web::Form<UserFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(target) = form.target(&user_id)? else {
+        return Ok(see_other("/admin/users"));
+    };
+    if set_user_active(target, false, &pool).await.map_err(e500)? {
+        FlashMessage::info("The user has been deactivated.").send();
+    } else {
+        FlashMessage::error("There is no user with the provided id.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name="Reactivate a user.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn activate_user(
+    form: web::Form<UserFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(target) = form.target(&user_id)? else {
+        return Ok(see_other("/admin/users"));
+    };
+    if set_user_active(target, true, &pool).await.map_err(e500)? {
+        FlashMessage::info("The user has been reactivated.").send();
+    } else {
+        FlashMessage::error("There is no user with the provided id.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(
+    name="Delete a user.",
+    skip(form, pool, user_id),
+    fields(user_id=%&*user_id)
+)]
+pub async fn delete_user(
+    form: web::Form<UserFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(target) = form.target(&user_id)? else {
+        return Ok(see_other("/admin/users"));
+    };
+    if remove_user(target, &pool).await.map_err(e500)? {
+        FlashMessage::info("The user has been deleted.").send();
+    } else {
+        FlashMessage::error("There is no user with the provided id.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
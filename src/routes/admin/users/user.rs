@@ -0,0 +1,119 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::Role;
+
+pub struct AdminUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: Option<String>,
+    pub is_active: bool,
+    pub role: Role,
+}
+
+#[tracing::instrument(
+    name="Get users.",
+    skip(pool)
+)]
+pub async fn get_users(pool: &PgPool) -> anyhow::Result<Vec<AdminUser>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT user_id, username, email, is_active, role
+        FROM users
+        ORDER BY username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve users.")?;
+    rows.into_iter()
+        .map(|r| Ok(AdminUser {
+            user_id: r.user_id,
+            username: r.username,
+            email: r.email,
+            is_active: r.is_active,
+            role: Role::try_from(r.role).map_err(anyhow::Error::msg)?,
+        }))
+        .collect()
+}
+
+/// What the admin area needs to know about the logged-in user.
+pub struct ActiveUser {
+    pub role: Role,
+    pub totp_enabled: bool,
+}
+
+/// `None` if the user was deleted or deactivated.
+#[tracing::instrument(
+    name="Get an active user.",
+    skip(pool)
+)]
+pub async fn get_active_user(user_id: Uuid, pool: &PgPool) -> anyhow::Result<Option<ActiveUser>> {
+    let row = sqlx::query!(
+        r#"SELECT role, totp_enabled FROM users WHERE user_id = $1 AND is_active"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve an active user.")?;
+    row.map(|r| Ok(ActiveUser {
+        role: Role::try_from(r.role).map_err(anyhow::Error::msg)?,
+        totp_enabled: r.totp_enabled,
+    }))
+    .transpose()
+}
+
+#[tracing::instrument(
+    name="Set the role of a user.",
+    skip(pool)
+)]
+pub async fn set_user_role(user_id: Uuid, role: Role, pool: &PgPool) -> anyhow::Result<bool> {
+    let updated = sqlx::query!(
+        r#"UPDATE users SET role = $2 WHERE user_id = $1"#,
+        user_id,
+        role.as_str(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the role of a user.")?
+    .rows_affected();
+    Ok(updated == 1)
+}
+
+#[tracing::instrument(
+    name="Set whether a user is active.",
+    skip(pool)
+)]
+pub async fn set_user_active(user_id: Uuid, is_active: bool, pool: &PgPool) -> anyhow::Result<bool> {
+    let updated = sqlx::query!(
+        r#"UPDATE users SET is_active = $2 WHERE user_id = $1"#,
+        user_id,
+        is_active,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update the status of a user.")?
+    .rows_affected();
+    Ok(updated == 1)
+}
+
+/// Delete a user along with the responses saved for their idempotency keys.
+#[tracing::instrument(
+    name="Delete user.",
+    skip(pool)
+)]
+pub async fn remove_user(user_id: Uuid, pool: &PgPool) -> anyhow::Result<bool> {
+    let mut transaction = pool.begin().await.context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(r#"DELETE FROM idempotency WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the saved responses of a user.")?;
+    let deleted = sqlx::query!(r#"DELETE FROM users WHERE user_id = $1"#, user_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete user.")?
+        .rows_affected();
+    transaction.commit().await.context("Failed to commit the deletion of a user.")?;
+    Ok(deleted == 1)
+}
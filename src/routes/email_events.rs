@@ -0,0 +1,161 @@
+use actix_web::http::header::{HeaderMap, HeaderValue};
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use base64::Engine;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use tracing::{field::display, Span};
+
+use crate::routes::error_chain_fmt;
+use crate::suppression_list::{add_suppression, SuppressionReason};
+use crate::startup::WebhookSecret;
+
+/// The fields we use from a Postmark webhook payload.
+/// See https://postmarkapp.com/developer/webhooks/webhooks-overview
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmailEvent {
+    record_type: String,
+    #[serde(rename = "Type", default)]
+    bounce_type: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+impl EmailEvent {
+    /// Why the recipient must not be emailed anymore, for events that should stop all mail to them.
+    fn suppression_reason(&self) -> Option<SuppressionReason> {
+        match (self.record_type.as_str(), self.bounce_type.as_deref()) {
+            ("Bounce", Some("HardBounce")) => Some(SuppressionReason::Bounced),
+            ("SpamComplaint", _) => Some(SuppressionReason::Complained),
+            _ => None,
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailEventError {
+    #[error("Authentication failed.")]
+    AuthError(#[source] anyhow::Error),
+    #[error("The event payload is invalid.")]
+    ValidationError(#[source] serde_json::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for EmailEventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for EmailEventError {
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            EmailEventError::UnexpectedError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            EmailEventError::ValidationError(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            EmailEventError::AuthError(_) => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                let header_value = HeaderValue::from_str(r#"Basic realm="email-events""#).unwrap();
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, header_value);
+                response
+            }
+        }
+    }
+}
+
+/// Ingest bounce and spam complaint notifications from Postmark.
+/// Postmark authenticates with basic auth, the password being the shared webhook secret.
+/// The payload is only parsed once the caller is authenticated.
+#[tracing::instrument(
+    name = "Ingest an email event",
+    skip(body, request, pool, secret),
+    fields(record_type = tracing::field::Empty)
+)]
+pub async fn ingest_email_event(
+    body: web::Bytes,
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    secret: web::Data<WebhookSecret>,
+) -> Result<HttpResponse, EmailEventError> {
+    check_webhook_secret(request.headers(), &secret).map_err(EmailEventError::AuthError)?;
+    let event: EmailEvent = serde_json::from_slice(&body).map_err(EmailEventError::ValidationError)?;
+    Span::current().record("record_type", display(&event.record_type));
+    let (Some(reason), Some(email)) = (event.suppression_reason(), event.email.as_deref()) else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    add_suppression(email, reason, "postmark", &pool).await?;
+    // The subscriber's status mirrors the reason, e.g. `bounced`.
+    let status = reason.as_str();
+    let updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = $2
+        WHERE lower(email) = lower($1)
+        "#,
+        email,
+        status,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the status of a subscriber.")?
+    .rows_affected();
+    tracing::info!(
+        status,
+        updated,
+        "Recorded a {} event.", event.record_type
+    );
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn check_webhook_secret(headers: &HeaderMap, secret: &WebhookSecret) -> Result<(), anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing.")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+    let (_, password) = decoded_credentials
+        .split_once(':')
+        .context("A password must be provided in 'Basic' auth.")?;
+    if !bool::from(password.as_bytes().ct_eq(secret.0.expose_secret().as_bytes())) {
+        anyhow::bail!("The webhook secret is invalid.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailEvent;
+    use crate::suppression_list::SuppressionReason;
+
+    fn event(json: serde_json::Value) -> EmailEvent {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn hard_bounces_and_complaints_suppress_the_recipient() {
+        let bounce = event(serde_json::json!({"RecordType": "Bounce", "Type": "HardBounce", "Email": "a@b.com"}));
+        let complaint = event(serde_json::json!({"RecordType": "SpamComplaint", "Type": "SpamComplaint", "Email": "a@b.com"}));
+        assert_eq!(bounce.suppression_reason(), Some(SuppressionReason::Bounced));
+        assert_eq!(complaint.suppression_reason(), Some(SuppressionReason::Complained));
+    }
+
+    #[test]
+    fn soft_bounces_and_other_events_are_ignored() {
+        let soft_bounce = event(serde_json::json!({"RecordType": "Bounce", "Type": "SoftBounce", "Email": "a@b.com"}));
+        let delivery = event(serde_json::json!({"RecordType": "Delivery", "Recipient": "a@b.com"}));
+        assert_eq!(soft_bounce.suppression_reason(), None);
+        assert_eq!(delivery.suppression_reason(), None);
+    }
+}
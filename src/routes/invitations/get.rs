@@ -0,0 +1,42 @@
+use std::fmt::Write;
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+
+use crate::routes::get_pending_invitation;
+use crate::utils::{e404, e500};
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    invitation_token: String,
+}
+
+/// Let an invitee pick their username and password.
+#[tracing::instrument(
+    name = "Show the invitation form",
+    skip(params, pool, flash_messages)
+)]
+pub async fn accept_invitation_form(
+    params: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let invitation = get_pending_invitation(&params.invitation_token, &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("This invitation is invalid or has expired."))?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("accept_invitation_form.html"),
+            msg_html = msg_html,
+            email = htmlescape::encode_minimal(&invitation.email),
+            role = invitation.role,
+            invitation_token = htmlescape::encode_minimal(&invitation.invitation_token),
+        )))
+}
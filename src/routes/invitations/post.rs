@@ -0,0 +1,81 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::authentication::{create_user, validate_new_password};
+use crate::configuration::PasswordHashingSettings;
+use crate::routes::{get_pending_invitation, mark_invitation_accepted};
+use crate::utils::{e404, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    invitation_token: String,
+    username: String,
+    password: Secret<String>,
+    password_check: Secret<String>,
+}
+
+/// Create the account of an invitee, who can then log in.
+#[tracing::instrument(
+    name = "Accept an invitation",
+    skip(form, pool, hashing),
+    fields(username = %form.username)
+)]
+pub async fn accept_invitation(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.into_inner();
+    let invitation = get_pending_invitation(&form.invitation_token, &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("This invitation is invalid or has expired."))?;
+    let form_url = format!("/invitations/accept?invitation_token={}", invitation.invitation_token);
+    let username = form.username.trim();
+    if username.is_empty() || username.len() > 255 {
+        FlashMessage::error("The username should be between 1 and 255 characters.").send();
+        return Ok(see_other(&form_url));
+    }
+    if form.password.expose_secret() != form.password_check.expose_secret() {
+        FlashMessage::error("You entered two different passwords - the field values must match.").send();
+        return Ok(see_other(&form_url));
+    }
+    if let Err(e) = validate_new_password(&form.password, username) {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&form_url));
+    }
+    if is_username_taken(username, &pool).await.map_err(e500)? {
+        FlashMessage::error("This username is already taken.").send();
+        return Ok(see_other(&form_url));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    if !mark_invitation_accepted(&invitation.invitation_token, &mut transaction).await.map_err(e500)? {
+        return Err(e404("This invitation is invalid or has expired."));
+    }
+    create_user(username, &invitation.email, invitation.role, form.password, &hashing, &mut transaction)
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to create a new user.")
+        .map_err(e500)?;
+    FlashMessage::info("Your account has been created, you can now log in.").send();
+    Ok(see_other("/login"))
+}
+
+async fn is_username_taken(username: &str, pool: &PgPool) -> anyhow::Result<bool> {
+    let row = sqlx::query!(r#"SELECT user_id FROM users WHERE username = $1"#, username)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to check whether a username is taken.")?;
+    Ok(row.is_some())
+}
@@ -1,9 +1,11 @@
-use actix_web::{error::InternalError, http::{header::LOCATION, StatusCode}, web, HttpResponse, ResponseError};
+use actix_web::{error::InternalError, http::{header::LOCATION, StatusCode}, web, HttpRequest, HttpResponse, ResponseError};
 use actix_web_flash_messages::FlashMessage;
+use chrono::{DateTime, Utc};
 use secrecy::Secret;
 use sqlx::PgPool;
 
-use crate::{authentication::{validate_credentials, AuthError, Credentials}, routes::error_chain_fmt, session_state::TypedSession};
+use crate::configuration::PasswordHashingSettings;
+use crate::{authentication::{clear_failed_logins, complete_login, count_login_attempt, record_failed_login, time_left, validate_credentials, AuthError, Credentials, DummyPasswordHash, LoginAttempt, LoginThrottle}, routes::{error_chain_fmt, get_active_user}, session_state::TypedSession, utils::client_ip};
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
@@ -13,7 +15,7 @@ pub struct FormData {
 
 #[tracing::instrument(
     name="Login a user",
-    skip(form, pool, session),
+    skip(form, pool, hashing, dummy_hash, session, request),
     fields(
         username = tracing::field::Empty,
         user_id = tracing::field::Empty
@@ -22,28 +24,71 @@ pub struct FormData {
 pub async fn login(
     form: web::Form<FormData>, 
     pool: web::Data<PgPool>,
+    hashing: web::Data<PasswordHashingSettings>,
+    dummy_hash: web::Data<DummyPasswordHash>,
     session: TypedSession,
+    request: HttpRequest,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let username = form.0.username;
+    tracing::Span::current().record("username", tracing::field::display(&username));
+    let ip = client_ip(&request);
+    let attempt = LoginAttempt {
+        username: &username,
+        ip: ip.as_deref(),
+    };
+    // Locked out attempts are refused before the password is even checked.
+    let counted = match count_login_attempt(&attempt, &pool)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        LoginThrottle::LockedOut(locked_until) => {
+            return Err(login_redirect(LoginError::TooManyAttempts(locked_until)))
+        },
+        LoginThrottle::Counted(counted) => counted,
+    };
     let credentials = Credentials {
-        username: form.0.username,
+        username: username.clone(),
         password: form.0.password,
     };
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
-    match validate_credentials(credentials, &pool).await {
+    match validate_credentials(credentials, &hashing, &dummy_hash, &pool).await {
         Ok(user_id) => {
-            session.renew();
-            session 
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
             tracing::Span::current()
-                .record("user_id", &tracing::field::display(&user_id));
+                .record("user_id", tracing::field::display(&user_id));
+            clear_failed_logins(&attempt, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            let user = get_active_user(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+                .ok_or_else(|| login_redirect(LoginError::AuthError(anyhow::anyhow!("The user is no longer active."))))?;
+            if user.totp_enabled {
+                // Half-authenticated until the second factor is checked.
+                session.renew();
+                session
+                    .insert_pending_user_id(user_id, Utc::now())
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/totp"))
+                    .finish());
+            }
+            complete_login(user_id, &session, &request, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         },
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    match record_failed_login(counted, &pool)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+                    {
+                        Some(locked_until) => LoginError::TooManyAttempts(locked_until),
+                        None => LoginError::AuthError(e.into()),
+                    }
+                },
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
             
@@ -59,6 +104,8 @@ pub enum LoginError {
     UnexpectedError(#[from] anyhow::Error),
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, try again in {}.", time_left(*.0))]
+    TooManyAttempts(DateTime<Utc>),
 }
 
 impl std::fmt::Debug for LoginError {
@@ -77,7 +124,7 @@ fn login_redirect(error: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(error.to_string()).send();
 
     let response = HttpResponse::SeeOther()
-        .insert_header((LOCATION, format!("/login")))
+        .insert_header((LOCATION, "/login"))
         .finish();
     InternalError::from_response(error, response)
 }
\ No newline at end of file
@@ -0,0 +1,25 @@
+use std::fmt::Write;
+
+use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use chrono::Utc;
+
+use crate::{session_state::TypedSession, utils::{e500, see_other}};
+
+/// The second login step, for users who enabled two-factor authentication.
+pub async fn login_totp_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_user_id(Utc::now()).map_err(e500)?.is_none() {
+        session.remove_pending_user_id();
+        return Ok(see_other("/login"));
+    }
+    let mut error_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(include_str!("totp_form.html"), error_html = error_html)))
+}
@@ -0,0 +1,64 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::authentication::{clear_failed_logins, complete_login, count_login_attempt, record_failed_login, time_left, verify_second_factor, LoginAttempt, LoginThrottle};
+use crate::configuration::TotpSettings;
+use crate::routes::dashboard::get_username;
+use crate::routes::get_active_user;
+use crate::session_state::TypedSession;
+use crate::utils::{client_ip, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name="Check the second factor of a login",
+    skip(form, pool, session, totp_settings, request),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn login_totp(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    totp_settings: web::Data<TotpSettings>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(user_id) = session.get_pending_user_id(Utc::now()).map_err(e500)? else {
+        session.remove_pending_user_id();
+        FlashMessage::error("Your login has expired, please enter your password again.").send();
+        return Ok(see_other("/login"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    // Codes are guessed against the same counters as passwords.
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    let ip = client_ip(&request);
+    let attempt = LoginAttempt {
+        username: &username,
+        ip: ip.as_deref(),
+    };
+    let counted = match count_login_attempt(&attempt, &pool).await.map_err(e500)? {
+        LoginThrottle::LockedOut(locked_until) => {
+            FlashMessage::error(format!("Too many failed login attempts, try again in {}.", time_left(locked_until))).send();
+            return Ok(see_other("/login/totp"));
+        }
+        LoginThrottle::Counted(counted) => counted,
+    };
+    if !verify_second_factor(user_id, &form.code, &totp_settings, &pool).await.map_err(e500)? {
+        match record_failed_login(counted, &pool).await.map_err(e500)? {
+            Some(locked_until) => FlashMessage::error(format!("Too many failed login attempts, try again in {}.", time_left(locked_until))).send(),
+            None => FlashMessage::error("The code is invalid or has already been used.").send(),
+        }
+        return Ok(see_other("/login/totp"));
+    }
+    clear_failed_logins(&attempt, &pool).await.map_err(e500)?;
+    if get_active_user(user_id, &pool).await.map_err(e500)?.is_none() {
+        session.log_out();
+        return Ok(see_other("/login"));
+    }
+    complete_login(user_id, &session, &request, &pool).await.map_err(e500)?;
+    Ok(see_other("/admin/dashboard"))
+}
@@ -1,13 +1,23 @@
+mod email_events;
 mod health_check;
 mod home;
+mod invitations;
 mod login;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod tracking;
 mod admin;
 
+pub use email_events::*;
 pub use health_check::*;
 pub use home::*;
+pub use invitations::*;
 pub use login::*;
+pub use password_reset::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_unsubscribe::*;
+pub use tracking::*;
 pub use admin::*;
\ No newline at end of file
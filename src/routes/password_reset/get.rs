@@ -0,0 +1,53 @@
+use std::fmt::Write;
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+
+use crate::authentication::verify_reset_token;
+use crate::startup::HmacSecret;
+use crate::utils::{e404, e500};
+
+/// Ask for the username of the account to reset the password of.
+pub async fn password_reset_request_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(include_str!("request_form.html"), msg_html = msg_html))
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+/// Let the owner of a reset link pick a new password.
+#[tracing::instrument(
+    name = "Show the password reset form",
+    skip(params, pool, secret, flash_messages)
+)]
+pub async fn password_reset_form(
+    params: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    verify_reset_token(&params.token, &secret, &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("This link is invalid or has expired."))?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("reset_form.html"),
+            msg_html = msg_html,
+            token = htmlescape::encode_minimal(&params.token),
+        )))
+}
@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::authentication::{allow_reset_request, generate_reset_token, get_reset_recipient, reset_password, revoke_all_sessions, validate_new_password, verify_reset_token};
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::{client_ip, e404, e500, see_other};
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    username: String,
+}
+
+/// Email a reset link to the user, if they exist and have an email address.
+/// The response is the same either way, so it does not reveal which usernames exist:
+/// the lookup and the email happen in the background, so they do not show in the response time either.
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, pool, email_client, base_url, secret, request),
+    fields(username = %form.username)
+)]
+pub async fn request_password_reset(
+    form: web::Form<RequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    secret: web::Data<HmacSecret>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = form.0.username.trim().to_owned();
+    let ip = client_ip(&request);
+    if !allow_reset_request(&username, ip.as_deref(), &pool).await.map_err(e500)? {
+        FlashMessage::error("Too many password reset requests, try again later.").send();
+        return Ok(see_other("/password-reset"));
+    }
+    tokio::spawn(
+        send_reset_link(
+            username,
+            pool.into_inner(),
+            email_client.into_inner(),
+            base_url.into_inner(),
+            secret.into_inner(),
+        )
+        .instrument(tracing::Span::current()),
+    );
+    FlashMessage::info(
+        "If this account exists and has an email address, we have sent it a link to reset its password.",
+    )
+    .send();
+    Ok(see_other("/login"))
+}
+
+async fn send_reset_link(
+    username: String,
+    pool: Arc<PgPool>,
+    email_client: Arc<EmailClient>,
+    base_url: Arc<ApplicationBaseUrl>,
+    secret: Arc<HmacSecret>,
+) {
+    let recipient = match get_reset_recipient(&username, &pool).await {
+        Ok(Some(recipient)) => recipient,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                "Failed to find the recipient of a password reset link."
+            );
+            return;
+        }
+    };
+    let reset_link = format!(
+        "{}/password-reset/new?token={}",
+        base_url.0,
+        generate_reset_token(&recipient, &secret)
+    );
+    let html_body = format!(
+        "Someone asked to reset the password of your newsletter account.<br />\
+        Click <a href=\"{}\">here</a> to choose a new password within 30 minutes.<br />\
+        If it was not you, you can ignore this email.",
+        reset_link
+    );
+    let text_body = format!(
+        "Someone asked to reset the password of your newsletter account.\n\
+        Visit {} to choose a new password within 30 minutes.\n\
+        If it was not you, you can ignore this email.",
+        reset_link
+    );
+    let sent = match SubscriberEmail::parse(recipient.email) {
+        Ok(email) => email_client
+            .send_email(&email, "Reset your password", &html_body, &text_body)
+            .await
+            .map_err(anyhow::Error::from),
+        Err(e) => Err(anyhow::anyhow!(e)),
+    };
+    if let Err(e) = sent {
+        tracing::error!(
+            error.cause_chain = ?e,
+            "Failed to send a password reset link."
+        );
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+/// Set a new password from a reset link, logging the user out everywhere.
+#[tracing::instrument(
+    name = "Reset a forgotten password",
+    skip(form, pool, secret, hashing)
+)]
+pub async fn reset_forgotten_password(
+    form: web::Form<ResetFormData>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form = form.into_inner();
+    let target = verify_reset_token(&form.token, &secret, &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e404("This link is invalid or has expired."))?;
+    let form_url = format!("/password-reset/new?token={}", urlencoding::encode(&form.token));
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        FlashMessage::error("You entered two different new passwords - the field values must match.").send();
+        return Ok(see_other(&form_url));
+    }
+    if let Err(e) = validate_new_password(&form.new_password, &target.username) {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&form_url));
+    }
+    let user_id = target.user_id;
+    if !reset_password(target, form.new_password, &hashing, &pool).await.map_err(e500)? {
+        return Err(e404("This link is invalid or has expired."));
+    }
+    revoke_all_sessions(user_id, None, &pool).await.map_err(e500)?;
+    FlashMessage::info("Your password has been reset, you can now log in.").send();
+    Ok(see_other("/login"))
+}
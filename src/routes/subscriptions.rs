@@ -1,24 +1,77 @@
-use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use std::collections::HashMap;
+
+use actix_web::{http::StatusCode, web, Either, HttpResponse, ResponseError};
 use anyhow::Context;
-use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use sqlx::{PgPool, Postgres, Transaction, Executor};
 use uuid::Uuid;
 use chrono::Utc;
 
-use crate::{domain::{NewSubscriber, SubscriberEmail, SubscriberName}, email_client::EmailClient, startup::ApplicationBaseUrl};
+use crate::{domain::{CustomField, CustomFieldValue, NewSubscriber, SubscriberEmail, SubscriberName}, email_client::{EmailClient, SendEmailError}, startup::ApplicationBaseUrl};
+use crate::routes::admin::custom_fields::get_custom_fields;
+use crate::utils::generate_token;
+
 #[derive(serde::Deserialize)]
 pub struct FormData {
     email: String,
     name: String,
+    #[serde(flatten)]
+    custom_fields: HashMap<String, RawFieldValue>,
+}
+
+/// Custom field values as submitted: always strings in a form, but JSON
+/// clients may send numbers and booleans directly.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+pub enum RawFieldValue {
+    Text(String),
+    Number(f64),
+    Boolean(bool),
+}
+
+impl std::fmt::Display for RawFieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawFieldValue::Text(s) => s.fmt(f),
+            RawFieldValue::Number(n) => n.fmt(f),
+            RawFieldValue::Boolean(b) => b.fmt(f),
+        }
+    }
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+impl FormData {
+    pub fn parse(mut self, custom_fields: &[CustomField]) -> Result<NewSubscriber, String> {
+        let name = SubscriberName::parse(self.name)?;
+        let email = SubscriberEmail::parse(self.email)?;
 
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(NewSubscriber {email, name})
+        let mut errors = Vec::new();
+        let mut values = Vec::new();
+        for field in custom_fields {
+            let raw = self.custom_fields
+                .remove(&field.name)
+                .map(|v| v.to_string())
+                .filter(|v| !v.trim().is_empty());
+            match raw {
+                Some(raw) => match field.parse_value(&raw) {
+                    Ok(value) => values.push(CustomFieldValue {
+                        field_id: field.field_id,
+                        name: field.name.clone(),
+                        value,
+                    }),
+                    Err(e) => errors.push(e),
+                },
+                None if field.required => errors.push(format!("'{}' is required.", field.name)),
+                None => {}
+            }
+        }
+        let mut unknown_fields: Vec<_> = self.custom_fields.into_keys().collect();
+        unknown_fields.sort();
+        for name in unknown_fields {
+            errors.push(format!("'{}' is not a known field.", name));
+        }
+        if !errors.is_empty() {
+            return Err(errors.join(" "));
+        }
+        Ok(NewSubscriber {email, name, custom_fields: values})
     }
 }
 
@@ -27,18 +80,28 @@ impl TryFrom<FormData> for NewSubscriber {
     name = "Adding a new subscriber",
     skip(form, pool, email_client, base_url),
     fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name
+        subscriber_email = tracing::field::Empty,
+        subscriber_name = tracing::field::Empty
     )
 )]
 pub async fn subscribe(
-    form: web::Form<FormData>, 
+    form: Either<web::Json<FormData>, web::Form<FormData>>,
     pool: web::Data<PgPool>, 
     email_client: web::Data<EmailClient>, 
     base_url: web::Data<ApplicationBaseUrl>) -> Result<HttpResponse, SubscribeError> {
+    let form = match form {
+        Either::Left(json) => json.into_inner(),
+        Either::Right(form) => form.into_inner(),
+    };
+    tracing::Span::current()
+        .record("subscriber_email", tracing::field::display(&form.email))
+        .record("subscriber_name", tracing::field::display(&form.name));
 
-    let new_subscriber = form.0.try_into()
-        .map_err(|e| SubscribeError::ValidationError(e))?;
+    let custom_fields = get_custom_fields(&pool)
+        .await
+        .context("Failed to retrieve custom field definitions.")?;
+    let new_subscriber = form.parse(&custom_fields)
+        .map_err(SubscribeError::ValidationError)?;
 
     let mut transaction = pool
         .begin()
@@ -50,7 +113,7 @@ pub async fn subscribe(
         .await
         .context("Failed to insert new subscriber in the database")?;
     
-    let subscription_token = generate_subscriptions_token();
+    let subscription_token = generate_token();
     
     store_token(&mut transaction, &subscription_token, subscriber_id)
         .await
@@ -61,9 +124,11 @@ pub async fn subscribe(
         .await
         .context("Failed to commit SQL transaction to store a new subscriber.")?;
     
-    send_confirmation_email(&email_client, new_subscriber, &base_url.0, &subscription_token)
-        .await
-        .context("Failed to send confirmation email.")?;
+    match send_confirmation_email(&email_client, new_subscriber, &base_url.0, &subscription_token).await {
+        // Suppressed addresses are not told apart from others, they just never hear from us.
+        Err(SendEmailError::Suppressed(_)) => tracing::info!("Skipped the confirmation email of a suppressed address."),
+        outcome => outcome.context("Failed to send confirmation email.")?,
+    }
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -99,28 +164,36 @@ pub async fn insert_subscriber(new_subscriber: &NewSubscriber, transaction: &mut
     let subscriber_id = Uuid::new_v4();
     let query = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
         Utc::now(),
+        // Kept apart from the confirmation token, which is sent to whoever signed up.
+        generate_token(),
     );
     transaction
         .execute(query)
         .await?;
+    for field in &new_subscriber.custom_fields {
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO subscriber_custom_fields (subscriber_id, field_id, value)
+            VALUES ($1, $2, $3)
+            "#,
+            subscriber_id,
+            field.field_id,
+            field.value,
+        );
+        transaction
+            .execute(query)
+            .await?;
+    }
     Ok(subscriber_id)
 }
 
-fn generate_subscriptions_token() -> String {
-    let mut rng = thread_rng();
-    std::iter::repeat_with(|| rng.sample(Alphanumeric))
-        .map(char::from)
-        .take(25)
-        .collect()   
-}
-
 
 #[tracing::instrument(
     name = "Store subscription token",
@@ -174,7 +247,7 @@ async fn send_confirmation_email(
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), SendEmailError> {
     let confirmation_link = format!("{}/subscriptions/confirm?subscription_token={}", base_url, subscription_token);
     let text_body = format!(
         "Welcome to our newsletter!<br />\
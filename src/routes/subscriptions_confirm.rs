@@ -81,7 +81,8 @@ pub async fn get_subscriber_id(
 pub async fn confirm_subscription(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        UPDATE subscriptions SET status = 'confirmed' WHERE id = $1
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = $1 AND status NOT IN ('bounced', 'complained')
         "#,
         subscriber_id,
     )
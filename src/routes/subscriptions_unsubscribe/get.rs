@@ -0,0 +1,22 @@
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use sqlx::PgPool;
+
+use super::{get_subscriber_id_by_unsubscribe_token, UnsubscribeError, UnsubscribeParameters};
+
+/// Ask for confirmation rather than unsubscribing straight away,
+/// since link scanners and prefetchers follow the link in the email.
+#[tracing::instrument(
+    name = "Show the unsubscribe confirmation page",
+    skip(params, pool)
+)]
+pub async fn unsubscribe_form(
+    params: web::Query<UnsubscribeParameters>, pool: web::Data<PgPool>
+) -> Result<HttpResponse, UnsubscribeError> {
+    get_subscriber_id_by_unsubscribe_token(&params.unsubscribe_token, &pool).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            include_str!("unsubscribe_form.html"),
+            unsubscribe_token = htmlescape::encode_minimal(&params.unsubscribe_token),
+        )))
+}
@@ -0,0 +1,58 @@
+mod get;
+mod post;
+
+pub use get::unsubscribe_form;
+pub use post::unsubscribe;
+
+use actix_web::{http::StatusCode, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::routes::error_chain_fmt;
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("There is no subscriber associated with the provided token.")]
+    UnknownToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error)
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UnsubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            UnsubscribeError::UnknownToken => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct UnsubscribeParameters {
+    unsubscribe_token: String,
+}
+
+#[tracing::instrument(
+    name = "Get subscriber id from an unsubscribe token",
+    skip(unsubscribe_token, pool)
+)]
+async fn get_subscriber_id_by_unsubscribe_token(
+    unsubscribe_token: &str,
+    pool: &PgPool,
+) -> Result<Uuid, UnsubscribeError> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE unsubscribe_token = $1"#,
+        unsubscribe_token,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber id associated with the token.")?;
+    row.map(|r| r.id).ok_or(UnsubscribeError::UnknownToken)
+}
@@ -0,0 +1,36 @@
+use anyhow::Context;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{get_subscriber_id_by_unsubscribe_token, UnsubscribeError, UnsubscribeParameters};
+
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(form, pool)
+)]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeParameters>, pool: web::Data<PgPool>
+) -> Result<HttpResponse, UnsubscribeError> {
+    let id = get_subscriber_id_by_unsubscribe_token(&form.unsubscribe_token, &pool).await?;
+    mark_as_unsubscribed(&pool, id)
+        .await
+        .context("Failed to update database as unsubscribed.")?;
+    Ok(HttpResponse::Ok().body("You have been unsubscribed."))
+}
+
+#[tracing::instrument(
+    name = "Mark subscriber as unsubscribed",
+    skip(pool),
+)]
+async fn mark_as_unsubscribed(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1
+        "#,
+        subscriber_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
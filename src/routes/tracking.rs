@@ -0,0 +1,188 @@
+use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+use crate::startup::HmacSecret;
+use crate::utils::{e404, e500};
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+/// The longest user agent stored with an open or a click, matching the column limit.
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+/// The request's user agent, truncated to fit the tracking tables.
+fn user_agent(request: &HttpRequest) -> Option<&str> {
+    let user_agent = request.headers().get("User-Agent")?.to_str().ok()?;
+    // `to_str` only accepts visible ASCII, so every byte is a character boundary.
+    Some(&user_agent[..user_agent.len().min(MAX_USER_AGENT_LENGTH)])
+}
+
+/// Restrict a query over `subscriptions s` to subscribers who opened or clicked an issue
+/// in the last `days` days.
+pub fn push_engagement_condition(query: &mut QueryBuilder<'_, Postgres>, days: i32) {
+    query
+        .push(
+            " AND (EXISTS (SELECT 1 FROM issue_opens o \
+            WHERE o.subscriber_id = s.id AND o.opened_at >= now() - make_interval(days => ",
+        )
+        .push_bind(days)
+        .push(
+            ")) OR EXISTS (SELECT 1 FROM issue_clicks c \
+            WHERE c.subscriber_id = s.id AND c.clicked_at >= now() - make_interval(days => ",
+        )
+        .push_bind(days)
+        .push(")))");
+}
+
+/// Record that a recipient opened an issue and serve the tracking pixel.
+/// Unknown tokens get the pixel as well, so the endpoint reveals nothing.
+#[tracing::instrument(
+    name = "Track a newsletter open",
+    skip(token, request, pool)
+)]
+pub async fn track_open(
+    token: web::Path<String>,
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_agent = user_agent(&request);
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_opens (newsletter_issue_id, subscriber_id, opened_at, user_agent)
+        SELECT t.newsletter_issue_id, t.subscriber_id, now(), $2
+        FROM issue_delivery_tasks t
+        JOIN newsletter_issues i ON i.newsletter_issue_id = t.newsletter_issue_id
+        WHERE t.tracking_token = $1 AND i.track_opens
+        "#,
+        token.as_str(),
+        user_agent,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to record a newsletter open.")
+    .map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(PIXEL))
+}
+
+/// The redirect URL recording a click of `recipient_token` on `url`.
+/// The target is signed, so the endpoint cannot be used to redirect to arbitrary sites.
+pub fn click_tracking_url(base_url: &str, recipient_token: &str, url: &str, secret: &HmacSecret) -> String {
+    let payload = format!("{}.{}", recipient_token, URL_SAFE_NO_PAD.encode(url));
+    let tag = click_mac(&payload, secret).finalize().into_bytes();
+    format!("{}/r/{}.{}", base_url, payload, hex::encode(tag))
+}
+
+/// The recipient token and target URL of a click token, if its signature is valid.
+fn verify_click_token(token: &str, secret: &HmacSecret) -> Option<(String, String)> {
+    let (payload, tag) = token.rsplit_once('.')?;
+    click_mac(payload, secret).verify_slice(&hex::decode(tag).ok()?).ok()?;
+    let (recipient_token, url) = payload.split_once('.')?;
+    let url = String::from_utf8(URL_SAFE_NO_PAD.decode(url).ok()?).ok()?;
+    Some((recipient_token.to_string(), url))
+}
+
+fn click_mac(payload: &str, secret: &HmacSecret) -> Hmac<sha2::Sha256> {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.0.expose_secret().as_bytes()).unwrap();
+    mac.update(b"click.");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Record that a recipient clicked a link of an issue and redirect them to it.
+#[tracing::instrument(
+    name = "Track a newsletter click",
+    skip(token, request, pool, secret)
+)]
+pub async fn track_click(
+    token: web::Path<String>,
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (recipient_token, url) = verify_click_token(&token, &secret)
+        .ok_or_else(|| e404("There is no link with the provided token."))?;
+    let user_agent = user_agent(&request);
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_clicks (newsletter_issue_id, subscriber_id, url, clicked_at, user_agent)
+        SELECT newsletter_issue_id, subscriber_id, $2, now(), $3
+        FROM issue_delivery_tasks
+        WHERE tracking_token = $1
+        "#,
+        recipient_token,
+        url,
+        user_agent,
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to record a newsletter click.")
+    .map_err(e500)?;
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use hmac::{Hmac, Mac};
+    use secrecy::Secret;
+
+    use super::{click_tracking_url, verify_click_token};
+    use crate::startup::HmacSecret;
+
+    fn secret(value: &str) -> HmacSecret {
+        HmacSecret(Secret::new(value.to_string()))
+    }
+
+    fn token(url: &str) -> &str {
+        url.rsplit('/').next().unwrap()
+    }
+
+    #[test]
+    fn a_signed_click_token_is_verified() {
+        let url = click_tracking_url("https://app.com", "abc", "https://example.com/?a=1", &secret("s"));
+        assert_eq!(
+            verify_click_token(token(&url), &secret("s")),
+            Some(("abc".to_string(), "https://example.com/?a=1".to_string()))
+        );
+    }
+
+    #[test]
+    fn a_click_token_signed_with_another_secret_is_rejected() {
+        let url = click_tracking_url("https://app.com", "abc", "https://example.com", &secret("other"));
+        assert_eq!(verify_click_token(token(&url), &secret("s")), None);
+    }
+
+    #[test]
+    fn a_click_token_with_a_tampered_target_is_rejected() {
+        let url = click_tracking_url("https://app.com", "abc", "https://example.com", &secret("s"));
+        let (_, tag) = token(&url).rsplit_once('.').unwrap();
+        let forged = format!("abc.{}.{}", URL_SAFE_NO_PAD.encode("https://evil.com"), tag);
+        assert_eq!(verify_click_token(&forged, &secret("s")), None);
+    }
+
+    #[test]
+    fn a_mac_of_the_same_payload_without_the_click_prefix_is_rejected() {
+        let payload = format!("abc.{}", URL_SAFE_NO_PAD.encode("https://evil.com"));
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"s").unwrap();
+        mac.update(payload.as_bytes());
+        let forged = format!("{}.{}", payload, hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(verify_click_token(&forged, &secret("s")), None);
+    }
+}
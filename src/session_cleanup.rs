@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use crate::authentication::delete_stale_sessions;
+use crate::configuration::{SessionSettings, Settings};
+use crate::startup::get_connection_pool;
+
+/// How often revoked and expired sessions are deleted from the index.
+const CLEANUP_INTERVAL_SECONDS: u64 = 60 * 60;
+
+pub async fn run_session_cleanup_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    cleanup_loop(connection_pool, configuration.session).await
+}
+
+async fn cleanup_loop(pool: PgPool, settings: SessionSettings) -> Result<(), anyhow::Error> {
+    loop {
+        match delete_stale_sessions(&settings, &pool).await {
+            Ok(deleted) => tracing::info!(deleted, "Deleted stale user sessions."),
+            Err(e) => tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to delete stale user sessions."
+            ),
+        }
+        tokio::time::sleep(Duration::from_secs(CLEANUP_INTERVAL_SECONDS)).await;
+    }
+}
@@ -2,12 +2,19 @@ use std::future::{ready, Ready};
 
 use actix_session::{Session, SessionGetError, SessionInsertError, SessionExt};
 use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use chrono::{DateTime, Duration, Utc};
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const LAST_SEEN_AT_KEY: &'static str = "last_seen_at";
+    /// Set once the password is checked, while the second factor is still to be checked.
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const PENDING_SINCE_KEY: &'static str = "pending_since";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -21,11 +28,66 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    /// Remember the id under which the session is indexed, to list and revoke it.
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    /// Timestamps are stored as seconds since the epoch.
+    pub fn insert_logged_in_at(&self, logged_in_at: DateTime<Utc>) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LOGGED_IN_AT_KEY, logged_in_at.timestamp())
+    }
+
+    pub fn get_logged_in_at(&self) -> Result<Option<DateTime<Utc>>, SessionGetError> {
+        Ok(self.0.get::<i64>(Self::LOGGED_IN_AT_KEY)?.and_then(|t| DateTime::from_timestamp(t, 0)))
+    }
+
+    pub fn insert_last_seen_at(&self, last_seen_at: DateTime<Utc>) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LAST_SEEN_AT_KEY, last_seen_at.timestamp())
+    }
+
+    pub fn get_last_seen_at(&self) -> Result<Option<DateTime<Utc>>, SessionGetError> {
+        Ok(self.0.get::<i64>(Self::LAST_SEEN_AT_KEY)?.and_then(|t| DateTime::from_timestamp(t, 0)))
+    }
+
+    pub fn insert_pending_user_id(&self, user_id: Uuid, now: DateTime<Utc>) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)?;
+        self.0.insert(Self::PENDING_SINCE_KEY, now.timestamp())
+    }
+
+    /// The user whose password was checked, unless that was too long ago to still finish the login.
+    pub fn get_pending_user_id(&self, now: DateTime<Utc>) -> Result<Option<Uuid>, SessionGetError> {
+        let pending_since = self.0.get::<i64>(Self::PENDING_SINCE_KEY)?.and_then(|t| DateTime::from_timestamp(t, 0));
+        if is_pending_login_expired(pending_since, now) {
+            return Ok(None);
+        }
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::PENDING_SINCE_KEY);
+    }
+
     pub fn log_out(&self) {
         self.0.purge()
     }
 }
 
+/// How long the second factor can be entered once the password is checked.
+const PENDING_LOGIN_TTL_MINUTES: i64 = 5;
+
+fn is_pending_login_expired(pending_since: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    match pending_since {
+        Some(pending_since) => now - pending_since >= Duration::minutes(PENDING_LOGIN_TTL_MINUTES),
+        None => true,
+    }
+}
+
 impl FromRequest for TypedSession {
     type Error = <Session as FromRequest>::Error;
     type Future = Ready<Result<TypedSession, Self::Error>>;
@@ -33,4 +95,24 @@ impl FromRequest for TypedSession {
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
         ready(Ok(TypedSession(req.get_session())))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::is_pending_login_expired;
+
+    #[test]
+    fn a_recent_pending_login_can_be_finished() {
+        let now = Utc::now();
+        assert!(!is_pending_login_expired(Some(now - Duration::minutes(4)), now));
+    }
+
+    #[test]
+    fn a_stale_or_undated_pending_login_has_expired() {
+        let now = Utc::now();
+        assert!(is_pending_login_expired(Some(now - Duration::minutes(5)), now));
+        assert!(is_pending_login_expired(None, now));
+    }
+}
@@ -1,4 +1,4 @@
-use std::net::TcpListener;
+use std::net::{IpAddr, TcpListener};
 
 use actix_session::storage::RedisSessionStore;
 use actix_web::cookie::Key;
@@ -13,9 +13,9 @@ use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use tracing_actix_web::TracingLogger;
 
-use crate::authentication::reject_anonymous_users;
+use crate::authentication::{authorize_by_role, reject_anonymous_users, reject_forged_requests, DummyPasswordHash};
 use crate::email_client::EmailClient;
-use crate::routes::{admin_dashboard, change_password, change_password_form, confirm, health_check, home, log_out, login, login_form, publish_newsletter, send_newsletter_form, subscribe};
+use crate::routes::{accept_invitation, accept_invitation_form, activate_user, add_subscriber_tag, add_subscriber_to_list, admin_dashboard, cancel_delivery, change_password, change_user_role, change_password_form, confirm, confirm_totp, create_custom_field, create_segment, create_suppression, deactivate_user, delete_custom_field, delete_draft, delete_segment, delete_suppression, delete_user, edit_newsletter_form, enroll_totp, health_check, home, ingest_email_event, invite_user, issue_failures_csv, issue_report, list_custom_fields, list_issues, list_segments, list_sessions, list_subscribers, list_suppressions, list_users, log_out, login, login_form, login_totp, login_totp_form, password_reset_form, password_reset_request_form, pause_delivery, preview_newsletter, publish_newsletter, remove_subscriber_from_list, remove_subscriber_tag, request_password_reset, reschedule_issue, reset_forgotten_password, resume_delivery, revoke_all_user_sessions, revoke_user_session, save_draft, schedule_newsletter, send_newsletter_form, send_test_newsletter, subscribe, totp_form, track_click, track_open, turn_off_totp, unschedule_issue, unsubscribe, unsubscribe_form};
 use crate::configuration::{DatabaseSettings, Settings};
 
 pub struct ApplicationBaseUrl(pub String);
@@ -23,23 +23,31 @@ pub struct ApplicationBaseUrl(pub String);
 #[derive(Clone)]
 pub struct HmacSecret(pub Secret<String>);
 
+pub struct WebhookSecret(pub Secret<String>);
+
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
 async fn run(
     listener: TcpListener, 
     db_pool: PgPool, 
     email_client: EmailClient, 
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_uri: Secret<String>,
-
+    configuration: Settings,
 ) -> Result<Server, anyhow::Error> {
-    let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
+    let application = configuration.application;
+    let secret_key = Key::from(application.hmac_secret.expose_secret().as_bytes());
     let storage_backend = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(storage_backend).build();
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_store = RedisSessionStore::new(configuration.redis_uri.expose_secret()).await?;
     let db_pool = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
-    let base_url = web::Data::new(ApplicationBaseUrl(base_url));
-    let hmac_secret = web::Data::new(HmacSecret(hmac_secret));
+    let base_url = web::Data::new(ApplicationBaseUrl(application.base_url));
+    let hmac_secret = web::Data::new(HmacSecret(application.hmac_secret));
+    let webhook_secret = web::Data::new(WebhookSecret(application.webhook_secret));
+    let trusted_proxies = web::Data::new(TrustedProxies(application.trusted_proxies));
+    let totp_settings = web::Data::new(configuration.totp);
+    let dummy_password_hash = web::Data::new(DummyPasswordHash::compute(&configuration.password_hashing)?);
+    let password_hashing = web::Data::new(configuration.password_hashing);
+    let session_settings = web::Data::new(configuration.session);
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
@@ -48,24 +56,88 @@ async fn run(
             .route("/health_check", web::get().to(health_check))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route("/subscriptions/unsubscribe", web::get().to(unsubscribe_form))
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route("/o/{token}", web::get().to(track_open))
+            .route("/r/{token}", web::get().to(track_click))
+            .route("/webhooks/email-events", web::post().to(ingest_email_event))
             .route("/", web::get().to(home))
             .route("/login", web::get().to(login_form))
             .route("/login", web::post().to(login))
+            .route("/login/totp", web::get().to(login_totp_form))
+            .route("/login/totp", web::post().to(login_totp))
+            .route("/password-reset", web::get().to(password_reset_request_form))
+            .route("/password-reset", web::post().to(request_password_reset))
+            .route("/password-reset/new", web::get().to(password_reset_form))
+            .route("/password-reset/new", web::post().to(reset_forgotten_password))
+            .route("/invitations/accept", web::get().to(accept_invitation_form))
+            .route("/invitations/accept", web::post().to(accept_invitation))
             .service(
                 web::scope("/admin")
+                // The last middleware registered runs first: users are authenticated,
+                // then authorized for the route, before their forms are checked.
+                .wrap(from_fn(reject_forged_requests))
+                .wrap(from_fn(authorize_by_role))
                 .wrap(from_fn(reject_anonymous_users))
                 .route("/dashboard", web::get().to(admin_dashboard))
                 .route("/password", web::get().to(change_password_form))
                 .route("/password", web::post().to(change_password))
                 .route("/logout", web::post().to(log_out))
+                .route("/sessions", web::get().to(list_sessions))
+                .route("/sessions/revoke", web::post().to(revoke_user_session))
+                .route("/sessions/revoke-all", web::post().to(revoke_all_user_sessions))
+                .route("/totp", web::get().to(totp_form))
+                .route("/totp/enroll", web::post().to(enroll_totp))
+                .route("/totp/confirm", web::post().to(confirm_totp))
+                .route("/totp/disable", web::post().to(turn_off_totp))
                 .route("/newsletters", web::post().to(publish_newsletter))
                 .route("/newsletters", web::get().to(send_newsletter_form))
+                .route("/newsletters/issues", web::get().to(list_issues))
+                .route("/newsletters/issues", web::post().to(save_draft))
+                .route("/newsletters/issues/delete", web::post().to(delete_draft))
+                .route("/newsletters/issues/test", web::post().to(send_test_newsletter))
+                .route("/newsletters/issues/schedule", web::post().to(schedule_newsletter))
+                .route("/newsletters/issues/reschedule", web::post().to(reschedule_issue))
+                .route("/newsletters/issues/unschedule", web::post().to(unschedule_issue))
+                .route("/newsletters/issues/pause", web::post().to(pause_delivery))
+                .route("/newsletters/issues/resume", web::post().to(resume_delivery))
+                .route("/newsletters/issues/cancel", web::post().to(cancel_delivery))
+                .route("/newsletters/issues/{issue_id}", web::get().to(issue_report))
+                .route("/newsletters/issues/{issue_id}/edit", web::get().to(edit_newsletter_form))
+                .route("/newsletters/issues/{issue_id}/preview", web::get().to(preview_newsletter))
+                .route("/newsletters/issues/{issue_id}/failures.csv", web::get().to(issue_failures_csv))
+                .route("/subscribers", web::get().to(list_subscribers))
+                .route("/subscribers/tags", web::post().to(add_subscriber_tag))
+                .route("/subscribers/tags/delete", web::post().to(remove_subscriber_tag))
+                .route("/subscribers/lists", web::post().to(add_subscriber_to_list))
+                .route("/subscribers/lists/delete", web::post().to(remove_subscriber_from_list))
+                .route("/suppressions", web::get().to(list_suppressions))
+                .route("/suppressions", web::post().to(create_suppression))
+                .route("/suppressions/delete", web::post().to(delete_suppression))
+                .route("/users", web::get().to(list_users))
+                .route("/users/invitations", web::post().to(invite_user))
+                .route("/users/deactivate", web::post().to(deactivate_user))
+                .route("/users/activate", web::post().to(activate_user))
+                .route("/users/delete", web::post().to(delete_user))
+                .route("/users/role", web::post().to(change_user_role))
+                .route("/segments", web::get().to(list_segments))
+                .route("/segments", web::post().to(create_segment))
+                .route("/segments/delete", web::post().to(delete_segment))
+                .route("/fields", web::get().to(list_custom_fields))
+                .route("/fields", web::post().to(create_custom_field))
+                .route("/fields/delete", web::post().to(delete_custom_field))
             )
 
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
             .app_data(hmac_secret.clone())
+            .app_data(webhook_secret.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(totp_settings.clone())
+            .app_data(password_hashing.clone())
+            .app_data(dummy_password_hash.clone())
+            .app_data(session_settings.clone())
     })
     .listen(listener)?
     .run();
@@ -89,25 +161,13 @@ impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
 
-        let sender_email = configuration.email_client.sender().expect("invalid sender email address.");
-        let timeout = configuration.email_client.timeout();
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url, 
-            sender_email, 
-            configuration.email_client.authorization_token, 
-            timeout);
+        let email_client = configuration.email_client.clone().client(connection_pool.clone());
 
         let address = format!("{}:{}", configuration.application.host, configuration.application.port);
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
         
-        let server = run(listener,
-            connection_pool, 
-            email_client, 
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
-            ).await?;
+        let server = run(listener, connection_pool, email_client, configuration).await?;
         Ok(Self { server, port })
     }
 
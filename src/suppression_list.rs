@@ -0,0 +1,166 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+/// Why an address must not be emailed anymore.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SuppressionReason {
+    Bounced,
+    Complained,
+    Blocked,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::Bounced => "bounced",
+            SuppressionReason::Complained => "complained",
+            SuppressionReason::Blocked => "blocked",
+        }
+    }
+}
+
+impl TryFrom<String> for SuppressionReason {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "bounced" => Ok(SuppressionReason::Bounced),
+            "complained" => Ok(SuppressionReason::Complained),
+            "blocked" => Ok(SuppressionReason::Blocked),
+            other => Err(format!("{} is not a known suppression reason.", other)),
+        }
+    }
+}
+
+/// An address that never receives mail, whichever list it subscribes to.
+/// Addresses are stored lowercased.
+pub struct Suppression {
+    pub email: String,
+    pub reason: SuppressionReason,
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where the email client looks up the addresses it must not send to.
+#[derive(Clone, Debug)]
+pub enum SuppressionList {
+    /// The `suppressions` table.
+    Postgres(PgPool),
+    /// Suppresses nothing, for tests of the email client that do not involve the list.
+    #[cfg(test)]
+    Empty,
+}
+
+impl SuppressionList {
+    pub async fn contains(&self, email: &str) -> Result<bool, sqlx::Error> {
+        match self {
+            Self::Postgres(pool) => is_suppressed(email, pool).await,
+            #[cfg(test)]
+            Self::Empty => Ok(false),
+        }
+    }
+}
+
+#[tracing::instrument(
+    name="Check the suppression list.",
+    skip(pool)
+)]
+pub async fn is_suppressed(email: &str, pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT email FROM suppressions WHERE email = lower($1)"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.is_some())
+}
+
+/// Leave suppressed addresses out of a query over `subscriptions s`, so that recipient counts
+/// match what the email client will actually send.
+pub fn push_not_suppressed_condition(query: &mut QueryBuilder<'_, Postgres>) {
+    query.push(" AND NOT EXISTS (SELECT 1 FROM suppressions x WHERE x.email = lower(s.email))");
+}
+
+/// Add `email` to the suppression list. Returns `false` when it was already suppressed.
+#[tracing::instrument(
+    name="Add an address to the suppression list.",
+    skip(pool)
+)]
+pub async fn add_suppression(
+    email: &str,
+    reason: SuppressionReason,
+    source: &str,
+    pool: &PgPool,
+) -> anyhow::Result<bool> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, source, created_at)
+        VALUES (lower($1), $2, $3, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        email,
+        reason.as_str(),
+        source,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert suppression.")?
+    .rows_affected();
+    Ok(inserted == 1)
+}
+
+/// Remove `email` from the suppression list. Returns `false` when it was not suppressed.
+/// Subscribers that were `bounced` or `complained` because of it are `confirmed` again,
+/// so they receive the next issues.
+#[tracing::instrument(
+    name="Remove an address from the suppression list.",
+    skip(pool)
+)]
+pub async fn remove_suppression(email: &str, pool: &PgPool) -> anyhow::Result<bool> {
+    let mut transaction = pool.begin().await.context("Failed to start a transaction.")?;
+    let deleted = sqlx::query!(r#"DELETE FROM suppressions WHERE email = lower($1)"#, email)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete suppression.")?
+        .rows_affected();
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE lower(email) = lower($1) AND status IN ('bounced', 'complained')
+        "#,
+        email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to restore the status of a subscriber.")?;
+    transaction.commit().await.context("Failed to remove suppression.")?;
+    Ok(deleted == 1)
+}
+
+#[tracing::instrument(
+    name="Get suppressions.",
+    skip(pool)
+)]
+pub async fn get_suppressions(pool: &PgPool) -> anyhow::Result<Vec<Suppression>> {
+    sqlx::query!(
+        r#"
+        SELECT email, reason, source, created_at
+        FROM suppressions
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve suppressions.")?
+    .into_iter()
+    .map(|r| {
+        Ok(Suppression {
+            email: r.email,
+            reason: r.reason.try_into().map_err(anyhow::Error::msg)?,
+            source: r.source,
+            created_at: r.created_at,
+        })
+    })
+    .collect()
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("A `{{{{` opened at character {0} is never closed.")]
+    UnclosedTag(usize),
+    #[error("`{{{{ {0} }}}}` is not a valid placeholder.")]
+    InvalidTag(String),
+    #[error("`{0}` is not a known variable.")]
+    UnknownVariable(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Variable { name: String, default: Option<String> },
+}
+
+/// A newsletter body with `{{ variable }}` or `{{ variable | default: "fallback" }}`
+/// placeholders, rendered once per recipient.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+            let after_open = &rest[start + 2..];
+            let end = after_open
+                .find("}}")
+                .ok_or_else(|| TemplateError::UnclosedTag(source.len() - rest.len() + start))?;
+            parts.push(parse_tag(&after_open[..end])?);
+            rest = &after_open[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Fail if the template references a variable outside of `known`.
+    pub fn check_variables(&self, known: &[&str]) -> Result<(), TemplateError> {
+        for name in self.variables() {
+            if !known.contains(&name) {
+                return Err(TemplateError::UnknownVariable(name.to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|p| match p {
+            Part::Variable { name, .. } => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Substitute variables from `context`, falling back to the placeholder
+    /// default (or an empty string) when a value is missing or empty.
+    /// Values are html-escaped when `escape_html` is set.
+    pub fn render(&self, context: &HashMap<String, String>, escape_html: bool) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(s) => output.push_str(s),
+                Part::Variable { name, default } => {
+                    let value = context
+                        .get(name)
+                        .filter(|v| !v.is_empty())
+                        .or(default.as_ref())
+                        .map(String::as_str)
+                        .unwrap_or_default();
+                    if escape_html {
+                        output.push_str(&htmlescape::encode_minimal(value));
+                    } else {
+                        output.push_str(value);
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+/// The subject and both bodies of a newsletter issue.
+#[derive(Debug, Clone)]
+pub struct NewsletterTemplate {
+    pub title: Template,
+    pub html: Template,
+    pub text: Template,
+}
+
+pub struct RenderedNewsletter {
+    pub title: String,
+    pub html: String,
+    pub text: String,
+}
+
+impl NewsletterTemplate {
+    pub const BUILT_IN_VARIABLES: [&'static str; 3] = ["name", "email", "unsubscribe_url"];
+
+    /// Parse all three parts, only allowing built-in variables and `custom_fields`.
+    pub fn parse(title: &str, html: &str, text: &str, custom_fields: &[&str]) -> Result<Self, TemplateError> {
+        let known: Vec<&str> = Self::BUILT_IN_VARIABLES
+            .iter()
+            .copied()
+            .chain(custom_fields.iter().copied())
+            .collect();
+        let template = Self {
+            title: Template::parse(title)?,
+            html: Template::parse(html)?,
+            text: Template::parse(text)?,
+        };
+        template.title.check_variables(&known)?;
+        template.html.check_variables(&known)?;
+        template.text.check_variables(&known)?;
+        Ok(template)
+    }
+
+    pub fn render(&self, context: &HashMap<String, String>) -> RenderedNewsletter {
+        RenderedNewsletter {
+            title: self.title.render(context, false),
+            html: self.html.render(context, true),
+            text: self.text.render(context, false),
+        }
+    }
+}
+
+fn parse_tag(tag: &str) -> Result<Part, TemplateError> {
+    let invalid = || TemplateError::InvalidTag(tag.trim().to_owned());
+    let (name, filter) = match tag.split_once('|') {
+        Some((name, filter)) => (name.trim(), Some(filter.trim())),
+        None => (tag.trim(), None),
+    };
+    let is_identifier = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !is_identifier {
+        return Err(invalid());
+    }
+    let default = match filter {
+        None => None,
+        Some(filter) => {
+            let argument = filter
+                .strip_prefix("default")
+                .and_then(|f| f.trim_start().strip_prefix(':'))
+                .map(str::trim)
+                .ok_or_else(invalid)?;
+            let quoted = argument.len() >= 2
+                && ((argument.starts_with('"') && argument.ends_with('"'))
+                    || (argument.starts_with('\'') && argument.ends_with('\'')));
+            if !quoted {
+                return Err(invalid());
+            }
+            Some(argument[1..argument.len() - 1].to_owned())
+        }
+    };
+    Ok(Part::Variable { name: name.to_owned(), default })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Template, TemplateError};
+    use claims::{assert_err, assert_ok};
+    use std::collections::HashMap;
+
+    fn context() -> HashMap<String, String> {
+        HashMap::from([
+            ("name".to_string(), "<Ursula>".to_string()),
+            ("company".to_string(), "".to_string()),
+        ])
+    }
+
+    #[test]
+    fn variables_are_substituted() {
+        let template = Template::parse("Hello {{name}}, bye {{ name }}!").unwrap();
+        assert_eq!(template.render(&context(), false), "Hello <Ursula>, bye <Ursula>!");
+    }
+
+    #[test]
+    fn values_are_escaped_in_html() {
+        let template = Template::parse("<p>Hello {{ name }}</p>").unwrap();
+        assert_eq!(template.render(&context(), true), "<p>Hello &lt;Ursula&gt;</p>");
+    }
+
+    #[test]
+    fn defaults_are_used_for_missing_or_empty_values() {
+        let template = Template::parse(r#"{{ company | default: "your team" }} / {{ city | default: 'nowhere' }}"#).unwrap();
+        assert_eq!(template.render(&context(), false), "your team / nowhere");
+    }
+
+    #[test]
+    fn unclosed_tags_are_rejected() {
+        assert_eq!(Template::parse("Hi {{ name").unwrap_err(), TemplateError::UnclosedTag(3));
+    }
+
+    #[test]
+    fn invalid_tags_are_rejected() {
+        assert_err!(Template::parse("{{ }}"));
+        assert_err!(Template::parse("{{ first name }}"));
+        assert_err!(Template::parse("{{ name | upper }}"));
+        assert_err!(Template::parse("{{ name | default: unquoted }}"));
+    }
+
+    #[test]
+    fn unknown_variables_are_reported() {
+        let template = Template::parse("{{ name }} {{ shoe_size }}").unwrap();
+        assert_ok!(template.check_variables(&["name", "shoe_size"]));
+        assert_eq!(
+            template.check_variables(&["name"]).unwrap_err(),
+            TemplateError::UnknownVariable("shoe_size".into())
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use actix_web::{http::header::LOCATION, HttpResponse};
+use actix_web::{http::header::LOCATION, web, HttpRequest, HttpResponse};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use crate::startup::TrustedProxies;
 
 pub fn e500<T>(e: T) -> actix_web::Error 
 where 
@@ -14,8 +17,45 @@ where
     actix_web::error::ErrorBadRequest(e)
 }
 
+pub fn e404<T>(e: T) -> actix_web::Error 
+where 
+    T: std::fmt::Debug + std::fmt::Display + 'static
+{
+    actix_web::error::ErrorNotFound(e)
+}
+
+pub fn e403<T>(e: T) -> actix_web::Error 
+where 
+    T: std::fmt::Debug + std::fmt::Display + 'static
+{
+    actix_web::error::ErrorForbidden(e)
+}
+
+/// The IP address of the client: the peer, unless it is one of the trusted proxies,
+/// in which case the address it forwarded. Forwarding headers from anyone else could be forged.
+pub fn client_ip(request: &HttpRequest) -> Option<String> {
+    let peer = request.peer_addr()?.ip();
+    let trusted = request
+        .app_data::<web::Data<TrustedProxies>>()
+        .is_some_and(|proxies| proxies.0.contains(&peer));
+    if trusted {
+        request.connection_info().realip_remote_addr().map(str::to_owned)
+    } else {
+        Some(peer.to_string())
+    }
+}
+
 pub fn see_other(location: &str) -> HttpResponse {
     HttpResponse::SeeOther()
                 .insert_header((LOCATION, location))
                 .finish()
-}
\ No newline at end of file
+}
+
+/// A random alphanumeric token, used for subscription, unsubscribe, tracking and invitation links.
+pub fn generate_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
+}
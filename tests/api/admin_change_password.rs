@@ -173,4 +173,50 @@ async fn changing_password_works() {
     let response = test_app.post_login(&login_body).await;
 
     assert_is_redirect_to(&response, "/admin/dashboard");
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn breached_passwords_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    let body = serde_json::json!(
+        {
+            "current_password": &test_app.test_user.password,
+            "new_password": "password1234",
+            "new_password_check": "password1234",
+        }
+    );
+    let response = test_app.post_change_password(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = test_app.get_change_password_html().await;
+    assert!(html_page.contains(
+        "<p><i>This password has appeared in a data breach or is too common, please choose another one.</i></p>"));
+}
+
+#[tokio::test]
+async fn passwords_containing_the_username_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let new_password = format!("{}-{}", test_app.test_user.username, Uuid::new_v4());
+    test_app.valid_login().await;
+
+    // Act
+    let body = serde_json::json!(
+        {
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }
+    );
+    let response = test_app.post_change_password(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = test_app.get_change_password_html().await;
+    assert!(html_page.contains("<p><i>The new password should not contain your username.</i></p>"));
+}
@@ -0,0 +1,138 @@
+use uuid::Uuid;
+use zero2prod::authentication::csrf_token;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn post_logout_with(test_app: &TestApp, body: &serde_json::Value) -> reqwest::Response {
+    test_app
+        .api_client
+        .post(format!("{}/admin/logout", &test_app.address))
+        .form(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn admin_forms_carry_the_csrf_token_of_the_session() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let csrf_token = test_app.csrf_token().await.unwrap();
+
+    // Act
+    let html_page = test_app.get_admin_dashboard_html().await;
+
+    // Assert
+    assert!(html_page.contains(&format!("name=\"csrf_token\" value=\"{}\"", csrf_token)));
+}
+
+#[tokio::test]
+async fn a_form_without_a_csrf_token_is_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = post_logout_with(&test_app, &serde_json::json!({})).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_form_with_the_csrf_token_of_another_session_is_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let forged_token = csrf_token(Uuid::new_v4(), &test_app.hmac_secret);
+
+    // Act
+    let response = post_logout_with(
+        &test_app,
+        &serde_json::json!({ "csrf_token": forged_token.as_ref() }),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_form_with_the_csrf_token_of_the_session_is_accepted() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let csrf_token = test_app.csrf_token().await.unwrap();
+
+    // Act
+    let response = post_logout_with(&test_app, &serde_json::json!({ "csrf_token": csrf_token })).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_password_change_without_a_csrf_token_is_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    let response = test_app
+        .api_client
+        .post(format!("{}/admin/password", &test_app.address))
+        .form(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    test_app.post_logout().await;
+    let response = test_app.valid_login().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_form_with_a_valid_csrf_token_reaches_the_handler_with_its_fields() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let csrf_token = test_app.csrf_token().await.unwrap();
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    let response = test_app
+        .api_client
+        .post(format!("{}/admin/password", &test_app.address))
+        .form(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+            "csrf_token": csrf_token,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = test_app.get_change_password_html().await;
+    assert!(html_page.contains("Your password has been changed"));
+    test_app.post_logout().await;
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -0,0 +1,244 @@
+use wiremock::{matchers::{body_partial_json, method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+#[tokio::test]
+async fn paused_issues_are_not_delivered_until_resumed() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula@mail.com").await;
+    test_app.create_confirmed_subscriber("octavia@mail.com").await;
+    test_app.post_newsletter(&newsletter_body()).await;
+    let issue_id = issue_id(&test_app).await;
+
+    // Act - Part 1 - Pause the delivery
+    let guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+    let response = test_app.post_delivery_action("pause", &issue_id).await;
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    test_app.dispatch_all_pending_emails().await;
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("Sending 'Weekly digest' is paused after 0 of 2 deliveries."));
+    assert!(html_page.contains("<td>Weekly digest</td><td>paused</td><td>0 of 2</td>"));
+    drop(guard);
+
+    // Act - Part 2 - Resume it
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+    let response = test_app.post_delivery_action("resume", &issue_id).await;
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("Sending 'Weekly digest' has resumed."));
+    assert!(html_page.contains("<td>Weekly digest</td><td>sent</td><td>2 of 2</td>"));
+}
+
+#[tokio::test]
+async fn cancelling_a_delivery_discards_the_remaining_emails() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula@mail.com").await;
+    test_app.create_confirmed_subscriber("octavia@mail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_newsletter(&newsletter_body()).await;
+    let issue_id = issue_id(&test_app).await;
+
+    // Act
+    let response = test_app.post_delivery_action("cancel", &issue_id).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains(
+        "Sending 'Weekly digest' has been cancelled after 0 of 2 deliveries. 2 remaining deliveries were discarded."
+    ));
+    assert!(html_page.contains("<td>Weekly digest</td><td>cancelled</td><td>0 of 2</td>"));
+}
+
+#[tokio::test]
+async fn only_issues_being_sent_can_be_paused() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let response = test_app.post_newsletter_draft(&newsletter_body()).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let issue_id = issue_id(&test_app).await;
+
+    // Act
+    let response = test_app.post_delivery_action("pause", &issue_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("Only issues that are being sent can be paused."));
+    assert!(html_page.contains("<td>Weekly digest</td><td>draft</td>"));
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_a_bounded_number_of_times() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula@mail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(4)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_newsletter(&newsletter_body()).await;
+
+    // Act
+    for _ in 0..5 {
+        test_app.dispatch_all_pending_emails().await;
+        make_retries_due(&test_app).await;
+    }
+
+    // Assert
+    let task = sqlx::query!("SELECT status, n_retries FROM issue_delivery_tasks")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(task.status, "failed");
+    assert_eq!(task.n_retries, 3);
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("<td>Weekly digest</td><td>sent</td><td>0 of 1</td>"));
+}
+
+#[tokio::test]
+async fn subscribers_who_unsubscribe_before_delivery_are_skipped() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula@mail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_newsletter(&newsletter_body()).await;
+
+    // Act
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = sqlx::query!("SELECT status FROM issue_delivery_tasks")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(task.status, "skipped");
+}
+
+#[tokio::test]
+async fn the_delivery_report_lists_counts_and_failure_reasons() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula@mail.com").await;
+    test_app.create_confirmed_subscriber("octavia@mail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "To": "octavia@mail.com" })))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&test_app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_newsletter(&newsletter_body()).await;
+    let issue_id = issue_id(&test_app).await;
+
+    // Act - Part 1 - The report refreshes itself while the issue is being sent
+    let html_page = test_app.get_issue_report(&issue_id, "").await.text().await.unwrap();
+    assert!(html_page.contains("<meta http-equiv=\"refresh\""));
+    assert!(html_page.contains("<tr><th>Pending</th><td>2</td></tr>"));
+
+    // Act - Part 2 - Deliver, retrying the failure until the worker gives up
+    for _ in 0..5 {
+        test_app.dispatch_all_pending_emails().await;
+        make_retries_due(&test_app).await;
+    }
+    let html_page = test_app.get_issue_report(&issue_id, "").await.text().await.unwrap();
+    assert!(!html_page.contains("<meta http-equiv=\"refresh\""));
+    assert!(html_page.contains("<tr><th>Recipients</th><td>2</td></tr>"));
+    assert!(html_page.contains("<tr><th>Delivered</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Failed</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Retried</th><td>1</td></tr>"));
+    assert!(html_page.contains("<td>octavia@mail.com</td><td>3</td>"));
+    assert!(html_page.contains("500 Internal Server Error"));
+
+    // Act - Part 3 - Download the failures
+    let response = test_app.get_issue_report(&issue_id, "/failures.csv").await;
+    assert_eq!(response.headers()["Content-Type"], "text/csv; charset=utf-8");
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "email,retries,reason,failed_at");
+    assert!(lines[1].starts_with("octavia@mail.com,3,"));
+    assert!(lines[1].contains("500 Internal Server Error"));
+}
+
+#[tokio::test]
+async fn drafts_have_no_delivery_report() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_newsletter_draft(&newsletter_body()).await;
+    let issue_id = issue_id(&test_app).await;
+
+    // Act
+    let response = test_app.get_issue_report(&issue_id, "").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+async fn issue_id(app: &TestApp) -> String {
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+        .to_string()
+}
+
+async fn make_retries_due(app: &TestApp) {
+    sqlx::query!("UPDATE issue_delivery_tasks SET execute_after = now() WHERE status = 'pending'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+fn newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Weekly digest",
+        "content_html": "<p>Hello {{ name }}</p><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hello {{ name }} - unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    })
+}
@@ -0,0 +1,264 @@
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_newsletter_issues() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = test_app.get_newsletter_issues().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn saving_a_draft_does_not_send_anything() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let issue_id = save_draft(&test_app, &draft_body()).await;
+
+    // Assert
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("The draft 'Weekly digest' has been saved."));
+    assert!(html_page.contains("<td>Weekly digest</td><td>draft</td>"));
+    assert!(html_page.contains(&format!("/admin/newsletters/issues/{}/edit", issue_id)));
+}
+
+#[tokio::test]
+async fn a_draft_can_be_edited() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let issue_id = save_draft(&test_app, &draft_body()).await;
+
+    // Act
+    let mut body = draft_body();
+    body["issue_id"] = issue_id.clone().into();
+    body["title"] = "Monthly <digest>".into();
+    let saved_id = save_draft(&test_app, &body).await;
+
+    // Assert
+    assert_eq!(saved_id, issue_id);
+    let html_page = test_app
+        .get_edit_newsletter_issue(&issue_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("value=\"Monthly &lt;digest&gt;\""));
+    assert!(html_page.contains("&lt;p&gt;Hello {{ name }}&lt;/p&gt;"));
+    let issues = sqlx::query!("SELECT title FROM newsletter_issues")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].title, "Monthly <digest>");
+}
+
+#[tokio::test]
+async fn drafts_without_a_title_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let mut body = draft_body();
+    body["title"] = "  ".into();
+
+    // Act
+    let response = test_app.post_newsletter_draft(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("The newsletter title cannot be empty."));
+}
+
+#[tokio::test]
+async fn the_preview_shows_the_rendered_html_and_text() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let issue_id = save_draft(&test_app, &draft_body()).await;
+
+    // Act
+    let html_page = test_app.get_newsletter_preview_html(&issue_id).await;
+
+    // Assert
+    assert!(html_page.contains("previewing with sample values"));
+    assert!(html_page.contains("srcdoc=\"&lt;p&gt;Hello Jane Doe&lt;/p&gt;"));
+    assert!(html_page.contains("<pre>Hello Jane Doe"));
+}
+
+#[tokio::test]
+async fn the_preview_reports_invalid_templates() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let mut body = draft_body();
+    body["content_html"] = "<p>Hello {{ shoe_size }}</p>".into();
+    let issue_id = save_draft(&test_app, &body).await;
+
+    // Act
+    let html_page = test_app.get_newsletter_preview_html(&issue_id).await;
+
+    // Assert
+    assert!(html_page.contains("The newsletter template is invalid: `shoe_size` is not a known variable."));
+}
+
+#[tokio::test]
+async fn a_draft_can_be_deleted() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let issue_id = save_draft(&test_app, &draft_body()).await;
+
+    // Act
+    let response = test_app.post_delete_newsletter_draft(&issue_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("The draft has been deleted."));
+    assert!(!html_page.contains("Weekly digest"));
+}
+
+#[tokio::test]
+async fn a_published_draft_is_sent_and_can_no_longer_be_edited() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    let issue_id = save_draft(&test_app, &draft_body()).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish the draft
+    let mut body = draft_body();
+    body["issue_id"] = issue_id.clone().into();
+    body["idempotency_key"] = uuid::Uuid::new_v4().to_string().into();
+    let response = test_app.post_newsletter(&body).await;
+    test_app.dispatch_all_pending_emails().await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 2 - Try to edit it
+    let response = test_app.get_edit_newsletter_issue(&issue_id).await;
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+
+    // Act - Part 3 - Try to publish it again
+    body["idempotency_key"] = uuid::Uuid::new_v4().to_string().into();
+    let response = test_app.post_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+
+    // Assert
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("This newsletter issue has already been published."));
+    assert!(html_page.contains("<td>Weekly digest</td><td>sent</td>"));
+}
+
+#[tokio::test]
+async fn test_emails_only_go_to_the_given_addresses() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+    let mut body = draft_body();
+    body["test_recipients"] = "admin@example.com, editor@example.com".into();
+
+    // Act
+    let response = test_app.post_test_newsletter(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let requests = test_app.email_server.received_requests().await.unwrap();
+    let sent: Vec<serde_json::Value> = requests[1..]
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect();
+    assert_eq!(sent[0]["To"], "admin@example.com");
+    assert_eq!(sent[1]["To"], "editor@example.com");
+    assert_eq!(sent[0]["Subject"], "[TEST] Weekly digest");
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("A test email has been sent to admin@example.com, editor@example.com."));
+    assert!(!html_page.contains("<td>Weekly digest</td>"));
+    let saved_issues = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_issues")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved_issues.count, 0);
+    let saved_responses = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM idempotency")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved_responses.count, 0);
+}
+
+#[tokio::test]
+async fn test_emails_to_invalid_addresses_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+    let mut body = draft_body();
+    body["test_recipients"] = "admin@example.com, not-an-address".into();
+
+    // Act
+    let response = test_app.post_test_newsletter(&body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 303);
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("not-an-address is not a valid email address."));
+}
+
+/// Save a draft and return its id, taken from the edit page we are redirected to.
+async fn save_draft(app: &TestApp, body: &serde_json::Value) -> String {
+    let response = app.post_newsletter_draft(body).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    location
+        .strip_prefix("/admin/newsletters/issues/")
+        .and_then(|l| l.strip_suffix("/edit"))
+        .expect("Not redirected to the draft edit page.")
+        .to_owned()
+}
+
+fn draft_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Weekly digest",
+        "content_html": "<p>Hello {{ name }}</p><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hello {{ name }} - unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    })
+}
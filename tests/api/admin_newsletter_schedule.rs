@@ -0,0 +1,207 @@
+use chrono::{Duration, Utc};
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+#[tokio::test]
+async fn scheduled_issues_are_published_once_due() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act - Part 1 - Schedule the issue
+    let response = test_app.post_schedule_newsletter(&schedule_body(Duration::days(1))).await;
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("Your newsletter 'Weekly digest' is scheduled for"));
+    assert!(html_page.contains("<td>Weekly digest</td><td>scheduled</td>"));
+
+    // Act - Part 2 - Nothing goes out before the scheduled time
+    test_app.publish_due_issues().await;
+    assert_eq!(issue_status(&test_app).await, "scheduled");
+
+    // Act - Part 3 - The issue goes out once due
+    make_scheduled_issues_due(&test_app).await;
+    test_app.publish_due_issues().await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(issue_status(&test_app).await, "sent");
+}
+
+#[tokio::test]
+async fn a_due_issue_is_published_exactly_once_by_concurrent_schedulers() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_millis(500)))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_schedule_newsletter(&schedule_body(Duration::days(1))).await;
+    make_scheduled_issues_due(&test_app).await;
+
+    // Act
+    tokio::join!(test_app.publish_due_issues(), test_app.publish_due_issues());
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(issue_status(&test_app).await, "sent");
+}
+
+#[tokio::test]
+async fn an_issue_whose_deliveries_cannot_be_queued_is_not_left_sending() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    test_app.post_schedule_newsletter(&schedule_body(Duration::days(1))).await;
+    make_scheduled_issues_due(&test_app).await;
+    sqlx::query(
+        "CREATE FUNCTION reject_delivery_tasks() RETURNS trigger AS $$ \
+        BEGIN RAISE EXCEPTION 'delivery tasks are unavailable'; END; $$ LANGUAGE plpgsql",
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "CREATE TRIGGER reject_delivery_tasks BEFORE INSERT ON issue_delivery_tasks \
+        FOR EACH ROW EXECUTE FUNCTION reject_delivery_tasks()",
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    test_app.publish_due_issues().await;
+
+    // Assert
+    assert_eq!(issue_status(&test_app).await, "draft");
+}
+
+#[tokio::test]
+async fn times_in_the_past_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.post_schedule_newsletter(&schedule_body(Duration::days(-1))).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("The scheduled time must be in the future."));
+}
+
+#[tokio::test]
+async fn schedules_are_expressed_in_the_given_timezone() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let mut body = schedule_body(Duration::days(1));
+    body["scheduled_for"] = "2100-01-15T07:00".into();
+    body["timezone"] = "America/New_York".into();
+
+    // Act
+    test_app.post_schedule_newsletter(&body).await;
+
+    // Assert
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("<td>2100-01-15 12:00 UTC</td>"));
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_can_be_rescheduled() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_schedule_newsletter(&schedule_body(Duration::days(1))).await;
+
+    // Act
+    let response = test_app
+        .post_reschedule_newsletter(&serde_json::json!({
+            "issue_id": issue_id(&test_app).await,
+            "scheduled_for": "2100-02-01T09:30",
+            "timezone": "Europe/Paris",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("The newsletter is now scheduled for 2100-02-01 08:30 UTC."));
+}
+
+#[tokio::test]
+async fn a_cancelled_schedule_never_fires() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_schedule_newsletter(&schedule_body(Duration::days(1))).await;
+
+    // Act
+    let response = test_app.post_unschedule_newsletter(&issue_id(&test_app).await).await;
+    make_scheduled_issues_due(&test_app).await;
+    test_app.publish_due_issues().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/issues");
+    let html_page = test_app.get_newsletter_issues_html().await;
+    assert!(html_page.contains("The schedule has been cancelled. The issue is back in drafts."));
+    assert_eq!(issue_status(&test_app).await, "draft");
+}
+
+async fn issue_id(app: &TestApp) -> String {
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+        .to_string()
+}
+
+async fn issue_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+async fn make_scheduled_issues_due(app: &TestApp) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_for = now() - interval '1 minute' WHERE status = 'scheduled'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+fn schedule_body(from_now: Duration) -> serde_json::Value {
+    let scheduled_for = (Utc::now() + from_now).format("%Y-%m-%dT%H:%M").to_string();
+    serde_json::json!({
+        "title": "Weekly digest",
+        "content_html": "<p>Hello {{ name }}</p><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hello {{ name }} - unsubscribe: {{ unsubscribe_url }}",
+        "scheduled_for": scheduled_for,
+        "timezone": "UTC",
+    })
+}
@@ -20,6 +20,7 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
     
     // Act
     let response = test_app.post_newsletter(&newsletter_body()).await;
+    test_app.dispatch_all_pending_emails().await;
 
     // Assert
     assert_is_redirect_to(&response, "/admin/newsletters");    
@@ -43,6 +44,7 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
 
      // Act
      let response = test_app.post_newsletter(&newsletter_body()).await;
+     test_app.dispatch_all_pending_emails().await;
 
      // Assert
      assert_is_redirect_to(&response, "/admin/newsletters");    
@@ -115,6 +117,7 @@ async fn newsletter_creation_is_idempotent() {
      // Act - Part 1 - publish newsletter
      let body = newsletter_body();
      let response = test_app.post_newsletter(&body).await;
+     test_app.dispatch_all_pending_emails().await;
      assert_is_redirect_to(&response, "/admin/newsletters");    
 
     // Act - Part 2 - validate success message has been injected
@@ -123,6 +126,7 @@ async fn newsletter_creation_is_idempotent() {
 
      // Act - Part 3 - publish newsletter **again**
      let response = test_app.post_newsletter(&body).await;
+     test_app.dispatch_all_pending_emails().await;
      assert_is_redirect_to(&response, "/admin/newsletters");    
 
     // Act - Part 4 - validate success message has been injected
@@ -133,6 +137,282 @@ async fn newsletter_creation_is_idempotent() {
      // Assert: mock verifies on Drop that only **1** call was made to endpoint, not 2
 }
 
+#[tokio::test]
+async fn newsletters_are_personalized_for_each_recipient() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_custom_field(&serde_json::json!({
+        "name": "nickname",
+        "field_type": "text",
+    })).await;
+    create_confirmed_subscriber(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_newsletter(&serde_json::json!({
+        "title": "News for {{ name }}",
+        "content_html": "<p>Hi {{ name }}</p><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hi {{ nickname | default: \"reader\" }}, unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    })).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("has been published.</i></p>"));
+
+    let email_request = test_app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let token = sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token;
+    assert_eq!(body["Subject"], "News for john doe");
+    assert!(body["HtmlBody"].as_str().unwrap().starts_with("<p>Hi john doe</p>"));
+    assert!(body["TextBody"].as_str().unwrap().starts_with("Hi reader, unsubscribe: "));
+    assert!(body["TextBody"].as_str().unwrap().ends_with(&format!("/subscriptions/unsubscribe?unsubscribe_token={}", token)));
+}
+
+#[tokio::test]
+async fn invalid_templates_are_rejected_before_sending() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    create_confirmed_subscriber(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let test_cases = [
+        ("<p>Hi {{ name </p>", "is never closed."),
+        ("<p>Hi {{ shoe_size }}</p>", "`shoe_size` is not a known variable."),
+    ];
+    for (content_html, error) in test_cases {
+        // Act
+        let response = test_app.post_newsletter(&serde_json::json!({
+            "title": "Title",
+            "content_html": content_html,
+            "content_text": "Text Content",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        })).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/newsletters");
+        let html_page = test_app.get_send_newsletters_html().await;
+        assert!(html_page.contains("The newsletter template is invalid"));
+        assert!(html_page.contains(error));
+    }
+}
+
+#[tokio::test]
+async fn markdown_newsletters_are_sent_as_html_and_text() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    create_confirmed_subscriber(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_newsletter(&serde_json::json!({
+        "title": "Title",
+        "content_format": "markdown",
+        "content_markdown": "Hello **{{ name }}**, read [the post](https://example.com/post).",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "ignore_warnings": true,
+    })).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let email_request = test_app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    // The link is rewritten to a click tracking redirect at send time.
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    let (before, rest) = html_body.split_once("<a href=\"").unwrap();
+    let (href, after) = rest.split_once('"').unwrap();
+    assert_eq!(before, "<p>Hello <strong>john doe</strong>, read ");
+    assert!(href.contains("/r/"));
+    assert_eq!(after, ">the post</a>.</p>\n");
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(text_body.starts_with("Hello **john doe**, read [the post][1]."));
+    assert!(text_body.contains("[1]: https://example.com/post"));
+}
+
+#[tokio::test]
+async fn the_text_version_is_derived_when_only_html_is_provided() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    create_confirmed_subscriber(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_newsletter(&serde_json::json!({
+        "title": "Title",
+        "content_html": "<p>Hello <a href=\"https://example.com\">world</a></p>",
+        "content_text": "",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "ignore_warnings": true,
+    })).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let email_request = test_app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["TextBody"], "Hello [world][1]\n\n[1]: https://example.com\n");
+}
+
+#[tokio::test]
+async fn unsafe_html_is_sanitized_before_sending() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    create_confirmed_subscriber(&test_app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_newsletter(&serde_json::json!({
+        "title": "Title",
+        "content_html": "<p onclick=\"steal()\">Hi</p><script>steal()</script>\
+            <form action=\"https://evil.com\"><input name=\"password\"></form>\
+            <a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hi - unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    })).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let email_request = test_app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(html_body.starts_with("<p>Hi</p><a href=\"http://127.0.0.1/subscriptions/unsubscribe?"));
+    assert!(!html_body.contains("<script"));
+    assert!(!html_body.contains("<form"));
+}
+
+#[tokio::test]
+async fn placeholders_cannot_inject_unsafe_urls() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    for name in ["website", "blog"] {
+        test_app.post_custom_field(&serde_json::json!({
+            "name": name,
+            "field_type": "text",
+        })).await;
+    }
+    test_app
+        .create_confirmed_subscriber_from_form(
+            "name=mallory&email=mallory%40mail.com&website=javascript%3Aalert(1)".into(),
+        )
+        .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_newsletter(&serde_json::json!({
+        "title": "Title",
+        "content_html": "<a href=\"{{ website }}\">Site</a>\
+            <a href=\"{{ blog | default: \"javascript:alert(2)\" }}\">Blog</a>\
+            <a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hi - unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "ignore_warnings": true,
+    })).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let email_request = test_app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(!html_body.contains("javascript:"));
+    assert!(html_body.starts_with("<a>Site</a><a>Blog</a><a href=\"http://127.0.0.1/subscriptions/unsubscribe?"));
+}
+
+#[tokio::test]
+async fn lint_warnings_must_be_acknowledged_before_sending() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    create_confirmed_subscriber(&test_app).await;
+    let mut body = serde_json::json!({
+        "title": "Title",
+        "content_html": "<p><img src=\"https://example.com/a.png\"><a href=\"/blog\">Blog</a></p>",
+        "content_text": "Text Content",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+
+    // Act - Part 1 - warnings block the send
+    let guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+    let response = test_app.post_newsletter(&body).await;
+    test_app.dispatch_all_pending_emails().await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("<p><i>1 image(s) have no alt text.</i></p>"));
+    assert!(html_page.contains("<p><i>The link '/blog' is relative and will not work from an inbox.</i></p>"));
+    assert!(html_page.contains("<p><i>The HTML body has no {{ unsubscribe_url }} link.</i></p>"));
+    assert!(html_page.contains("<p><i>The text body has no {{ unsubscribe_url }} link.</i></p>"));
+    drop(guard);
+
+    // Act - Part 2 - the admin acknowledges the warnings
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    body["ignore_warnings"] = true.into();
+    let response = test_app.post_newsletter(&body).await;
+    test_app.dispatch_all_pending_emails().await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("has been published.</i></p>"));
+}
+
 async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
     let body = "name=john%20doe&email=john_doe%40mail.com"; 
     
@@ -172,8 +452,8 @@ async fn create_confirmed_subscriber(app: &TestApp) {
 fn newsletter_body() -> serde_json::Value {
     serde_json::json!({
         "title": "Title",
-        "content_html": "<p> HTML Content </p>",
-        "content_text": "Text Content",
+        "content_html": "<p> HTML Content </p><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Text Content - unsubscribe: {{ unsubscribe_url }}",
         "idempotency_key": uuid::Uuid::new_v4().to_string(),
     })
 }
\ No newline at end of file
@@ -0,0 +1,69 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn viewers_can_browse_subscribers() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.login_with_role("viewer").await;
+
+    // Act
+    let html_page = test_app.get_admin_subscribers_html().await;
+
+    // Assert
+    assert!(html_page.contains("<title>Subscribers</title>"));
+    let response = test_app.get_newsletter_issues().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn viewers_cannot_publish_newsletters() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.login_with_role("viewer").await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+
+    // Act
+    let form_response = test_app.get_send_newsletters().await;
+    let publish_response = test_app.post_newsletter(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(form_response.status().as_u16(), 403);
+    assert_eq!(publish_response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn viewers_and_editors_cannot_manage_users() {
+    for role in ["viewer", "editor"] {
+        // Arrange
+        let test_app = spawn_app().await;
+        test_app.login_with_role(role).await;
+
+        // Act
+        let list_response = test_app.get_admin_users().await;
+        let invite_response = test_app.post_invitation("someone@example.com", "owner").await;
+
+        // Assert
+        assert_eq!(list_response.status().as_u16(), 403, "{} could list users", role);
+        assert_eq!(invite_response.status().as_u16(), 403, "{} could invite users", role);
+        let html_page = test_app.get_admin_dashboard_html().await;
+        assert!(!html_page.contains("/admin/users"));
+    }
+}
+
+#[tokio::test]
+async fn editors_can_publish_newsletters() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.login_with_role("editor").await;
+
+    // Act
+    let response = test_app.get_send_newsletters().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,242 @@
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_segments() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = test_app.get_admin_segments().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn tags_are_listed_on_the_subscribers_page() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("tagged@mail.com").await;
+
+    // Act
+    let response = test_app.post_subscriber_tag(&serde_json::json!({
+        "email": "tagged@mail.com",
+        "tag": "Early-Adopters",
+    })).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = test_app.get_admin_subscribers_html().await;
+    assert!(html_page.contains("The tag 'early-adopters' has been added."));
+    assert!(html_page.contains("<td>early-adopters</td>"));
+}
+
+#[tokio::test]
+async fn invalid_tags_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("tagged@mail.com").await;
+
+    // Act
+    let response = test_app.post_subscriber_tag(&serde_json::json!({
+        "email": "tagged@mail.com",
+        "tag": "early adopters",
+    })).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = test_app.get_admin_subscribers_html().await;
+    assert!(html_page.contains("is not a valid tag."));
+}
+
+#[tokio::test]
+async fn segments_preview_their_recipient_count() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("tagged@mail.com").await;
+    test_app.create_confirmed_subscriber("untagged@mail.com").await;
+    tag_subscriber(&test_app, "tagged@mail.com", "vip").await;
+
+    // Act
+    let response = test_app.post_segment(&serde_json::json!({
+        "name": "VIPs",
+        "tag": "vip",
+    })).await;
+    assert_is_redirect_to(&response, "/admin/segments");
+
+    // Assert
+    let html_page = test_app.get_admin_segments_html().await;
+    assert!(html_page.contains("The segment 'VIPs' has been saved."));
+    assert!(html_page.contains("<td>VIPs</td><td>vip</td><td></td><td></td><td></td><td></td><td></td><td>1</td>"));
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("All confirmed subscribers (2 recipients)"));
+    assert!(html_page.contains("VIPs (1 recipients)"));
+}
+
+#[tokio::test]
+async fn newsletters_are_only_delivered_to_the_selected_segment() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("tagged@mail.com").await;
+    test_app.create_confirmed_subscriber("untagged@mail.com").await;
+    tag_subscriber(&test_app, "tagged@mail.com", "vip").await;
+    test_app.post_segment(&serde_json::json!({
+        "name": "VIPs",
+        "tag": "vip",
+    })).await;
+    let segment_id = sqlx::query!("SELECT segment_id FROM segments WHERE name = 'VIPs'")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .segment_id;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_newsletter(&serde_json::json!({
+        "title": "Title",
+        "content_html": "<p> HTML Content </p>",
+        "content_text": "Text Content",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "segment_id": segment_id.to_string(),
+        "ignore_warnings": true,
+    })).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let received_requests = test_app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&received_requests.last().unwrap().body).unwrap();
+    assert_eq!(body["To"], "tagged@mail.com");
+}
+
+#[tokio::test]
+async fn segments_can_target_custom_field_values() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_custom_field(&serde_json::json!({
+        "name": "plan",
+        "field_type": "enum",
+        "options": "free, pro",
+    })).await;
+    test_app.create_confirmed_subscriber_from_form("name=pro&email=pro%40mail.com&plan=pro".into()).await;
+    test_app.create_confirmed_subscriber_from_form("name=free&email=free%40mail.com&plan=free".into()).await;
+
+    // Act
+    let response = test_app.post_segment(&serde_json::json!({
+        "name": "Paying",
+        "field_name": "plan",
+        "field_value": "pro",
+    })).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/segments");
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("Paying (1 recipients)"));
+}
+
+#[tokio::test]
+async fn segments_can_target_list_members() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("member@mail.com").await;
+    test_app.create_confirmed_subscriber("other@mail.com").await;
+    let response = test_app.post_subscriber_list(&serde_json::json!({
+        "email": "member@mail.com",
+        "list_name": "Weekly-Digest",
+    })).await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = test_app.get_admin_subscribers_html().await;
+    assert!(html_page.contains("The subscriber has been added to the list 'weekly-digest'."));
+
+    // Act
+    let response = test_app.post_segment(&serde_json::json!({
+        "name": "Digest readers",
+        "list_name": "weekly-digest",
+    })).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/segments");
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("Digest readers (1 recipients)"));
+}
+
+#[tokio::test]
+async fn segments_can_target_engaged_subscribers() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("engaged@mail.com").await;
+    test_app.create_confirmed_subscriber("dormant@mail.com").await;
+    test_app.post_newsletter_draft(&serde_json::json!({
+        "title": "Title",
+        "content_html": "<p>HTML Content</p>",
+        "content_text": "Text Content",
+    })).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_opens (newsletter_issue_id, subscriber_id, opened_at)
+        SELECT i.newsletter_issue_id, s.id, now() - interval '3 days'
+        FROM newsletter_issues i, subscriptions s
+        WHERE s.email = 'engaged@mail.com'
+        "#
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    test_app.post_segment(&serde_json::json!({
+        "name": "Engaged this week",
+        "engaged_within_days": "7",
+    })).await;
+    test_app.post_segment(&serde_json::json!({
+        "name": "Engaged today",
+        "engaged_within_days": "1",
+    })).await;
+
+    // Assert
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("Engaged this week (1 recipients)"));
+    assert!(html_page.contains("Engaged today (0 recipients)"));
+}
+
+#[tokio::test]
+async fn a_non_positive_engagement_period_is_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.post_segment(&serde_json::json!({
+        "name": "Never",
+        "engaged_within_days": "0",
+    })).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/segments");
+    let html_page = test_app.get_admin_segments_html().await;
+    assert!(html_page.contains("The engagement period must be a positive number of days."));
+}
+
+async fn tag_subscriber(app: &TestApp, email: &str, tag: &str) {
+    let response = app.post_subscriber_tag(&serde_json::json!({
+        "email": email,
+        "tag": tag,
+    })).await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+}
+
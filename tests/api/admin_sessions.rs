@@ -0,0 +1,277 @@
+use uuid::Uuid;
+use zero2prod::authentication::delete_stale_sessions;
+use zero2prod::configuration::SessionSettings;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+/// The ids of the sessions of the test user that have not been revoked, oldest first.
+async fn active_session_ids(test_app: &TestApp) -> Vec<Uuid> {
+    sqlx::query!(
+        "SELECT session_id FROM user_sessions WHERE user_id = $1 AND revoked_at IS NULL ORDER BY created_at",
+        test_app.test_user.user_id
+    )
+    .fetch_all(&test_app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.session_id)
+    .collect()
+}
+
+async fn get_dashboard(test_app: &TestApp, client: &reqwest::Client) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = test_app.get_admin_sessions().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn your_sessions_are_listed() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.login_on_another_device().await;
+    test_app.valid_login().await;
+
+    // Act
+    let html_page = test_app.get_admin_sessions_html().await;
+
+    // Assert
+    assert_eq!(active_session_ids(&test_app).await.len(), 2);
+    assert!(html_page.contains("This session"));
+    assert_eq!(html_page.matches("<button type=\"submit\">Revoke</button>").count(), 1);
+}
+
+#[tokio::test]
+async fn a_revoked_session_is_logged_out() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let other_device = test_app.login_on_another_device().await;
+    test_app.valid_login().await;
+    let other_session_id = active_session_ids(&test_app).await[0];
+
+    // Act
+    let response = test_app.post_revoke_session(&other_session_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let html_page = test_app.get_admin_sessions_html().await;
+    assert!(html_page.contains("The session has been revoked."));
+    let response = get_dashboard(&test_app, &other_device).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = get_dashboard(&test_app, &test_app.api_client).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn all_other_sessions_can_be_revoked_at_once() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let first_device = test_app.login_on_another_device().await;
+    let second_device = test_app.login_on_another_device().await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.post_revoke_all_sessions().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    assert_eq!(active_session_ids(&test_app).await.len(), 1);
+    assert_is_redirect_to(&get_dashboard(&test_app, &first_device).await, "/login");
+    assert_is_redirect_to(&get_dashboard(&test_app, &second_device).await, "/login");
+    assert_eq!(get_dashboard(&test_app, &test_app.api_client).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn changing_your_password_revokes_your_other_sessions() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let other_device = test_app.login_on_another_device().await;
+    test_app.valid_login().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    test_app
+        .post_change_password(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&get_dashboard(&test_app, &other_device).await, "/login");
+    assert_eq!(get_dashboard(&test_app, &test_app.api_client).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn logging_out_revokes_the_session() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    test_app.post_logout().await;
+
+    // Assert
+    assert!(active_session_ids(&test_app).await.is_empty());
+}
+
+#[tokio::test]
+async fn only_owners_can_see_the_sessions_of_other_users() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.login_with_role("editor").await;
+
+    // Act
+    let response = test_app
+        .api_client
+        .get(format!("{}/admin/sessions?user_id={}", &test_app.address, test_app.test_user.user_id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn an_idle_session_expires() {
+    // Arrange
+    let test_app = spawn_app_with(|c| c.session.idle_timeout_minutes = 0).await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Your session has expired after a period of inactivity, please log in again."));
+    assert!(active_session_ids(&test_app).await.is_empty());
+}
+
+#[tokio::test]
+async fn a_session_expires_after_its_maximum_duration() {
+    // Arrange
+    let test_app = spawn_app_with(|c| c.session.absolute_timeout_minutes = 0).await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Your session has reached its maximum duration, please log in again."));
+}
+
+#[tokio::test]
+async fn an_active_session_does_not_expire() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    test_app.get_admin_dashboard().await;
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_recently_active_session_is_not_saved_again() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let session_cookies = response
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter(|cookie| cookie.to_str().unwrap().starts_with("id="))
+        .count();
+    assert_eq!(session_cookies, 0);
+}
+
+#[tokio::test]
+async fn activity_is_recorded_at_most_once_a_minute() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let set_last_seen_at = |seconds_ago: f64| {
+        sqlx::query!(
+            "UPDATE user_sessions SET last_seen_at = now() - make_interval(secs => $1) RETURNING last_seen_at",
+            seconds_ago
+        )
+        .fetch_one(&test_app.db_pool)
+    };
+    let last_seen_at = || {
+        sqlx::query!("SELECT last_seen_at FROM user_sessions").fetch_one(&test_app.db_pool)
+    };
+
+    // Act - Part 1
+    let recent = set_last_seen_at(30.0).await.unwrap().last_seen_at;
+    test_app.get_admin_dashboard().await;
+
+    // Assert - Part 1
+    assert_eq!(last_seen_at().await.unwrap().last_seen_at, recent);
+
+    // Act - Part 2
+    let old = set_last_seen_at(120.0).await.unwrap().last_seen_at;
+    test_app.get_admin_dashboard().await;
+
+    // Assert - Part 2
+    assert!(last_seen_at().await.unwrap().last_seen_at > old);
+}
+
+#[tokio::test]
+async fn revoked_and_expired_sessions_are_deleted() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.login_on_another_device().await;
+    test_app.login_on_another_device().await;
+    test_app.valid_login().await;
+    let session_ids = active_session_ids(&test_app).await;
+    test_app.post_revoke_session(&session_ids[0]).await;
+    sqlx::query!(
+        "UPDATE user_sessions SET last_seen_at = now() - interval '1 hour' WHERE session_id = $1",
+        session_ids[1]
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    let settings = SessionSettings { idle_timeout_minutes: 30, absolute_timeout_minutes: 720 };
+
+    // Act
+    let deleted = delete_stale_sessions(&settings, &test_app.db_pool).await.unwrap();
+
+    // Assert
+    assert_eq!(deleted, 2);
+    let remaining: Vec<Uuid> = sqlx::query!("SELECT session_id FROM user_sessions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.session_id)
+        .collect();
+    assert_eq!(remaining, vec![session_ids[2]]);
+}
@@ -0,0 +1,138 @@
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = test_app.get_admin_suppressions().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn blocked_addresses_are_listed_and_can_be_removed() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act - Part 1 - Block an address
+    let response = test_app.post_suppression("Blocked@Mail.com").await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    // Assert
+    let html_page = test_app.get_admin_suppressions_html().await;
+    assert!(html_page.contains("has been added to the suppression list."));
+    assert!(html_page.contains("<tr><td>blocked@mail.com</td><td>blocked</td><td>admin</td>"));
+
+    // Act - Part 2 - Remove it
+    let response = test_app.post_delete_suppression("blocked@mail.com").await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    // Assert
+    let html_page = test_app.get_admin_suppressions_html().await;
+    assert!(html_page.contains("blocked@mail.com has been removed from the suppression list."));
+    assert!(!html_page.contains("<td>blocked@mail.com</td>"));
+}
+
+#[tokio::test]
+async fn suppressed_addresses_do_not_receive_confirmation_emails() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_suppression("ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn suppressed_addresses_do_not_receive_test_emails() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_suppression("blocked@mail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    test_app.post_test_newsletter(&serde_json::json!({
+        "title": "Weekly digest",
+        "content_html": "<p>Hello</p><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hello - unsubscribe: {{ unsubscribe_url }}",
+        "test_recipients": "blocked@mail.com, editor@mail.com",
+    }))
+    .await;
+
+    // Assert
+    let html_page = test_app.get_send_newsletters_html().await;
+    assert!(html_page.contains("blocked@mail.com is on the suppression list, no test email was sent to it."));
+    assert!(html_page.contains("A test email has been sent to editor@mail.com."));
+}
+
+#[tokio::test]
+async fn hard_bounces_add_the_address_to_the_suppression_list() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    test_app
+        .post_email_event(
+            &serde_json::json!({"RecordType": "Bounce", "Type": "HardBounce", "Email": "gone@mail.com"}),
+            Some(&test_app.webhook_secret),
+        )
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let html_page = test_app.get_admin_suppressions_html().await;
+    assert!(html_page.contains("<tr><td>gone@mail.com</td><td>bounced</td><td>postmark</td>"));
+}
+
+#[tokio::test]
+async fn removing_a_suppression_confirms_bounced_subscribers_again() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    test_app
+        .post_email_event(
+            &serde_json::json!({"RecordType": "Bounce", "Type": "HardBounce", "Email": "ursula_le_guin@gmail.com"}),
+            Some(&test_app.webhook_secret),
+        )
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = test_app.post_delete_suppression("ursula_le_guin@gmail.com").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+}
@@ -0,0 +1,285 @@
+use uuid::Uuid;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// Invite `email` as an editor and return the invitation token found in the email sent to them.
+async fn invite(test_app: &TestApp, email: &str) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Send invitation")
+        .expect(1)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+    let response = test_app.post_invitation(email, "editor").await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    let email_request = test_app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = test_app.get_confirmation_links(&email_request);
+    links
+        .html
+        .query_pairs()
+        .find(|(k, _)| k == "invitation_token")
+        .map(|(_, v)| v.into_owned())
+        .unwrap()
+}
+
+fn account_form(invitation_token: &str, username: &str, password: &str) -> serde_json::Value {
+    serde_json::json!({
+        "invitation_token": invitation_token,
+        "username": username,
+        "password": password,
+        "password_check": password,
+    })
+}
+
+async fn user_id_of(test_app: &TestApp, username: &str) -> Uuid {
+    sqlx::query!("SELECT user_id FROM users WHERE username = $1", username)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .user_id
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_users() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = test_app.get_admin_users().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_invited_user_can_set_up_their_account_and_log_in() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act - Part 1 - Invite
+    let invitation_token = invite(&test_app, "new_admin@example.com").await;
+
+    // Assert
+    let html_page = test_app.get_admin_users_html().await;
+    assert!(html_page.contains("An invitation has been sent to new_admin@example.com."));
+    assert!(html_page.contains("<tr><td>new_admin@example.com</td><td>editor</td>"));
+
+    // Act - Part 2 - Accept
+    test_app.post_logout().await;
+    let response = test_app
+        .post_accept_invitation(&account_form(&invitation_token, "new_admin", "a-long-enough-password"))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Your account has been created, you can now log in."));
+
+    // Act - Part 3 - Log in
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": "new_admin",
+            "password": "a-long-enough-password",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = test_app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Welcome, new_admin!"));
+}
+
+#[tokio::test]
+async fn an_invitation_can_only_be_used_once() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let invitation_token = invite(&test_app, "new_admin@example.com").await;
+    let response = test_app
+        .post_accept_invitation(&account_form(&invitation_token, "new_admin", "a-long-enough-password"))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act
+    let response = test_app
+        .post_accept_invitation(&account_form(&invitation_token, "another_admin", "a-long-enough-password"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn expired_invitations_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let invitation_token = invite(&test_app, "new_admin@example.com").await;
+    sqlx::query!("UPDATE user_invitations SET expires_at = now() - interval '1 minute'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = test_app
+        .post_accept_invitation(&account_form(&invitation_token, "new_admin", "a-long-enough-password"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_short_password_is_rejected_when_accepting_an_invitation() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let invitation_token = invite(&test_app, "new_admin@example.com").await;
+
+    // Act
+    let response = test_app
+        .post_accept_invitation(&account_form(&invitation_token, "new_admin", "short"))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/invitations/accept?invitation_token={}", invitation_token),
+    );
+    let count = sqlx::query!("SELECT count(*) AS \"count!\" FROM users WHERE username = 'new_admin'")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn a_deactivated_user_is_logged_out_and_cannot_log_in() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let invitation_token = invite(&test_app, "new_admin@example.com").await;
+    test_app
+        .post_accept_invitation(&account_form(&invitation_token, "new_admin", "a-long-enough-password"))
+        .await;
+    let new_admin_id = user_id_of(&test_app, "new_admin").await;
+
+    // Act - Part 1 - Deactivate the user while they are logged in
+    test_app.post_logout().await;
+    let login_body = serde_json::json!({
+        "username": "new_admin",
+        "password": "a-long-enough-password",
+    });
+    test_app.post_login(&login_body).await;
+    sqlx::query!("UPDATE users SET is_active = false WHERE user_id = $1", new_admin_id)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Your account has been deactivated."));
+
+    // Act - Part 2 - Try to log in again
+    let response = test_app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn users_can_be_deactivated_reactivated_and_deleted() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let invitation_token = invite(&test_app, "new_admin@example.com").await;
+    test_app
+        .post_accept_invitation(&account_form(&invitation_token, "new_admin", "a-long-enough-password"))
+        .await;
+    let new_admin_id = user_id_of(&test_app, "new_admin").await;
+
+    // Act - Part 1 - Deactivate
+    let response = test_app.post_user_action("deactivate", &new_admin_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    // Assert
+    let html_page = test_app.get_admin_users_html().await;
+    assert!(html_page.contains("<tr><td>new_admin</td><td>new_admin@example.com</td><td>editor</td><td>deactivated</td>"));
+
+    // Act - Part 2 - Reactivate
+    test_app.post_user_action("activate", &new_admin_id).await;
+
+    // Assert
+    let html_page = test_app.get_admin_users_html().await;
+    assert!(html_page.contains("<tr><td>new_admin</td><td>new_admin@example.com</td><td>editor</td><td>active</td>"));
+
+    // Act - Part 3 - Delete
+    test_app.post_user_action("delete", &new_admin_id).await;
+
+    // Assert
+    let html_page = test_app.get_admin_users_html().await;
+    assert!(!html_page.contains("<td>new_admin</td>"));
+}
+
+#[tokio::test]
+async fn you_cannot_deactivate_or_delete_yourself() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.post_user_action("delete", &test_app.test_user.user_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = test_app.get_admin_users_html().await;
+    assert!(html_page.contains("You cannot deactivate or delete your own account."));
+    assert!(html_page.contains(&format!("<tr><td>{}</td>", test_app.test_user.username)));
+}
+
+#[tokio::test]
+async fn owners_can_change_the_role_of_other_users() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let invitation_token = invite(&test_app, "new_admin@example.com").await;
+    test_app
+        .post_accept_invitation(&account_form(&invitation_token, "new_admin", "a-long-enough-password"))
+        .await;
+    let new_admin_id = user_id_of(&test_app, "new_admin").await;
+
+    // Act
+    let response = test_app.post_user_role(&new_admin_id, "viewer").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = test_app.get_admin_users_html().await;
+    assert!(html_page.contains("The role of the user has been changed to viewer."));
+    assert!(html_page.contains("<tr><td>new_admin</td><td>new_admin@example.com</td><td>viewer</td><td>active</td>"));
+}
+
+#[tokio::test]
+async fn you_cannot_change_your_own_role() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+
+    // Act
+    let response = test_app.post_user_role(&test_app.test_user.user_id, "viewer").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = test_app.get_admin_users_html().await;
+    assert!(html_page.contains("You cannot change your own role."));
+}
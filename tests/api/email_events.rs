@@ -0,0 +1,137 @@
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp};
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+#[tokio::test]
+async fn email_events_without_credentials_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = test_app.post_email_event(&hard_bounce(), None).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["WWW-Authenticate"], r#"Basic realm="email-events""#);
+}
+
+#[tokio::test]
+async fn email_events_with_an_invalid_secret_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.create_confirmed_subscriber(EMAIL).await;
+
+    // Act
+    let response = test_app.post_email_event(&hard_bounce(), Some("not-the-secret")).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(subscriber_status(&test_app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn invalid_payloads_are_rejected_only_after_authentication() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let payload = serde_json::json!({"Type": "HardBounce"});
+
+    // Act
+    let anonymous = test_app.post_email_event(&payload, None).await;
+    let authenticated = test_app.post_email_event(&payload, Some(&test_app.webhook_secret)).await;
+
+    // Assert
+    assert_eq!(anonymous.status().as_u16(), 401);
+    assert_eq!(authenticated.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn hard_bounces_and_spam_complaints_update_the_subscriber_status() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let complaint = serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Type": "SpamComplaint",
+        "Email": EMAIL,
+    });
+    test_app.create_confirmed_subscriber(EMAIL).await;
+
+    for (event, status) in [(hard_bounce(), "bounced"), (complaint, "complained")] {
+        // Act
+        let response = test_app.post_email_event(&event, Some(&test_app.webhook_secret)).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(subscriber_status(&test_app).await, status);
+    }
+}
+
+#[tokio::test]
+async fn soft_bounces_do_not_update_the_subscriber_status() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.create_confirmed_subscriber(EMAIL).await;
+    let soft_bounce = serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "SoftBounce",
+        "Email": EMAIL,
+    });
+
+    // Act
+    let response = test_app.post_email_event(&soft_bounce, Some(&test_app.webhook_secret)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&test_app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_bounced_subscribers() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber(EMAIL).await;
+    test_app
+        .post_email_event(&hard_bounce(), Some(&test_app.webhook_secret))
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    test_app.post_newsletter(&serde_json::json!({
+        "title": "Weekly digest",
+        "content_html": "<p>Hello</p><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hello - unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    }))
+    .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert - the mock verifies no email is sent on drop
+}
+
+fn hard_bounce() -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "HardBounce",
+        "TypeCode": 1,
+        "Email": EMAIL,
+        "Description": "The server was unable to deliver your message.",
+    })
+}
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
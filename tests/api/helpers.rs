@@ -1,14 +1,20 @@
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
 use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Connection, PgConnection, PgPool, Executor};
 use uuid::Uuid;
 use once_cell::sync::Lazy;
 
 
-use wiremock::MockServer;
-use zero2prod::configuration::{self, DatabaseSettings};
-use zero2prod::startup::{get_connection_pool, Application};
+use wiremock::{matchers::{method, path}, Mock, MockServer, ResponseTemplate};
+use zero2prod::authentication::csrf_token;
+use zero2prod::authentication::totp::{current_time_step, decrypt_totp_secret, totp_code};
+use zero2prod::configuration::{self, DatabaseSettings, Settings};
+use zero2prod::email_client::EmailClient;
+use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome, PreparedIssueCache};
+use zero2prod::issue_scheduler::{self, try_publish_due_issue};
+use zero2prod::startup::{get_connection_pool, Application, HmacSecret};
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
 
@@ -31,12 +37,17 @@ pub struct TestApp {
     pub port: u16,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub base_url: String,
+    pub hmac_secret: HmacSecret,
+    pub webhook_secret: String,
+    pub totp_encryption_key: Secret<String>,
 }
 
 impl TestApp {
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", self.address))
+            .post(format!("{}/subscriptions", self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -44,6 +55,22 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_subscriptions_json(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions", self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_custom_field<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/fields", body).await
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).expect("Failed to parse request body.");
 
@@ -60,27 +87,58 @@ impl TestApp {
             confirmation_link.set_port(Some(self.port)).unwrap();
             confirmation_link   
         };
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
-        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
         ConfirmationLinks {html, plain_text} 
     }
 
+    /// Subscribe `email` and follow the link of the confirmation email.
+    pub async fn create_confirmed_subscriber(&self, email: &str) {
+        let body = format!("name=le%20guin&email={}", urlencoding::encode(email));
+        self.create_confirmed_subscriber_from_form(body).await;
+    }
+
+    /// Subscribe with a raw form `body`, e.g. to fill in custom fields, and confirm the subscription.
+    pub async fn create_confirmed_subscriber_from_form(&self, body: String) {
+        let _mock_guard = Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount_as_scoped(&self.email_server)
+            .await;
+        self.post_subscriptions(body)
+            .await
+            .error_for_status()
+            .unwrap();
+        let email_request = self.email_server.received_requests().await.unwrap().pop().unwrap();
+        let confirmation_links = self.get_confirmation_links(&email_request);
+        reqwest::get(confirmation_links.html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
     pub async fn post_newsletter<Body>(&self, body: &Body) -> reqwest::Response
     where
     Body: serde::Serialize,
     {
-        self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
-            .form(body)
-            // .basic_auth(&self.test_user.username, Some(&self.test_user.password))
-            .send()
-            .await
-            .expect("Failed to post newsletter.")
+        self.post_admin_form("/admin/newsletters", body).await
+    }
+
+    pub async fn post_email_event(&self, body: &serde_json::Value, password: Option<&str>) -> reqwest::Response {
+        let mut request = self.api_client
+            .post(format!("{}/webhooks/email-events", &self.address))
+            .json(body);
+        if let Some(password) = password {
+            request = request.basic_auth("postmark", Some(password));
+        }
+        request.send().await.expect("Failed to execute request.")
     }
 
     pub async fn get_send_newsletters(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -100,7 +158,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
@@ -117,9 +175,23 @@ impl TestApp {
         self.post_login(&login_body).await
     }
 
+    /// Log in as a new user with `role` instead of the test user, who is an owner.
+    pub async fn login_with_role(&self, role: &str) -> TestUser {
+        let user = TestUser::generate();
+        user.store_with_role(&self.db_pool, role).await;
+        let login_body = serde_json::json!(
+            {
+                "username": &user.username,
+                "password": &user.password,
+            }
+        );
+        self.post_login(&login_body).await;
+        user
+    }
+
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -130,7 +202,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -149,17 +221,12 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.api_client
-            .post(&format!("{}/admin/password", &self.address))
-            .form(body)
-            .send()
-            .await
-            .expect("Failed to execute request.")
+        self.post_admin_form("/admin/password", body).await
     }
     
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -170,16 +237,358 @@ impl TestApp {
     
     }
 
-    pub async fn post_logout(&self) -> reqwest::Response {
+    pub async fn get_admin_subscribers_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .expect("Failed to get response text.")
+    }
+
+    pub async fn post_subscriber_tag<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/subscribers/tags", body).await
+    }
+
+    pub async fn post_subscriber_list<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/subscribers/lists", body).await
+    }
+
+    pub async fn get_admin_segments(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/segments", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_segments_html(&self) -> String {
+        self.get_admin_segments().await.text().await.unwrap()
+    }
+
+    pub async fn post_segment<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/segments", body).await
+    }
+
+    pub async fn get_admin_suppressions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_suppressions_html(&self) -> String {
+        self.get_admin_suppressions().await.text().await.unwrap()
+    }
+
+    pub async fn post_suppression(&self, email: &str) -> reqwest::Response {
+        self.post_admin_form("/admin/suppressions", &serde_json::json!({ "email": email })).await
+    }
+
+    pub async fn post_delete_suppression(&self, email: &str) -> reqwest::Response {
+        self.post_admin_form("/admin/suppressions/delete", &serde_json::json!({ "email": email })).await
+    }
+
+    pub async fn get_admin_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_users_html(&self) -> String {
+        self.get_admin_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_invitation(&self, email: &str, role: &str) -> reqwest::Response {
+        self.post_admin_form("/admin/users/invitations", &serde_json::json!({ "email": email, "role": role })).await
+    }
+
+    pub async fn post_user_action(&self, action: &str, user_id: &Uuid) -> reqwest::Response {
+        self.post_admin_form(&format!("/admin/users/{}", action), &serde_json::json!({ "user_id": user_id.to_string() })).await
+    }
+
+    pub async fn post_user_role(&self, user_id: &Uuid, role: &str) -> reqwest::Response {
+        self.post_admin_form("/admin/users/role", &serde_json::json!({ "user_id": user_id.to_string(), "role": role })).await
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/invitations/accept", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_newsletter_draft<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/newsletters/issues", body).await
+    }
+
+    pub async fn post_test_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/newsletters/issues/test", body).await
+    }
+
+    pub async fn get_newsletter_issues(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issue_report(&self, issue_id: &str, page: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters/issues/{}{}", &self.address, issue_id, page))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_issues_html(&self) -> String {
+        self.get_newsletter_issues().await.text().await.unwrap()
+    }
+
+    pub async fn get_edit_newsletter_issue(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters/issues/{}/edit", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_preview_html(&self, issue_id: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/newsletters/issues/{}/preview", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_delete_newsletter_draft(&self, issue_id: &str) -> reqwest::Response {
+        self.post_admin_form("/admin/newsletters/issues/delete", &serde_json::json!({ "issue_id": issue_id })).await
+    }
+
+    pub async fn post_schedule_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/newsletters/issues/schedule", body).await
+    }
+
+    pub async fn post_reschedule_newsletter<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.post_admin_form("/admin/newsletters/issues/reschedule", body).await
+    }
+
+    pub async fn post_unschedule_newsletter(&self, issue_id: &str) -> reqwest::Response {
+        self.post_admin_form("/admin/newsletters/issues/unschedule", &serde_json::json!({ "issue_id": issue_id })).await
+    }
+
+    /// Pause, resume or cancel the delivery of an issue.
+    pub async fn post_delivery_action(&self, action: &str, issue_id: &str) -> reqwest::Response {
+        self.post_admin_form(&format!("/admin/newsletters/issues/{}", action), &serde_json::json!({ "issue_id": issue_id })).await
+    }
+
+    /// Run the scheduler until no scheduled issue is due.
+    pub async fn publish_due_issues(&self) {
+        loop {
+            if let issue_scheduler::ExecutionOutcome::NoDueIssue =
+                try_publish_due_issue(&self.db_pool).await.unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Run the delivery worker until no delivery task is ready to be executed.
+    pub async fn dispatch_all_pending_emails(&self) {
+        let mut issues = PreparedIssueCache::default();
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_execute_task(&self.db_pool, &self.email_client, &self.base_url, &self.hmac_secret, &mut issues)
+                    .await
+                    .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    /// The code `user_id`'s authenticator shows `offset` time steps from now.
+    pub async fn totp_code(&self, user_id: Uuid, offset: i64) -> String {
+        let encrypted = sqlx::query!("SELECT totp_secret FROM users WHERE user_id = $1", user_id)
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .totp_secret
+            .expect("The user has no TOTP secret.");
+        let secret = decrypt_totp_secret(&encrypted, &self.totp_encryption_key).unwrap();
+        totp_code(&secret, current_time_step().checked_add_signed(offset).unwrap())
+    }
+
+    /// The code of the last time step a TOTP code of the user was accepted for.
+    pub async fn last_accepted_totp_code(&self, user_id: Uuid) -> String {
+        let row = sqlx::query!(
+            "SELECT totp_secret, totp_last_time_step FROM users WHERE user_id = $1",
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .unwrap();
+        let encrypted = row.totp_secret.expect("The user has no TOTP secret.");
+        let time_step = row.totp_last_time_step.expect("No TOTP code has been accepted yet.");
+        let secret = decrypt_totp_secret(&encrypted, &self.totp_encryption_key).unwrap();
+        totp_code(&secret, time_step as u64)
+    }
+
+    pub async fn get_totp_settings_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/totp", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_totp(&self, action: &str, code: &str) -> reqwest::Response {
+        self.post_admin_form(&format!("/admin/totp/{}", action), &serde_json::json!({ "code": code })).await
+    }
+
+    pub async fn post_login_totp(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/totp", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request(&self, username: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(&serde_json::json!({ "username": username }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/new", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// The CSRF token of the session the test client opened last, if it is logged in.
+    pub async fn csrf_token(&self) -> Option<String> {
+        let row = sqlx::query!(
+            "SELECT session_id FROM user_sessions WHERE revoked_at IS NULL ORDER BY created_at DESC LIMIT 1"
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .unwrap();
+        row.map(|r| csrf_token(r.session_id, &self.hmac_secret).as_ref().to_owned())
+    }
+
+    /// Post `body` to an admin form, along with the CSRF token a browser would submit.
+    pub async fn post_admin_form<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut form = serde_json::to_value(body).unwrap();
+        if let Some(csrf_token) = self.csrf_token().await {
+            form["csrf_token"] = csrf_token.into();
+        }
+        self.api_client
+            .post(format!("{}{}", &self.address, path))
+            .form(&form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_sessions(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_sessions_html(&self) -> String {
+        self.get_admin_sessions().await.text().await.unwrap()
+    }
+
+    pub async fn post_revoke_session(&self, session_id: &Uuid) -> reqwest::Response {
+        self.post_admin_form("/admin/sessions/revoke", &serde_json::json!({ "session_id": session_id.to_string() })).await
+    }
+
+    pub async fn post_revoke_all_sessions(&self) -> reqwest::Response {
+        self.post_admin_form("/admin/sessions/revoke-all", &serde_json::json!({})).await
+    }
+
+    /// Log the test user in from another device, with a client of its own.
+    pub async fn login_on_another_device(&self) -> reqwest::Client {
+        let client = api_client();
+        client
+            .post(format!("{}/login", &self.address))
+            .form(&serde_json::json!({
+                "username": &self.test_user.username,
+                "password": &self.test_user.password,
+            }))
             .send()
             .await
-            .expect("Failed ot execute request")
+            .expect("Failed to execute request.");
+        client
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.post_admin_form("/admin/logout", &serde_json::json!({})).await
     }
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Like `spawn_app`, with `customize` applied to the configuration.
+/// Owners are not required to use two-factor authentication unless `customize` says so.
+pub async fn spawn_app_with(customize: impl FnOnce(&mut Settings)) -> TestApp {
     
     Lazy::force(&TRACING);
     
@@ -192,6 +601,8 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string(); 
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        c.totp.required_for_owners = false;
+        customize(&mut c);
         c
     };
     
@@ -204,26 +615,35 @@ pub async fn spawn_app() -> TestApp {
         .expect("Failed to build server.");
     let application_port = application.port();
     let address = format!("http://localhost:{}", application_port);
-    let _ = tokio::spawn(application.run_until_stopped());
+    tokio::spawn(application.run_until_stopped());
     
-    let api_client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
+    let api_client = api_client();
 
     let test_app = TestApp {
-        address: address,
+        address,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
         port: application_port,
         test_user: TestUser::generate(),
         api_client,
+        email_client: configuration.email_client.client(get_connection_pool(&configuration.database)),
+        base_url: configuration.application.base_url,
+        hmac_secret: HmacSecret(configuration.application.hmac_secret),
+        webhook_secret: configuration.application.webhook_secret.expose_secret().clone(),
+        totp_encryption_key: configuration.totp.encryption_key,
     };
     test_app.test_user.store(&test_app.db_pool).await;
     test_app
 }
 
+fn api_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap()
+}
+
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
     let mut connection = PgConnection::connect_with(
         &config.without_db()
@@ -248,6 +668,7 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
+    pub email: String,
     pub password: String,
 }
 
@@ -256,11 +677,16 @@ impl TestUser {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
             password: Uuid::new_v4().to_string(),
         }
     }
 
     async fn store(&self, db_pool: &PgPool) {
+        self.store_with_role(db_pool, "owner").await
+    }
+
+    async fn store_with_role(&self, db_pool: &PgPool, role: &str) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
             Algorithm::Argon2id,
@@ -271,10 +697,12 @@ impl TestUser {
             .unwrap()
             .to_string();
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, email, password_hash, role) VALUES ($1, $2, $3, $4, $5)",
             self.user_id,
             self.username,
+            self.email,
             password_hash,
+            role,
         )
         .execute(db_pool)
         .await
@@ -1,4 +1,4 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -53,4 +53,301 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     
     // Assert - Part 2
     assert!(html.contains(&format!("Welcome, {}", test_app.test_user.username)));    
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn repeated_failures_lock_the_username_out() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let wrong_password = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": "wrong_password",
+    });
+    for _ in 0..4 {
+        test_app.post_login(&wrong_password).await;
+    }
+
+    // Act
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html = test_app.get_login_html().await;
+    assert!(html.contains("Too many failed login attempts, try again in"));
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_locked_out_like_existing_ones() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": "unknown_username",
+        "password": "wrong_password",
+    });
+    for _ in 0..3 {
+        test_app.post_login(&login_body).await;
+    }
+
+    // Act
+    let response = test_app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html = test_app.get_login_html().await;
+    assert!(html.contains("Too many failed login attempts, try again in 1 second."));
+}
+
+#[tokio::test]
+async fn a_lockout_is_recorded_after_too_many_failures() {
+    // Arrange
+    let test_app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO failed_logins (scope, key, failures, last_failure_at) VALUES ('username_ip', $1, 9, now())",
+        format!("127.0.0.1/{}", test_app.test_user.username),
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "wrong_password",
+        }))
+        .await;
+
+    // Assert
+    let html = test_app.get_login_html().await;
+    assert!(html.contains("Too many failed login attempts, try again in 15 minutes."));
+    let lockout = sqlx::query!("SELECT scope, key, failures FROM login_lockouts")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(lockout.scope, "username_ip");
+    assert_eq!(lockout.key, format!("127.0.0.1/{}", test_app.test_user.username));
+    assert_eq!(lockout.failures, 10);
+}
+
+#[tokio::test]
+async fn failures_from_another_address_do_not_lock_the_user_out() {
+    // Arrange
+    let test_app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO failed_logins (scope, key, failures, last_failure_at, locked_until) \
+        VALUES ('username_ip', $1, 10, now(), now() + interval '15 minutes')",
+        format!("203.0.113.7/{}", test_app.test_user.username),
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = test_app.valid_login().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn failures_spread_over_many_addresses_lock_the_username_out() {
+    // Arrange
+    let test_app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO failed_logins (scope, key, failures, last_failure_at) VALUES ('username', $1, 49, now())",
+        &test_app.test_user.username,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "wrong_password",
+        }))
+        .await;
+
+    // Assert
+    let html = test_app.get_login_html().await;
+    assert!(html.contains("Too many failed login attempts, try again in 15 minutes."));
+    let lockout = sqlx::query!("SELECT scope, key, failures FROM login_lockouts")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(lockout.scope, "username");
+    assert_eq!(lockout.key, test_app.test_user.username);
+    assert_eq!(lockout.failures, 50);
+}
+
+#[tokio::test]
+async fn locked_out_attempts_are_not_counted() {
+    // Arrange
+    let test_app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO failed_logins (scope, key, failures, last_failure_at, locked_until) \
+        VALUES ('username_ip', $1, 5, now(), now() + interval '1 minute')",
+        format!("127.0.0.1/{}", test_app.test_user.username),
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = test_app.valid_login().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let failures = sqlx::query!("SELECT scope, failures FROM failed_logins ORDER BY scope")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].scope, "username_ip");
+    assert_eq!(failures[0].failures, 5);
+}
+
+async fn ip_failures(test_app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT key FROM failed_logins WHERE scope = 'ip'")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.key)
+        .collect()
+}
+
+async fn post_forwarded_login(test_app: &TestApp, forwarded_for: &str) {
+    test_app
+        .api_client
+        .post(format!("{}/login", &test_app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .form(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "wrong_password",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+}
+
+#[tokio::test]
+async fn forwarded_addresses_are_ignored_from_untrusted_peers() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    post_forwarded_login(&test_app, "198.51.100.1").await;
+
+    // Assert
+    assert_eq!(ip_failures(&test_app).await, vec!["127.0.0.1"]);
+}
+
+#[tokio::test]
+async fn forwarded_addresses_are_believed_from_trusted_proxies() {
+    // Arrange
+    let test_app = spawn_app_with(|c| c.application.trusted_proxies = vec!["127.0.0.1".parse().unwrap()]).await;
+
+    // Act
+    post_forwarded_login(&test_app, "198.51.100.1").await;
+
+    // Assert
+    assert_eq!(ip_failures(&test_app).await, vec!["198.51.100.1"]);
+}
+
+#[tokio::test]
+async fn a_successful_login_clears_previous_failures() {
+    // Arrange
+    let test_app = spawn_app().await;
+    for _ in 0..3 {
+        test_app
+            .post_login(&serde_json::json!({
+                "username": &test_app.test_user.username,
+                "password": "wrong_password",
+            }))
+            .await;
+    }
+    test_app.valid_login().await;
+    test_app.post_logout().await;
+
+    // Act
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "wrong_password",
+        }))
+        .await;
+
+    // Assert
+    let html = test_app.get_login_html().await;
+    assert!(html.contains("<p><i>Authentication failed</i></p>"));
+}
+
+async fn stored_password_hash(test_app: &TestApp) -> String {
+    sqlx::query!("SELECT password_hash FROM users WHERE user_id = $1", test_app.test_user.user_id)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .password_hash
+}
+
+#[tokio::test]
+async fn an_outdated_password_hash_is_upgraded_on_login() {
+    // Arrange
+    let test_app = spawn_app_with(|c| c.password_hashing.iterations = 3).await;
+    let outdated_password_hash = stored_password_hash(&test_app).await;
+    assert!(outdated_password_hash.contains("m=15000,t=2,p=1"));
+
+    // Act - Part 1 - Log in
+    let response = test_app.valid_login().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let upgraded_password_hash = stored_password_hash(&test_app).await;
+    assert!(upgraded_password_hash.starts_with("$argon2id$v=19$m=15000,t=3,p=1$"));
+
+    // Act - Part 2 - Log in again with the upgraded hash
+    test_app.post_logout().await;
+    let response = test_app.valid_login().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(stored_password_hash(&test_app).await, upgraded_password_hash);
+}
+
+#[tokio::test]
+async fn an_up_to_date_password_hash_is_left_unchanged() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let password_hash = stored_password_hash(&test_app).await;
+
+    // Act
+    test_app.valid_login().await;
+
+    // Assert
+    assert_eq!(stored_password_hash(&test_app).await, password_hash);
+}
+
+#[tokio::test]
+async fn a_failed_login_does_not_upgrade_the_password_hash() {
+    // Arrange
+    let test_app = spawn_app_with(|c| c.password_hashing.iterations = 3).await;
+    let password_hash = stored_password_hash(&test_app).await;
+
+    // Act
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "wrong_password",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(stored_password_hash(&test_app).await, password_hash);
+}
@@ -0,0 +1,151 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+/// Turn on two-factor authentication for the logged-in test user and return their recovery codes.
+async fn enable_totp(test_app: &TestApp) -> Vec<String> {
+    let response = test_app.post_totp("enroll", "").await;
+    assert_is_redirect_to(&response, "/admin/totp");
+    let html_page = test_app.get_totp_settings_html().await;
+    assert!(html_page.contains("otpauth://totp/Newsletter:"));
+
+    let code = test_app.totp_code(test_app.test_user.user_id, 0).await;
+    let response = test_app.post_totp("confirm", &code).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s.split("</code>").next().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn a_totp_code_is_required_after_the_password_once_enabled() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let recovery_codes = enable_totp(&test_app).await;
+    assert_eq!(recovery_codes.len(), 10);
+    test_app.post_logout().await;
+
+    // Act - Part 1 - Password
+    let response = test_app.valid_login().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/totp");
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Code
+    let code = test_app.totp_code(test_app.test_user.user_id, 1).await;
+    let response = test_app.post_login_totp(&code).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let response = test_app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn invalid_and_replayed_totp_codes_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    enable_totp(&test_app).await;
+    test_app.post_logout().await;
+    test_app.valid_login().await;
+
+    // Act - Part 1 - Invalid code
+    let response = test_app.post_login_totp("000000x").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/totp");
+
+    // Act - Part 2 - The code used to enroll
+    let code = test_app.last_accepted_totp_code(test_app.test_user.user_id).await;
+    let response = test_app.post_login_totp(&code).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/totp");
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_recovery_code_can_only_be_used_once() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let recovery_codes = enable_totp(&test_app).await;
+    test_app.post_logout().await;
+
+    // Act - Part 1
+    test_app.valid_login().await;
+    let response = test_app.post_login_totp(&recovery_codes[0].to_uppercase()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Act - Part 2
+    test_app.post_logout().await;
+    test_app.valid_login().await;
+    let response = test_app.post_login_totp(&recovery_codes[0]).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/totp");
+}
+
+#[tokio::test]
+async fn the_second_step_requires_a_valid_password_first() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = test_app.post_login_totp("123456").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn owners_must_set_up_two_factor_authentication_when_required() {
+    // Arrange
+    let test_app = spawn_app_with(|c| c.totp.required_for_owners = true).await;
+    test_app.valid_login().await;
+
+    // Act - Part 1
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/totp");
+    let html_page = test_app.get_totp_settings_html().await;
+    assert!(html_page.contains("Owners must set up two-factor authentication."));
+
+    // Act - Part 2
+    enable_totp(&test_app).await;
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = test_app.get_totp_settings_html().await;
+    assert!(html_page.contains("It is required for owners."));
+}
+
+#[tokio::test]
+async fn two_factor_authentication_can_be_turned_off_with_a_code() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    enable_totp(&test_app).await;
+
+    // Act
+    let code = test_app.totp_code(test_app.test_user.user_id, 1).await;
+    let response = test_app.post_totp("disable", &code).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/totp");
+    let html_page = test_app.get_totp_settings_html().await;
+    assert!(html_page.contains("Two-factor authentication has been turned off."));
+    test_app.post_logout().await;
+    let response = test_app.valid_login().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
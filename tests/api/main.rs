@@ -2,7 +2,21 @@ mod helpers;
 mod health_check;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
 mod admin_newsletters;
 mod login;
 mod admin_dashboard;
-mod admin_change_password;
\ No newline at end of file
+mod admin_change_password;
+mod admin_segments;
+mod admin_newsletter_issues;
+mod admin_newsletter_schedule;
+mod admin_newsletter_delivery;
+mod newsletter_tracking;
+mod email_events;
+mod admin_suppressions;
+mod admin_users;
+mod admin_roles;
+mod login_totp;
+mod password_reset;
+mod admin_sessions;
+mod admin_csrf;
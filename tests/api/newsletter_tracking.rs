@@ -0,0 +1,231 @@
+use reqwest::Url;
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp};
+
+#[tokio::test]
+async fn opens_are_recorded_when_tracking_is_enabled() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    let html = publish_and_deliver(&test_app, true).await;
+    let pixel_urls = tracking_urls(&test_app, &html, "/o/");
+    assert_eq!(pixel_urls.len(), 1);
+
+    // Act
+    for user_agent in ["Thunderbird", "Apple Mail"] {
+        let response = reqwest::Client::new()
+            .get(pixel_urls[0].clone())
+            .header("User-Agent", user_agent)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["Content-Type"], "image/gif");
+    }
+
+    // Assert
+    let user_agents: Vec<Option<String>> = sqlx::query!("SELECT user_agent FROM issue_opens ORDER BY opened_at")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.user_agent)
+        .collect();
+    assert_eq!(user_agents, vec![Some("Thunderbird".to_string()), Some("Apple Mail".to_string())]);
+    let html_page = get_report_html(&test_app).await;
+    assert!(html_page.contains("<tr><th>Unique opens</th><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><th>Total opens</th><td>2</td></tr>"));
+}
+
+#[tokio::test]
+async fn long_user_agents_are_truncated() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    let html = publish_and_deliver(&test_app, true).await;
+    let pixel_urls = tracking_urls(&test_app, &html, "/o/");
+
+    // Act
+    let response = reqwest::Client::new()
+        .get(pixel_urls[0].clone())
+        .header("User-Agent", "a".repeat(2000))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT user_agent FROM issue_opens")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.user_agent, Some("a".repeat(512)));
+}
+
+#[tokio::test]
+async fn no_pixel_is_added_when_tracking_is_disabled() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+
+    // Act
+    let html = publish_and_deliver(&test_app, false).await;
+
+    // Assert
+    assert!(tracking_urls(&test_app, &html, "/o/").is_empty());
+    let html_page = get_report_html(&test_app).await;
+    assert!(html_page.contains("Opens are not tracked for this issue."));
+}
+
+#[tokio::test]
+async fn unknown_pixel_tokens_are_served_but_not_recorded() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/o/unknown", test_app.address)).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+    let opens = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_opens")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(opens.count, 0);
+}
+
+#[tokio::test]
+async fn clicks_are_recorded_and_redirected_to_the_original_link() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    let html = publish_and_deliver(&test_app, false).await;
+    let click_urls = tracking_urls(&test_app, &html, "/r/");
+    assert_eq!(click_urls.len(), 1, "Only the external link should be rewritten.");
+
+    // Act
+    let response = test_app.api_client.get(click_urls[0].clone()).send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(response.headers()["Location"], "https://example.com/post?id=1&ref=mail");
+    let html_page = get_report_html(&test_app).await;
+    assert!(html_page.contains("<tr><td>https://example.com/post?id=1&amp;ref=mail</td><td>1</td><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn click_tokens_with_an_invalid_signature_are_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    let html = publish_and_deliver(&test_app, false).await;
+    let mut click_url = tracking_urls(&test_app, &html, "/r/").pop().unwrap();
+    let token = click_url.path().trim_start_matches("/r/").to_owned();
+    let (payload, _) = token.rsplit_once('.').unwrap();
+    click_url.set_path(&format!("/r/{}.{}", payload, "00".repeat(32)));
+
+    // Act
+    let response = test_app.api_client.get(click_url).send().await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    let clicks = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_clicks")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(clicks.count, 0);
+}
+
+#[tokio::test]
+async fn utm_parameters_are_added_to_external_links_when_enabled() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.create_confirmed_subscriber("ursula_le_guin@gmail.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    test_app.post_newsletter(&serde_json::json!({
+        "title": "Weekly digest",
+        "content_html": "<a href=\"https://example.com/post?id=1\">Read</a><a href=\"mailto:team@example.com\">Reply</a><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Read https://example.com/post?id=1 - unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "add_utm_parameters": true,
+        "utm_source": "newsletter",
+        "utm_medium": "email",
+        "utm_campaign": "weekly digest",
+    }))
+    .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = test_app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let tagged_url = "https://example.com/post?id=1&utm_source=newsletter&utm_medium=email&utm_campaign=weekly%20digest";
+    let text = body["TextBody"].as_str().unwrap();
+    assert!(text.contains(tagged_url));
+    assert_eq!(text.matches("utm_source").count(), 1, "The unsubscribe link must not be tagged.");
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.contains("href=\"mailto:team@example.com\""));
+    let click_url = tracking_urls(&test_app, html, "/r/").pop().unwrap();
+    let response = test_app.api_client.get(click_url).send().await.unwrap();
+    assert_eq!(response.headers()["Location"], tagged_url);
+}
+
+/// Publish an issue to the confirmed subscriber and return the HTML body they received.
+async fn publish_and_deliver(app: &TestApp, track_opens: bool) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_newsletter(&serde_json::json!({
+        "title": "Weekly digest",
+        "content_html": "<p>Hello {{ name }}</p><a href=\"https://example.com/post?id=1&ref=mail\">Read</a><a href=\"{{ unsubscribe_url }}\">Unsubscribe</a>",
+        "content_text": "Hello {{ name }} - unsubscribe: {{ unsubscribe_url }}",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+        "track_opens": track_opens,
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+    let email_request = app.email_server.received_requests().await.unwrap().pop().unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["HtmlBody"].as_str().unwrap().to_owned()
+}
+
+/// The links in `html` pointing at the application under `prefix`.
+fn tracking_urls(app: &TestApp, html: &str, prefix: &str) -> Vec<Url> {
+    linkify::LinkFinder::new()
+        .links(html)
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        .map(|l| Url::parse(l.as_str()).unwrap())
+        .filter(|url| url.host_str() == Some("127.0.0.1") && url.path().starts_with(prefix))
+        .map(|mut url| {
+            url.set_port(Some(app.port)).unwrap();
+            url
+        })
+        .collect()
+}
+
+async fn get_report_html(app: &TestApp) -> String {
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    app.get_issue_report(&issue_id.to_string(), "").await.text().await.unwrap()
+}
+
@@ -0,0 +1,183 @@
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// Ask for a reset link for the test user and return the token it contains.
+async fn request_reset_token(test_app: &TestApp) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Send password reset link")
+        .expect(1)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+    let response = test_app.post_password_reset_request(&test_app.test_user.username).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let email_request = wait_for_reset_email(test_app).await;
+    let links = test_app.get_confirmation_links(&email_request);
+    assert_eq!(links.html.path(), "/password-reset/new");
+    links
+        .html
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .map(|(_, v)| v.into_owned())
+        .unwrap()
+}
+
+/// The link is emailed in the background, after the response has been sent.
+async fn wait_for_reset_email(test_app: &TestApp) -> wiremock::Request {
+    for _ in 0..50 {
+        if let Some(request) = test_app.email_server.received_requests().await.unwrap().pop() {
+            return request;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("No password reset email was sent.");
+}
+
+fn reset_form(token: &str, password: &str) -> serde_json::Value {
+    serde_json::json!({
+        "token": token,
+        "new_password": password,
+        "new_password_check": password,
+    })
+}
+
+#[tokio::test]
+async fn a_forgotten_password_can_be_reset_from_the_emailed_link() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let token = request_reset_token(&test_app).await;
+
+    // Act - Part 1 - Follow the link
+    let response = test_app
+        .api_client
+        .get(format!("{}/password-reset/new?token={}", test_app.address, token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 2 - Pick a new password
+    let response = test_app.post_password_reset(&reset_form(&token, "a-brand-new-password")).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("Your password has been reset, you can now log in."));
+    let response = test_app.valid_login().await;
+    assert_is_redirect_to(&response, "/login");
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "a-brand-new-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn unknown_usernames_get_the_same_response_and_no_email() {
+    // Arrange
+    let test_app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_password_reset_request("nobody").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login_html().await;
+    assert!(html_page.contains("If this account exists and has an email address, we have sent it a link"));
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let token = request_reset_token(&test_app).await;
+    test_app.post_password_reset(&reset_form(&token, "a-brand-new-password")).await;
+
+    // Act
+    let response = test_app.post_password_reset(&reset_form(&token, "yet-another-password")).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn a_tampered_token_is_rejected() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let token = request_reset_token(&test_app).await;
+    let (payload, _) = token.rsplit_once('.').unwrap();
+    let tampered = format!("{}.{}", payload, "00".repeat(32));
+
+    // Act
+    let response = test_app.post_password_reset(&reset_form(&tampered, "a-brand-new-password")).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn new_passwords_must_follow_the_password_policy() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let token = request_reset_token(&test_app).await;
+
+    // Act
+    let response = test_app.post_password_reset(&reset_form(&token, "short")).await;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/password-reset/new?token={}", token));
+    let response = test_app.valid_login().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn resetting_a_password_logs_the_user_out_everywhere() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    let token = request_reset_token(&test_app).await;
+
+    // Act
+    test_app.post_password_reset(&reset_form(&token, "a-brand-new-password")).await;
+
+    // Assert
+    let response = test_app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn reset_requests_are_rate_limited_per_username() {
+    // Arrange
+    let test_app = spawn_app().await;
+    for _ in 0..3 {
+        let response = test_app.post_password_reset_request("nobody").await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // Act
+    let response = test_app.post_password_reset_request("nobody").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    let html_page = test_app
+        .api_client
+        .get(format!("{}/password-reset", test_app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Too many password reset requests, try again later."));
+}
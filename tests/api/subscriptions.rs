@@ -143,4 +143,63 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     
     // Assert
     assert_eq!(response.status().as_u16(), 500);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn subscribe_accepts_json_with_custom_fields() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_custom_field(&serde_json::json!({
+        "name": "age",
+        "field_type": "number",
+    })).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_subscriptions_json(&serde_json::json!({
+        "name": "john doe",
+        "email": "john_doe@mail.com",
+        "age": 42,
+    })).await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT value FROM subscriber_custom_fields")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved custom field.");
+    assert_eq!(saved.value, "42");
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_when_custom_fields_are_invalid() {
+    // Arrange
+    let test_app = spawn_app().await;
+    test_app.valid_login().await;
+    test_app.post_custom_field(&serde_json::json!({
+        "name": "birthday",
+        "field_type": "date",
+        "required": true,
+    })).await;
+    let test_cases = vec![
+        ("name=john&email=john%40example.com", "'birthday' is required."),
+        ("name=john&email=john%40example.com&birthday=yesterday", "'birthday' must be a date in the YYYY-MM-DD format."),
+        ("name=john&email=john%40example.com&birthday=2000-01-01&shoe_size=42", "'shoe_size' is not a known field."),
+    ];
+
+    for (body, error) in test_cases {
+        // Act
+        let response = test_app.post_subscriptions(body.into()).await;
+
+        // Assert
+        assert_eq!(400, response.status().as_u16(), "The API did not fail with 400 Bad Request when payload was {body}.");
+        assert_eq!(response.text().await.unwrap(), error);
+    }
+}
@@ -0,0 +1,121 @@
+use wiremock::{matchers::{method, path}, Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn create_subscriber(test_app: &TestApp) -> String {
+    let body = "name=john%20doe&email=john_doe%40mail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_subscriptions(body.into()).await;
+    sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token
+}
+
+async fn subscription_status(test_app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+async fn post_unsubscribe(test_app: &TestApp, token: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/subscriptions/unsubscribe", test_app.address))
+        .form(&serde_json::json!({ "unsubscribe_token": token }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_unsubscribe_page_asks_for_confirmation_without_unsubscribing() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let token = create_subscriber(&test_app).await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+        test_app.address, token
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"<form action="/subscriptions/unsubscribe" method="post">"#));
+    assert!(html_page.contains(&format!(r#"name="unsubscribe_token" value="{}""#, token)));
+    assert_eq!(subscription_status(&test_app).await, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn the_unsubscribe_page_returns_a_404_for_an_unknown_token() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token=unknown",
+        test_app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn unsubscribing_with_an_unknown_token_is_rejected_with_a_404() {
+    // Arrange
+    let test_app = spawn_app().await;
+
+    // Act
+    let response = post_unsubscribe(&test_app, "unknown").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_confirmation_token_cannot_be_used_to_unsubscribe() {
+    // Arrange
+    let test_app = spawn_app().await;
+    create_subscriber(&test_app).await;
+    let confirmation_token = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .subscription_token;
+
+    // Act
+    let response = post_unsubscribe(&test_app, &confirmation_token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(subscription_status(&test_app).await, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn unsubscribing_marks_the_subscriber_as_unsubscribed() {
+    // Arrange
+    let test_app = spawn_app().await;
+    let token = create_subscriber(&test_app).await;
+
+    // Act
+    let response = post_unsubscribe(&test_app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscription_status(&test_app).await, "unsubscribed");
+}